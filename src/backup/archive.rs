@@ -0,0 +1,331 @@
+use crate::backup::manifest::{sha256_reader, IntegrityReport, Manifest, ManifestEntry};
+use crate::backup::{MANIFEST_FILE, METADATA_FILE};
+use crate::error::{Result, SupamigrateError};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// File extension of single-file backups
+pub const ARCHIVE_EXTENSION: &str = "tar.gz";
+
+/// Top-level directory holding storage objects; streamed last and never buffered
+const STORAGE_PREFIX: &str = "storage/";
+
+/// Largest file kept in memory when opening an archive; bigger ones (usually the
+/// database dump) are read from the archive again when needed
+const BUFFER_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// Streaming access to a `.tar.gz` backup.
+/// Opening it reads the archive once: every entry is hashed for the integrity check and
+/// small files outside `storage/` are kept in memory. Larger files are read again on
+/// demand, and storage objects are streamed through [`ArchiveReader::entries`].
+pub struct ArchiveReader {
+    path: PathBuf,
+    manifest: Option<Manifest>,
+    entries: Vec<ManifestEntry>,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+/// Whether `path` looks like a single-file backup
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file()
+        && path.file_name().is_some_and(|n| {
+            n.to_string_lossy()
+                .ends_with(&format!(".{}", ARCHIVE_EXTENSION))
+        })
+}
+
+/// Pack a backup directory (with its manifest) into a single `.tar.gz`
+pub fn create_archive(dir: &Path, archive_path: &Path) -> Result<()> {
+    let manifest = Manifest::load(dir)?
+        .ok_or_else(|| SupamigrateError::InvalidBackup(format!("{} not found", MANIFEST_FILE)))?;
+
+    let file = fs::File::create(archive_path)?;
+    let encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+
+    for path in archive_order(&manifest) {
+        builder.append_path_with_name(dir.join(&path), &path)?;
+    }
+
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(())
+}
+
+/// Manifest first, then metadata, then everything else with storage objects last,
+/// so restore can read all small files before reaching the bulk of the archive
+fn archive_order(manifest: &Manifest) -> Vec<String> {
+    let rank = |path: &str| match path {
+        MANIFEST_FILE => 0,
+        METADATA_FILE => 1,
+        p if p.starts_with(STORAGE_PREFIX) => 3,
+        _ => 2,
+    };
+
+    let mut paths: Vec<String> = manifest.files.iter().map(|e| e.path.clone()).collect();
+    paths.push(MANIFEST_FILE.to_string());
+    paths.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+    paths
+}
+
+impl ArchiveReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = Self {
+            path: path.to_path_buf(),
+            manifest: None,
+            entries: Vec::new(),
+            files: BTreeMap::new(),
+        };
+
+        let mut archive = Self::entries(path)?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry.path()?.to_string_lossy().to_string();
+
+            let buffered = name == MANIFEST_FILE
+                || (!name.starts_with(STORAGE_PREFIX) && entry.header().size()? <= BUFFER_LIMIT);
+            let (sha256, size) = if !buffered {
+                sha256_reader(&mut entry)?
+            } else {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content)?;
+                let hash = sha256_reader(&mut content.as_slice())?;
+                if name == MANIFEST_FILE {
+                    reader.manifest =
+                        Some(Manifest::from_json(&String::from_utf8_lossy(&content))?);
+                } else {
+                    reader.files.insert(name.clone(), content);
+                }
+                hash
+            };
+
+            if name != MANIFEST_FILE {
+                reader.entries.push(ManifestEntry {
+                    path: name,
+                    size,
+                    sha256,
+                });
+            }
+        }
+
+        Ok(reader)
+    }
+
+    /// Manifest and metadata of an archive without reading past them
+    /// (both are written before any other entry)
+    pub fn peek(path: &Path) -> Result<(Option<Manifest>, Option<Vec<u8>>)> {
+        let mut manifest = None;
+        let mut metadata = None;
+
+        let mut archive = Self::entries(path)?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            let mut content = Vec::new();
+            match name.as_str() {
+                MANIFEST_FILE => {
+                    entry.read_to_end(&mut content)?;
+                    manifest = Some(Manifest::from_json(&String::from_utf8_lossy(&content))?);
+                }
+                METADATA_FILE => {
+                    entry.read_to_end(&mut content)?;
+                    metadata = Some(content);
+                }
+                _ => break,
+            }
+        }
+
+        Ok((manifest, metadata))
+    }
+
+    /// A fresh pass over the raw archive entries
+    pub fn entries(path: &Path) -> Result<tar::Archive<GzDecoder<BufReader<fs::File>>>> {
+        let file = fs::File::open(path)?;
+        Ok(tar::Archive::new(GzDecoder::new(BufReader::new(file))))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Integrity of the archive contents, or `None` if it has no manifest
+    pub fn verify(&self) -> Option<IntegrityReport> {
+        self.manifest.as_ref().map(|m| m.check(&self.entries))
+    }
+
+    /// Contents of a file outside `storage/`
+    pub fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        if let Some(content) = self.files.get(path) {
+            return Ok(Some(content.clone()));
+        }
+        Ok(self.read_unbuffered(|p| p == path)?.pop().map(|(_, c)| c))
+    }
+
+    /// Files outside `storage/` below `prefix`, with paths relative to it
+    pub fn files_under(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let relative = |path: &str| {
+            path.strip_prefix(prefix)
+                .and_then(|p| p.strip_prefix('/'))
+                .map(String::from)
+        };
+
+        let mut files: Vec<(String, Vec<u8>)> = self
+            .files
+            .iter()
+            .filter_map(|(path, content)| relative(path).map(|p| (p, content.clone())))
+            .collect();
+        for (path, content) in self.read_unbuffered(|p| relative(p).is_some())? {
+            files.extend(relative(&path).map(|p| (p, content)));
+        }
+        Ok(files)
+    }
+
+    /// Read the files outside `storage/` matching `wanted` that were too large to keep
+    /// in memory, in one pass that stops once all of them are found
+    fn read_unbuffered(&self, wanted: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<u8>)>> {
+        let pending = self
+            .entries
+            .iter()
+            .filter(|e| {
+                wanted(&e.path)
+                    && !e.path.starts_with(STORAGE_PREFIX)
+                    && !self.files.contains_key(&e.path)
+            })
+            .count();
+
+        let mut files = Vec::new();
+        if pending == 0 {
+            return Ok(files);
+        }
+        let mut archive = Self::entries(&self.path)?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            if !wanted(&name) || name.starts_with(STORAGE_PREFIX) || self.files.contains_key(&name)
+            {
+                continue;
+            }
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.push((name, content));
+            if files.len() == pending {
+                break;
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Split `storage/<bucket>/<object path>` into bucket and object path
+pub fn storage_object(path: &str) -> Option<(&str, &str)> {
+    path.strip_prefix(STORAGE_PREFIX)?
+        .split_once('/')
+        .filter(|(bucket, object)| !bucket.is_empty() && !object.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup = dir.path().join("backup");
+        fs::create_dir_all(backup.join("storage/avatars/users")).unwrap();
+        fs::create_dir_all(backup.join("functions/hello")).unwrap();
+        fs::write(backup.join("metadata.json"), "{}").unwrap();
+        fs::write(backup.join("database.sql"), "SELECT 1;").unwrap();
+        fs::write(backup.join("functions/hello/index.ts"), "export {}").unwrap();
+        fs::write(backup.join("storage/avatars/users/a.png"), b"png").unwrap();
+        let manifest = Manifest::generate(&backup).unwrap();
+        manifest.write(&backup).unwrap();
+
+        assert_eq!(
+            archive_order(&manifest),
+            vec![
+                "manifest.json",
+                "metadata.json",
+                "database.sql",
+                "functions/hello/index.ts",
+                "storage/avatars/users/a.png",
+            ]
+        );
+
+        let archive = dir.path().join("backup.tar.gz");
+        create_archive(&backup, &archive).unwrap();
+        assert!(is_archive(&archive));
+        assert!(!is_archive(&backup));
+
+        let (peeked, metadata) = ArchiveReader::peek(&archive).unwrap();
+        assert_eq!(peeked.as_ref(), Some(&manifest));
+        assert_eq!(metadata, Some(b"{}".to_vec()));
+
+        let reader = ArchiveReader::open(&archive).unwrap();
+        assert_eq!(reader.manifest(), Some(&manifest));
+        assert!(reader.verify().unwrap().is_ok());
+        assert_eq!(
+            reader.read("database.sql").unwrap(),
+            Some(b"SELECT 1;".to_vec())
+        );
+        // Storage objects are streamed, not buffered
+        assert_eq!(reader.read("storage/avatars/users/a.png").unwrap(), None);
+
+        let functions = reader.files_under("functions").unwrap();
+        assert_eq!(
+            functions,
+            vec![("hello/index.ts".to_string(), b"export {}".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_archive_large_files_not_buffered() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup = dir.path().join("backup");
+        fs::create_dir_all(backup.join("functions/big")).unwrap();
+        let dump = vec![b'x'; BUFFER_LIMIT as usize + 1];
+        fs::write(backup.join("metadata.json"), "{}").unwrap();
+        fs::write(backup.join("database.sql"), &dump).unwrap();
+        fs::write(backup.join("functions/big/module.wasm"), &dump).unwrap();
+        fs::write(backup.join("functions/big/index.ts"), "export {}").unwrap();
+        Manifest::generate(&backup).unwrap().write(&backup).unwrap();
+        let archive = dir.path().join("backup.tar.gz");
+        create_archive(&backup, &archive).unwrap();
+
+        let reader = ArchiveReader::open(&archive).unwrap();
+        assert!(reader.verify().unwrap().is_ok());
+        assert!(!reader.files.contains_key("database.sql"));
+        assert_eq!(reader.read("database.sql").unwrap(), Some(dump.clone()));
+        assert_eq!(reader.read("missing.sql").unwrap(), None);
+
+        let mut functions = reader.files_under("functions").unwrap();
+        functions.sort();
+        assert_eq!(
+            functions,
+            vec![
+                ("big/index.ts".to_string(), b"export {}".to_vec()),
+                ("big/module.wasm".to_string(), dump),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_storage_object() {
+        assert_eq!(
+            storage_object("storage/avatars/users/a.png"),
+            Some(("avatars", "users/a.png"))
+        );
+        assert_eq!(storage_object("storage/avatars"), None);
+        assert_eq!(storage_object("functions/hello/index.ts"), None);
+    }
+}
@@ -0,0 +1,472 @@
+use crate::backup::archive::{is_archive, storage_object, ArchiveReader};
+use crate::backup::encryption::ENCRYPTED_EXTENSION;
+use crate::backup::manifest::{IntegrityReport, Manifest};
+use crate::backup::metadata::{BackupMetadata, METADATA_FILE, STORAGE_HASHES_FILE};
+use crate::backup::retention::{BackupDestination, BackupEntry};
+use crate::backup::source::{BackupLocation, BackupSource};
+use crate::db::{DumpToc, VaultBackup};
+use crate::error::Result;
+use crate::functions::secrets::SecretsBackup;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+
+/// One line of `backup list`
+pub struct BackupSummary {
+    pub entry: BackupEntry,
+    /// `None` if `metadata.json` is missing or was not fetched (remote archives)
+    pub metadata: Option<BackupMetadata>,
+    pub size: u64,
+    pub integrity: Integrity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrity {
+    /// Every file is present with the size listed in the manifest
+    SizesMatch,
+    /// Every file matches its manifest checksum
+    Verified,
+    Problems(usize),
+    NoManifest,
+    NotChecked,
+}
+
+/// Everything `backup inspect` shows and `backup diff` compares
+pub struct BackupContents {
+    pub metadata: BackupMetadata,
+    pub toc: DumpToc,
+    /// Bucket → object path → content hash
+    pub buckets: BTreeMap<String, BTreeMap<String, String>>,
+    /// Function slug → hash over all of its files
+    pub functions: BTreeMap<String, String>,
+    pub secrets: BTreeSet<String>,
+    pub vault_secrets: BTreeSet<String>,
+}
+
+/// A difference between two backups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+/// Changes from an older backup to a newer one
+#[derive(Debug, Default)]
+pub struct ContentsDiff {
+    pub schema: Vec<Change>,
+    /// Table, rows before and after
+    pub rows: Vec<(String, Option<u64>, Option<u64>)>,
+    pub storage: Vec<Change>,
+    pub functions: Vec<Change>,
+    pub secrets: Vec<Change>,
+    pub vault_secrets: Vec<Change>,
+}
+
+/// Summaries of the backups at `destination`, oldest first. `verify` hashes every
+/// file of local backups instead of only comparing sizes; remote backups are not checked.
+pub async fn list_backups(
+    destination: &BackupDestination,
+    project: Option<&str>,
+    verify: bool,
+) -> Result<Vec<BackupSummary>> {
+    let mut entries = destination.list(project).await?;
+    entries.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+
+    let mut summaries = Vec::new();
+    match destination {
+        BackupDestination::Local(dir) => {
+            for entry in entries {
+                summaries.push(summarize_local(&dir.join(&entry.name), entry, verify)?);
+            }
+        }
+        BackupDestination::S3 { client, url } => {
+            let objects = client.list(&url.bucket, &url.prefix).await?;
+            let staging = tempfile::tempdir()?;
+            for entry in entries {
+                let backup = url.join(&entry.name);
+                let prefix = format!("{}/", backup.prefix);
+                let size = objects
+                    .iter()
+                    .filter(|o| o.key == backup.prefix || o.key.starts_with(&prefix))
+                    .map(|o| o.size)
+                    .sum();
+
+                let metadata_key = format!("{}{}", prefix, METADATA_FILE);
+                let metadata = if objects.iter().any(|o| o.key == metadata_key) {
+                    let path = staging.path().join(&entry.name);
+                    client.get_file(&url.bucket, &metadata_key, &path).await?;
+                    serde_json::from_slice(&fs::read(&path)?).ok()
+                } else {
+                    None
+                };
+
+                summaries.push(BackupSummary {
+                    entry,
+                    metadata,
+                    size,
+                    integrity: Integrity::NotChecked,
+                });
+            }
+        }
+    }
+    Ok(summaries)
+}
+
+fn summarize_local(
+    path: &std::path::Path,
+    entry: BackupEntry,
+    verify: bool,
+) -> Result<BackupSummary> {
+    let (manifest, metadata, size, report) = if is_archive(path) {
+        let (manifest, metadata) = ArchiveReader::peek(path)?;
+        let report = if verify {
+            ArchiveReader::open(path)?.verify()
+        } else {
+            None
+        };
+        (manifest, metadata, fs::metadata(path)?.len(), report)
+    } else {
+        let manifest = Manifest::load(path)?;
+        let metadata = fs::read(path.join(METADATA_FILE)).ok();
+        let report = match (&manifest, verify) {
+            (Some(_), true) => BackupSource::open(path)?.verify()?,
+            (Some(manifest), false) => Some(manifest.verify_sizes(path)?),
+            (None, _) => None,
+        };
+        let size = match &manifest {
+            Some(manifest) => manifest.total_size(),
+            None => dir_size(path)?,
+        };
+        (manifest, metadata, size, report)
+    };
+
+    let integrity = match (manifest, report) {
+        (None, _) => Integrity::NoManifest,
+        (Some(_), None) => Integrity::NotChecked,
+        (Some(_), Some(report)) => Integrity::from_report(&report, verify),
+    };
+
+    Ok(BackupSummary {
+        entry,
+        metadata: metadata.and_then(|m| serde_json::from_slice(&m).ok()),
+        size,
+        integrity,
+    })
+}
+
+/// Total size of the files under `dir`, for backups written before manifests existed
+fn dir_size(dir: &std::path::Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        size += if entry.file_type()?.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            entry.metadata()?.len()
+        };
+    }
+    Ok(size)
+}
+
+impl Integrity {
+    fn from_report(report: &IntegrityReport, hashed: bool) -> Self {
+        match (report.is_ok(), hashed) {
+            (false, _) => Self::Problems(report.problems.len()),
+            (true, true) => Self::Verified,
+            (true, false) => Self::SizesMatch,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, Self::Problems(_))
+    }
+}
+
+impl fmt::Display for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SizesMatch => write!(f, "ok (sizes)"),
+            Self::Verified => write!(f, "ok (verified)"),
+            Self::Problems(count) => write!(f, "{} problems", count),
+            Self::NoManifest => write!(f, "no manifest"),
+            Self::NotChecked => write!(f, "not checked"),
+        }
+    }
+}
+
+impl BackupContents {
+    /// Read metadata, dump, storage listing, functions and secret names.
+    /// Encrypted backups need keys set on `source`.
+    pub fn load(source: &BackupSource) -> Result<Self> {
+        let metadata = source.metadata()?;
+        let toc = DumpToc::parse(&source.read_dump(&metadata)?);
+
+        let mut buckets: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        if let Some(index) = source.storage_index()? {
+            for object in index.objects {
+                buckets
+                    .entry(object.bucket)
+                    .or_default()
+                    .insert(object.path, object.sha256);
+            }
+        } else if let Some(hashes) = source.read_to_string(STORAGE_HASHES_FILE)? {
+            let hashes: BTreeMap<String, String> = serde_json::from_str(&hashes)?;
+            for (object, sha256) in hashes {
+                if let Some((bucket, path)) = object.split_once('/') {
+                    buckets
+                        .entry(bucket.to_string())
+                        .or_default()
+                        .insert(path.to_string(), sha256);
+                }
+            }
+        } else {
+            // Backups written before storage hashes were recorded; the manifest hash of
+            // an encrypted object is of its ciphertext, so those show up as changed
+            let manifest = match (source.manifest()?, source.location()) {
+                (Some(manifest), _) => Some(manifest),
+                (None, BackupLocation::Dir(dir)) => Some(Manifest::generate(dir)?),
+                (None, BackupLocation::Archive(_)) => None,
+            };
+            let suffix = format!(".{}", ENCRYPTED_EXTENSION);
+            for entry in manifest.iter().flat_map(|m| &m.files) {
+                if let Some((bucket, object)) = storage_object(&entry.path) {
+                    let object = object.strip_suffix(&suffix).unwrap_or(object);
+                    buckets
+                        .entry(bucket.to_string())
+                        .or_default()
+                        .insert(object.to_string(), entry.sha256.clone());
+                }
+            }
+        }
+
+        let mut function_files: BTreeMap<String, Sha256> = BTreeMap::new();
+        for (path, content) in source.files_under("functions")? {
+            let Some((slug, file)) = path.split_once('/') else {
+                continue;
+            };
+            let hasher = function_files.entry(slug.to_string()).or_default();
+            hasher.update(file.as_bytes());
+            hasher.update([0]);
+            hasher.update(&content);
+        }
+        let functions = function_files
+            .into_iter()
+            .map(|(slug, hasher)| (slug, hex::encode(hasher.finalize())))
+            .collect();
+
+        let secrets = match source.read_to_string("secrets.json")? {
+            Some(content) => serde_json::from_str::<SecretsBackup>(&content)?
+                .secrets
+                .into_iter()
+                .map(|s| s.name)
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        let vault_secrets = match source.read_to_string("vault_secrets.json")? {
+            Some(content) => serde_json::from_str::<VaultBackup>(&content)?
+                .secrets
+                .into_iter()
+                .map(|s| s.name)
+                .collect(),
+            None => BTreeSet::new(),
+        };
+
+        Ok(Self {
+            metadata,
+            toc,
+            buckets,
+            functions,
+            secrets,
+            vault_secrets,
+        })
+    }
+
+    /// Every storage object as `bucket/path` → content hash
+    fn storage_objects(&self) -> BTreeMap<String, String> {
+        self.buckets
+            .iter()
+            .flat_map(|(bucket, objects)| {
+                objects
+                    .iter()
+                    .map(move |(path, hash)| (format!("{}/{}", bucket, path), hash.clone()))
+            })
+            .collect()
+    }
+
+    /// What changed going from `self` to `newer`
+    pub fn diff(&self, newer: &Self) -> ContentsDiff {
+        let names = |set: &BTreeSet<String>| -> BTreeMap<String, String> {
+            set.iter().map(|n| (n.clone(), String::new())).collect()
+        };
+
+        let tables: BTreeSet<&String> = self.toc.rows.keys().chain(newer.toc.rows.keys()).collect();
+        let rows = tables
+            .into_iter()
+            .map(|t| {
+                (
+                    t.clone(),
+                    self.toc.rows.get(t).copied(),
+                    newer.toc.rows.get(t).copied(),
+                )
+            })
+            .filter(|(_, before, after)| before != after)
+            .collect();
+
+        ContentsDiff {
+            schema: diff_maps(&self.toc.objects, &newer.toc.objects),
+            rows,
+            storage: diff_maps(&self.storage_objects(), &newer.storage_objects()),
+            functions: diff_maps(&self.functions, &newer.functions),
+            secrets: diff_maps(&names(&self.secrets), &names(&newer.secrets)),
+            vault_secrets: diff_maps(&names(&self.vault_secrets), &names(&newer.vault_secrets)),
+        }
+    }
+}
+
+impl ContentsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.schema.is_empty()
+            && self.rows.is_empty()
+            && self.storage.is_empty()
+            && self.functions.is_empty()
+            && self.secrets.is_empty()
+            && self.vault_secrets.is_empty()
+    }
+}
+
+/// Keys added to, removed from or with a different value in `after`
+fn diff_maps(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> Vec<Change> {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| match (before.get(key), after.get(key)) {
+            (None, Some(_)) => Some(Change::Added(key.clone())),
+            (Some(_), None) => Some(Change::Removed(key.clone())),
+            (Some(a), Some(b)) if a != b => Some(Change::Changed(key.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_maps() {
+        let before = map(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let after = map(&[("b", "2"), ("c", "4"), ("d", "5")]);
+        assert_eq!(
+            diff_maps(&before, &after),
+            vec![
+                Change::Removed("a".to_string()),
+                Change::Changed("c".to_string()),
+                Change::Added("d".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_local_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup = dir.path().join("prod_20240115_120000");
+        fs::create_dir_all(&backup).unwrap();
+        fs::write(
+            backup.join(METADATA_FILE),
+            r#"{"project_ref":"abc","timestamp":"2024-01-15T12:00:00Z","schema_only":false,
+               "include_storage":true,"compressed":true}"#,
+        )
+        .unwrap();
+        fs::write(backup.join("database.sql.gz"), b"dump").unwrap();
+        Manifest::generate(&backup).unwrap().write(&backup).unwrap();
+        fs::create_dir_all(dir.path().join("staging_20240116_120000")).unwrap();
+
+        let destination = BackupDestination::Local(dir.path().to_path_buf());
+        let summaries = list_backups(&destination, None, false).await.unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].integrity, Integrity::SizesMatch);
+        assert_eq!(
+            summaries[0].metadata.as_ref().unwrap().components(),
+            vec!["database", "storage"]
+        );
+        assert_eq!(summaries[1].integrity, Integrity::NoManifest);
+
+        fs::write(backup.join("database.sql.gz"), b"dumped").unwrap();
+        let summaries = list_backups(&destination, Some("prod"), true)
+            .await
+            .unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].integrity, Integrity::Problems(1));
+    }
+
+    #[test]
+    fn test_diff_encrypted_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let identity = age::x25519::Identity::generate();
+        let identity_file = dir.path().join("backup.key");
+        {
+            use age::secrecy::ExposeSecret;
+            fs::write(&identity_file, identity.to_string().expose_secret()).unwrap();
+        }
+        let config = crate::config::EncryptionConfig {
+            identity_files: vec![identity_file.display().to_string()],
+            ..Default::default()
+        };
+        let encryption = || {
+            crate::backup::Encryption::from_configs([Some(&config)])
+                .unwrap()
+                .unwrap()
+        };
+
+        let write_backup = |name: &str, avatar: &[u8]| {
+            let backup = dir.path().join(name);
+            fs::create_dir_all(backup.join("storage/avatars")).unwrap();
+            fs::write(
+                backup.join(METADATA_FILE),
+                r#"{"project_ref":"abc","timestamp":"2024-01-15T12:00:00Z","schema_only":false,
+                   "include_storage":true,"compressed":false,"encrypted":true}"#,
+            )
+            .unwrap();
+            let encryption = encryption();
+            encryption
+                .write_file(&backup.join("database.sql"), b"")
+                .unwrap();
+            encryption
+                .write_file(&backup.join("storage/avatars/a.png"), avatar)
+                .unwrap();
+            let hashes = BTreeMap::from([(
+                "avatars/a.png".to_string(),
+                hex::encode(Sha256::digest(avatar)),
+            )]);
+            encryption
+                .write_file(
+                    &backup.join(STORAGE_HASHES_FILE),
+                    serde_json::to_string(&hashes).unwrap().as_bytes(),
+                )
+                .unwrap();
+
+            let mut source = BackupSource::open(&backup).unwrap();
+            source.set_encryption(Some(encryption));
+            BackupContents::load(&source).unwrap()
+        };
+
+        // Same contents encrypt to different ciphertexts
+        let first = write_backup("prod_20240115_120000", b"png");
+        let second = write_backup("prod_20240116_120000", b"png");
+        assert!(first.diff(&second).storage.is_empty());
+
+        let third = write_backup("prod_20240117_120000", b"new png");
+        assert_eq!(
+            second.diff(&third).storage,
+            vec![Change::Changed("avatars/a.png".to_string())]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::config::EncryptionConfig;
+use crate::error::{Result, SupamigrateError};
+use age::secrecy::SecretString;
+use std::fs;
+use std::io::{Read, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+
+/// Suffix appended to the name of every encrypted file
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
+/// Every age file starts with this line
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1";
+
+/// age keys for encrypting and decrypting backup files.
+/// Recipients take precedence over a passphrase when encrypting.
+pub struct Encryption {
+    recipients: Vec<Box<dyn age::Recipient + Send>>,
+    identities: Vec<Box<dyn age::Identity>>,
+    passphrase: Option<SecretString>,
+}
+
+impl Encryption {
+    /// Combined keys of the given configs; `None` if none of them is set
+    pub fn from_configs<'a>(
+        configs: impl IntoIterator<Item = Option<&'a EncryptionConfig>>,
+    ) -> Result<Option<Self>> {
+        let mut encryption = Self {
+            recipients: Vec::new(),
+            identities: Vec::new(),
+            passphrase: None,
+        };
+        let mut configured = false;
+
+        for config in configs.into_iter().flatten() {
+            configured = true;
+
+            for recipient in &config.recipients {
+                let recipient: age::x25519::Recipient = recipient.parse().map_err(|e| {
+                    SupamigrateError::Encryption(format!("invalid recipient {}: {}", recipient, e))
+                })?;
+                encryption.recipients.push(Box::new(recipient));
+            }
+
+            for path in &config.identity_files {
+                let path = shellexpand::tilde(path).to_string();
+                let file = age::IdentityFile::from_file(path.clone()).map_err(|e| {
+                    SupamigrateError::Encryption(format!(
+                        "cannot read identity file {}: {}",
+                        path, e
+                    ))
+                })?;
+                encryption
+                    .recipients
+                    .extend(file.to_recipients().map_err(encryption_error)?);
+                encryption
+                    .identities
+                    .extend(file.into_identities().map_err(encryption_error)?);
+            }
+
+            if let Some(var) = &config.passphrase_env {
+                let passphrase = std::env::var(var).map_err(|_| {
+                    SupamigrateError::Encryption(format!(
+                        "passphrase environment variable {} is not set",
+                        var
+                    ))
+                })?;
+                encryption.passphrase = Some(SecretString::from(passphrase));
+            }
+        }
+
+        Ok(configured.then_some(encryption))
+    }
+
+    /// Short description for plans, e.g. "age, 2 recipients"
+    pub fn describe(&self) -> String {
+        if self.recipients.is_empty() {
+            "age, passphrase".to_string()
+        } else {
+            format!("age, {} recipients", self.recipients.len())
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let encryptor = if self.recipients.is_empty() {
+            let passphrase = self.passphrase.clone().ok_or_else(|| {
+                SupamigrateError::Encryption("no recipients or passphrase configured".to_string())
+            })?;
+            age::Encryptor::with_user_passphrase(passphrase)
+        } else {
+            age::Encryptor::with_recipients(
+                self.recipients
+                    .iter()
+                    .map(|r| r.as_ref() as &dyn age::Recipient),
+            )
+            .map_err(encryption_error)?
+        };
+
+        let mut ciphertext = Vec::with_capacity(plaintext.len() + 256);
+        let mut writer = encryptor.wrap_output(&mut ciphertext)?;
+        writer.write_all(plaintext)?;
+        writer.finish()?;
+        Ok(ciphertext)
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let decryptor = age::Decryptor::new_buffered(ciphertext).map_err(encryption_error)?;
+
+        let mut reader = if decryptor.is_scrypt() {
+            let passphrase = self.passphrase.clone().ok_or_else(|| {
+                SupamigrateError::Encryption(
+                    "file is passphrase-encrypted but no passphrase_env is configured".to_string(),
+                )
+            })?;
+            let identity = age::scrypt::Identity::new(passphrase);
+            decryptor
+                .decrypt(iter::once(&identity as &dyn age::Identity))
+                .map_err(encryption_error)?
+        } else {
+            decryptor
+                .decrypt(self.identities.iter().map(|i| i.as_ref()))
+                .map_err(encryption_error)?
+        };
+
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    /// Write `content` encrypted to `<path>.age`
+    pub fn write_file(&self, path: &Path, content: &[u8]) -> Result<PathBuf> {
+        let encrypted_path = encrypted_path(path);
+        fs::write(&encrypted_path, self.encrypt(content)?)?;
+        Ok(encrypted_path)
+    }
+}
+
+/// Whether `content` is an age file
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(AGE_HEADER)
+}
+
+/// `<path>.age`
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", ENCRYPTED_EXTENSION));
+    PathBuf::from(name)
+}
+
+fn encryption_error(e: impl std::fmt::Display) -> SupamigrateError {
+    SupamigrateError::Encryption(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipient_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let identity = age::x25519::Identity::generate();
+        let identity_file = dir.path().join("backup.key");
+        {
+            use age::secrecy::ExposeSecret;
+            fs::write(&identity_file, identity.to_string().expose_secret()).unwrap();
+        }
+
+        let encrypt_only = EncryptionConfig {
+            recipients: vec![identity.to_public().to_string()],
+            ..Default::default()
+        };
+        let with_identity = EncryptionConfig {
+            identity_files: vec![identity_file.display().to_string()],
+            ..Default::default()
+        };
+
+        let encryption = Encryption::from_configs([Some(&encrypt_only)])
+            .unwrap()
+            .unwrap();
+        let ciphertext = encryption.encrypt(b"vault secret").unwrap();
+        assert!(is_encrypted(&ciphertext));
+        assert!(encryption.decrypt(&ciphertext).is_err());
+
+        let decryption = Encryption::from_configs([None, Some(&with_identity)])
+            .unwrap()
+            .unwrap();
+        assert_eq!(decryption.decrypt(&ciphertext).unwrap(), b"vault secret");
+
+        assert!(Encryption::from_configs([None]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_encrypted_path() {
+        assert_eq!(
+            encrypted_path(Path::new("backup/database.sql.gz")),
+            PathBuf::from("backup/database.sql.gz.age")
+        );
+    }
+}
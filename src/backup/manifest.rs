@@ -0,0 +1,282 @@
+use crate::error::{Result, SupamigrateError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// File name of the manifest inside a backup directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Current backup format version; bump when the layout changes incompatibly
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Every file in a backup with its size and SHA-256
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub created_at: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the backup directory, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A file that does not match the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityProblem {
+    Missing(String),
+    SizeMismatch {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+    ChecksumMismatch(String),
+    Untracked(String),
+}
+
+/// Result of checking a backup directory against its manifest
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    pub checked: usize,
+    pub problems: Vec<IntegrityProblem>,
+}
+
+impl Manifest {
+    /// Hash every file under `dir` (except the manifest itself)
+    pub fn generate(dir: &Path) -> Result<Self> {
+        Ok(Self {
+            format_version: FORMAT_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            files: hash_files(dir)?,
+        })
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Load the manifest of a backup, or `None` for backups made before manifests existed
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Self::from_json(&fs::read_to_string(&path)?).map(Some)
+    }
+
+    /// Parse a manifest, rejecting format versions this build does not understand
+    pub fn from_json(content: &str) -> Result<Self> {
+        let manifest: Self = serde_json::from_str(content)?;
+        if manifest.format_version > FORMAT_VERSION {
+            return Err(SupamigrateError::InvalidBackup(format!(
+                "backup format version {} is newer than supported version {}",
+                manifest.format_version, FORMAT_VERSION
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// Check sizes and hashes of all listed files and look for unlisted ones
+    pub fn verify(&self, dir: &Path) -> Result<IntegrityReport> {
+        Ok(self.check(&hash_files(dir)?))
+    }
+
+    /// Like [`Manifest::verify`], but only checks that files exist with the right size
+    pub fn verify_sizes(&self, dir: &Path) -> Result<IntegrityReport> {
+        let mut actual = Vec::new();
+        for path in list_files(dir)? {
+            let sha256 = self
+                .files
+                .iter()
+                .find(|e| e.path == path)
+                .map(|e| e.sha256.clone())
+                .unwrap_or_default();
+            actual.push(ManifestEntry {
+                size: fs::metadata(dir.join(&path))?.len(),
+                sha256,
+                path,
+            });
+        }
+        Ok(self.check(&actual))
+    }
+
+    /// Compare the manifest against the files actually present
+    pub fn check(&self, actual: &[ManifestEntry]) -> IntegrityReport {
+        let mut report = IntegrityReport::default();
+
+        for entry in &self.files {
+            report.checked += 1;
+            match actual.iter().find(|a| a.path == entry.path) {
+                None => report
+                    .problems
+                    .push(IntegrityProblem::Missing(entry.path.clone())),
+                Some(a) if a.size != entry.size => {
+                    report.problems.push(IntegrityProblem::SizeMismatch {
+                        path: entry.path.clone(),
+                        expected: entry.size,
+                        actual: a.size,
+                    });
+                }
+                Some(a) if a.sha256 != entry.sha256 => report
+                    .problems
+                    .push(IntegrityProblem::ChecksumMismatch(entry.path.clone())),
+                Some(_) => {}
+            }
+        }
+
+        for a in actual {
+            if a.path != MANIFEST_FILE && !self.files.iter().any(|e| e.path == a.path) {
+                report
+                    .problems
+                    .push(IntegrityProblem::Untracked(a.path.clone()));
+            }
+        }
+
+        report
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|e| e.size).sum()
+    }
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "{}: missing", path),
+            Self::SizeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: size {} bytes, expected {} bytes",
+                path, actual, expected
+            ),
+            Self::ChecksumMismatch(path) => write!(f, "{}: checksum mismatch", path),
+            Self::Untracked(path) => write!(f, "{}: not listed in manifest", path),
+        }
+    }
+}
+
+/// SHA-256 of everything read from `reader`, as lowercase hex, and the byte count
+pub fn sha256_reader(reader: &mut impl io::Read) -> io::Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(reader, &mut hasher)?;
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+/// SHA-256 of a file as lowercase hex
+pub fn sha256_file(path: &Path) -> Result<String> {
+    Ok(sha256_reader(&mut fs::File::open(path)?)?.0)
+}
+
+/// Size and hash of every file under `dir`
+fn hash_files(dir: &Path) -> Result<Vec<ManifestEntry>> {
+    let mut files = Vec::new();
+    for path in list_files(dir)? {
+        let full_path = dir.join(&path);
+        files.push(ManifestEntry {
+            size: fs::metadata(&full_path)?.len(),
+            sha256: sha256_file(&full_path)?,
+            path,
+        });
+    }
+    Ok(files)
+}
+
+/// Relative paths of all files under `dir`, sorted, excluding the manifest
+fn list_files(dir: &Path) -> Result<Vec<String>> {
+    fn walk(dir: &Path, prefix: &str, files: &mut Vec<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+
+            if entry.file_type()?.is_dir() {
+                walk(&entry.path(), &relative, files)?;
+            } else {
+                files.push(relative);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(dir, "", &mut files)?;
+    files.retain(|f| f != MANIFEST_FILE);
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("metadata.json"), "{}").unwrap();
+        fs::create_dir_all(dir.path().join("storage/avatars")).unwrap();
+        fs::write(dir.path().join("storage/avatars/a.png"), b"png").unwrap();
+
+        let manifest = Manifest::generate(dir.path()).unwrap();
+        manifest.write(dir.path()).unwrap();
+        let paths: Vec<&str> = manifest.files.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["metadata.json", "storage/avatars/a.png"]);
+        assert_eq!(manifest.total_size(), 5);
+
+        let loaded = Manifest::load(dir.path()).unwrap().unwrap();
+        assert_eq!(loaded, manifest);
+        assert!(loaded.verify(dir.path()).unwrap().is_ok());
+
+        // Same size, different content
+        fs::write(dir.path().join("storage/avatars/a.png"), b"gif").unwrap();
+        fs::write(dir.path().join("extra.txt"), b"x").unwrap();
+        fs::remove_file(dir.path().join("metadata.json")).unwrap();
+
+        // A same-size change is only caught by hashing
+        assert_eq!(loaded.verify_sizes(dir.path()).unwrap().problems.len(), 2);
+
+        let report = loaded.verify(dir.path()).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(
+            report.problems,
+            vec![
+                IntegrityProblem::Missing("metadata.json".to_string()),
+                IntegrityProblem::ChecksumMismatch("storage/avatars/a.png".to_string()),
+                IntegrityProblem::Untracked("extra.txt".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
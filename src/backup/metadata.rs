@@ -0,0 +1,78 @@
+use crate::db::SnapshotInfo;
+use serde::{Deserialize, Serialize};
+
+/// File name of the backup metadata; always written in plain text
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Storage objects left out because they changed after the database snapshot
+pub const LATE_OBJECTS_FILE: &str = "storage_after_snapshot.json";
+
+/// SHA-256 of the plain contents of each object in `storage/`, as `bucket/path` → hash.
+/// Encrypted objects get a new ciphertext on every backup, so `backup diff` compares these.
+pub const STORAGE_HASHES_FILE: &str = "storage_hashes.json";
+
+/// What a backup contains, written at the end of every backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMetadata {
+    pub project_ref: String,
+    pub timestamp: String,
+    pub schema_only: bool,
+    pub include_storage: bool,
+    #[serde(default)]
+    pub incremental_storage: bool,
+    #[serde(default)]
+    pub include_functions: bool,
+    #[serde(default)]
+    pub include_roles: bool,
+    #[serde(default)]
+    pub roles_count: usize,
+    #[serde(default)]
+    pub include_cron: bool,
+    #[serde(default)]
+    pub cron_count: usize,
+    #[serde(default)]
+    pub include_secrets: bool,
+    #[serde(default)]
+    pub secrets_count: usize,
+    #[serde(default)]
+    pub include_vault: bool,
+    #[serde(default)]
+    pub vault_count: usize,
+    pub compressed: bool,
+    #[serde(default)]
+    pub encrypted: bool,
+    /// WAL position and time the database dump was read at; storage objects
+    /// changed later are not in the backup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotInfo>,
+    #[serde(default)]
+    pub late_objects: usize,
+}
+
+impl BackupMetadata {
+    /// Name of the database dump inside the backup
+    pub fn dump_file(&self) -> &'static str {
+        if self.compressed {
+            "database.sql.gz"
+        } else {
+            "database.sql"
+        }
+    }
+
+    /// Short names of the parts included in the backup, e.g. `["schema", "storage"]`
+    pub fn components(&self) -> Vec<&'static str> {
+        [
+            (self.schema_only, "schema"),
+            (!self.schema_only, "database"),
+            (self.include_roles, "roles"),
+            (self.include_cron, "cron"),
+            (self.include_functions, "functions"),
+            (self.include_storage, "storage"),
+            (self.include_secrets, "secrets"),
+            (self.include_vault, "vault"),
+        ]
+        .into_iter()
+        .filter_map(|(included, name)| included.then_some(name))
+        .collect()
+    }
+}
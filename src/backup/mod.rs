@@ -0,0 +1,19 @@
+mod archive;
+mod catalog;
+mod encryption;
+mod manifest;
+mod metadata;
+mod retention;
+mod s3;
+mod source;
+mod store;
+
+pub use archive::{create_archive, storage_object, ArchiveReader, ARCHIVE_EXTENSION};
+pub use catalog::{list_backups, BackupContents, Change, Integrity};
+pub use encryption::{encrypted_path, is_encrypted, Encryption};
+pub use manifest::{Manifest, MANIFEST_FILE};
+pub use metadata::{BackupMetadata, LATE_OBJECTS_FILE, METADATA_FILE, STORAGE_HASHES_FILE};
+pub use retention::{plan as retention_plan, BackupDestination, BackupEntry, PARTIAL_SUFFIX};
+pub use s3::{S3Client, S3Url};
+pub use source::{BackupLocation, BackupSource};
+pub use store::{backup_storage, ObjectStore, StorageIndex};
@@ -0,0 +1,357 @@
+use crate::backup::archive::ARCHIVE_EXTENSION;
+use crate::backup::manifest::MANIFEST_FILE;
+use crate::backup::s3::{S3Client, S3Url};
+use crate::backup::store::{referenced_blobs, GcStats, ObjectStore};
+use crate::config::{RetentionConfig, S3Config};
+use crate::error::Result;
+use chrono::{Datelike, NaiveDateTime};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::warn;
+
+/// Timestamp format of backup names (`{project}_{timestamp}`)
+const TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S";
+
+/// Appended to the name of a backup until it is complete; such names never parse
+/// as a [`BackupEntry`], so unfinished backups are not listed, restored or kept
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Maps a time to the number of the day, week or month it falls in
+type Period = fn(&NaiveDateTime) -> i64;
+
+/// A timestamped backup directory or archive at a destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupEntry {
+    pub name: String,
+    pub project: String,
+    pub created: NaiveDateTime,
+}
+
+/// Outcome of applying a retention policy, newest first
+#[derive(Debug, Default)]
+pub struct RetentionPlan {
+    pub keep: Vec<BackupEntry>,
+    pub delete: Vec<BackupEntry>,
+}
+
+/// Where backups are written: a local directory or an `s3://` prefix
+pub enum BackupDestination {
+    Local(PathBuf),
+    S3 { client: Box<S3Client>, url: S3Url },
+}
+
+impl BackupEntry {
+    /// Parse `{project}_{YYYYmmdd_HHMMSS}` with an optional `.tar.gz` suffix;
+    /// `None` for anything else
+    pub fn parse(name: &str) -> Option<Self> {
+        let stem = name
+            .strip_suffix(&format!(".{}", ARCHIVE_EXTENSION))
+            .unwrap_or(name);
+        let mut parts = stem.rsplitn(3, '_');
+        let (time, date, project) = (parts.next()?, parts.next()?, parts.next()?);
+        let created =
+            NaiveDateTime::parse_from_str(&format!("{}_{}", date, time), TIMESTAMP_FORMAT).ok()?;
+        (!project.is_empty()).then(|| Self {
+            name: name.to_string(),
+            project: project.to_string(),
+            created,
+        })
+    }
+}
+
+/// Split backups into those the policy keeps and those it deletes.
+/// An empty policy keeps everything, and the newest backup is always kept.
+pub fn plan(
+    policy: &RetentionConfig,
+    mut backups: Vec<BackupEntry>,
+    now: NaiveDateTime,
+) -> RetentionPlan {
+    backups.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.name.cmp(&a.name)));
+    if policy.is_empty() {
+        return RetentionPlan {
+            keep: backups,
+            delete: Vec::new(),
+        };
+    }
+
+    let mut keep: HashSet<usize> = (0..backups.len())
+        .take(policy.keep_last.max(1) as usize)
+        .collect();
+
+    let rules: [(u32, Period); 3] = [
+        (policy.keep_daily, |t| {
+            i64::from(t.date().num_days_from_ce())
+        }),
+        (policy.keep_weekly, |t| {
+            let days = i64::from(t.date().num_days_from_ce());
+            (days - i64::from(t.weekday().num_days_from_monday())) / 7
+        }),
+        (policy.keep_monthly, |t| {
+            i64::from(t.year()) * 12 + i64::from(t.month0())
+        }),
+    ];
+
+    for (count, period) in rules {
+        let oldest = period(&now) - i64::from(count) + 1;
+        let mut seen = HashSet::new();
+        // Backups are sorted newest first, so the first one seen in a period is kept
+        for (i, backup) in backups.iter().enumerate() {
+            let p = period(&backup.created);
+            if p >= oldest && seen.insert(p) {
+                keep.insert(i);
+            }
+        }
+    }
+
+    let mut plan = RetentionPlan::default();
+    for (i, backup) in backups.into_iter().enumerate() {
+        if keep.contains(&i) {
+            plan.keep.push(backup);
+        } else {
+            plan.delete.push(backup);
+        }
+    }
+    plan
+}
+
+impl BackupDestination {
+    pub fn open(location: &str, s3: Option<&S3Config>) -> Result<Self> {
+        match S3Url::parse(location) {
+            Some(url) => Ok(Self::S3 {
+                client: Box::new(S3Client::from_config(s3)?),
+                url,
+            }),
+            None => Ok(Self::Local(PathBuf::from(location))),
+        }
+    }
+
+    /// Timestamped backups at this destination, optionally only those of `project`
+    pub async fn list(&self, project: Option<&str>) -> Result<Vec<BackupEntry>> {
+        let names: BTreeSet<String> = match self {
+            Self::Local(dir) => {
+                if !dir.is_dir() {
+                    return Ok(Vec::new());
+                }
+                let mut names = BTreeSet::new();
+                for entry in fs::read_dir(dir)? {
+                    names.insert(entry?.file_name().to_string_lossy().to_string());
+                }
+                names
+            }
+            Self::S3 { client, url } => {
+                let prefix = if url.prefix.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}/", url.prefix)
+                };
+                // An archive is a single object; a directory backup is complete once its
+                // manifest, which is uploaded last, is there
+                client
+                    .list(&url.bucket, &prefix)
+                    .await?
+                    .iter()
+                    .filter_map(|o| {
+                        let key = o.key.strip_prefix(&prefix)?;
+                        match key.split_once('/') {
+                            None => Some(key),
+                            Some((name, MANIFEST_FILE)) => Some(name),
+                            Some(_) => None,
+                        }
+                    })
+                    .map(str::to_string)
+                    .collect()
+            }
+        };
+
+        Ok(names
+            .iter()
+            .filter_map(|name| BackupEntry::parse(name))
+            .filter(|b| project.map_or(true, |p| b.project == p))
+            .collect())
+    }
+
+    pub async fn delete(&self, backup: &BackupEntry) -> Result<()> {
+        match self {
+            Self::Local(dir) => {
+                let path = dir.join(&backup.name);
+                if path.is_dir() {
+                    fs::remove_dir_all(path)?;
+                } else {
+                    fs::remove_file(path)?;
+                }
+            }
+            Self::S3 { client, url } => {
+                client.delete_backup(&url.join(&backup.name)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove blobs of the object store no longer referenced once `deleted` are gone;
+    /// with `dry_run` only count them. `None` if the destination has no object store.
+    pub fn collect_garbage(
+        &self,
+        deleted: &[BackupEntry],
+        dry_run: bool,
+    ) -> Result<Option<GcStats>> {
+        let Self::Local(dir) = self else {
+            return Ok(None);
+        };
+        let store = ObjectStore::open(dir);
+        if !store.exists() {
+            return Ok(None);
+        }
+
+        // Blobs of backups still running are not in any index yet: keep everything
+        // written since the oldest of them started, or since this collection started
+        let started = SystemTime::now();
+        let in_progress = in_progress(dir)?;
+        if let Some(oldest) = in_progress.iter().min_by_key(|b| b.created) {
+            warn!(
+                "{} unfinished backup(s) in {} (oldest {}); keeping blobs written since then",
+                in_progress.len(),
+                dir.display(),
+                oldest.name
+            );
+        }
+        let in_use_since = in_progress
+            .iter()
+            .map(|b| SystemTime::from(b.created.and_utc()))
+            .fold(started, SystemTime::min);
+
+        let referenced = referenced_blobs(dir, deleted)?;
+        store
+            .collect_garbage(&referenced, in_use_since, dry_run)
+            .map(Some)
+    }
+}
+
+/// Backups in `dir` still carrying their partial name, i.e. running or failed
+fn in_progress(dir: &Path) -> Result<Vec<BackupEntry>> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        backups.extend(
+            name.strip_suffix(PARTIAL_SUFFIX)
+                .and_then(BackupEntry::parse),
+        );
+    }
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(timestamp: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).unwrap()
+    }
+
+    fn names(backups: &[BackupEntry]) -> Vec<&str> {
+        backups.iter().map(|b| b.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_backup_name() {
+        let backup = BackupEntry::parse("prod_20240115_120000.tar.gz").unwrap();
+        assert_eq!(backup.project, "prod");
+        assert_eq!(backup.created, at("20240115_120000"));
+        assert_eq!(
+            BackupEntry::parse("prod_eu_20240115_120000")
+                .unwrap()
+                .project,
+            "prod_eu"
+        );
+        assert!(BackupEntry::parse("_20240115_120000").is_none());
+        assert!(BackupEntry::parse("prod_latest").is_none());
+        assert!(BackupEntry::parse("objects").is_none());
+        // Unfinished backups
+        assert!(BackupEntry::parse("prod_20240115_120000.partial").is_none());
+        assert!(BackupEntry::parse("prod_20240115_120000.tar.gz.partial").is_none());
+    }
+
+    #[test]
+    fn test_retention_plan() {
+        let backups: Vec<BackupEntry> = [
+            "p_20240301_120000",
+            "p_20240301_060000",
+            "p_20240229_120000",
+            "p_20240226_120000",
+            "p_20240220_120000",
+            "p_20240115_120000",
+            "p_20231231_120000",
+        ]
+        .iter()
+        .map(|n| BackupEntry::parse(n).unwrap())
+        .collect();
+        let now = at("20240301_180000");
+
+        let policy = RetentionConfig {
+            keep_daily: 2,
+            ..Default::default()
+        };
+        let result = plan(&policy, backups.clone(), now);
+        assert_eq!(
+            names(&result.keep),
+            vec!["p_20240301_120000", "p_20240229_120000"]
+        );
+
+        // 2024-03-01 is a Friday: this week and last week
+        let policy = RetentionConfig {
+            keep_weekly: 2,
+            keep_monthly: 3,
+            ..Default::default()
+        };
+        let result = plan(&policy, backups.clone(), now);
+        assert_eq!(
+            names(&result.keep),
+            vec![
+                "p_20240301_120000",
+                "p_20240229_120000",
+                "p_20240220_120000",
+                "p_20240115_120000",
+            ]
+        );
+        assert_eq!(names(&result.delete).len(), 3);
+
+        // The newest backup survives even a policy that selects nothing
+        let policy = RetentionConfig {
+            keep_monthly: 1,
+            ..Default::default()
+        };
+        let result = plan(&policy, backups.clone(), at("20250101_000000"));
+        assert_eq!(names(&result.keep), vec!["p_20240301_120000"]);
+
+        let result = plan(&RetentionConfig::default(), backups, now);
+        assert!(result.delete.is_empty());
+    }
+
+    #[test]
+    fn test_gc_keeps_blobs_of_unfinished_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let (sha256, _) = ObjectStore::open(dir.path()).put(b"abc", None).unwrap();
+        let blob = dir.path().join("objects").join(&sha256[..2]).join(&sha256);
+        let written = SystemTime::from(at("20240101_000000").and_utc());
+        fs::File::options()
+            .write(true)
+            .open(&blob)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+        let destination = BackupDestination::Local(dir.path().to_path_buf());
+
+        // Not referenced yet, but an incremental backup started before it was written
+        let running = dir.path().join("p_20231231_235959.partial");
+        fs::create_dir(&running).unwrap();
+        let stats = destination.collect_garbage(&[], false).unwrap().unwrap();
+        assert_eq!(stats.blobs, 0);
+        assert!(blob.is_file());
+
+        fs::remove_dir(&running).unwrap();
+        let stats = destination.collect_garbage(&[], false).unwrap().unwrap();
+        assert_eq!(stats.blobs, 1);
+        assert!(!blob.is_file());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::backup::archive::{is_archive, ArchiveReader};
+use crate::backup::encryption::{Encryption, ENCRYPTED_EXTENSION};
+use crate::backup::manifest::{IntegrityProblem, IntegrityReport, Manifest};
+use crate::backup::metadata::{BackupMetadata, METADATA_FILE};
+use crate::backup::store::{ObjectStore, StorageIndex};
+use crate::error::{Result, SupamigrateError};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Where the backup files live
+pub enum BackupLocation {
+    Dir(PathBuf),
+    Archive(ArchiveReader),
+}
+
+/// A backup to restore from: a directory or a single-file archive.
+/// Encrypted (`.age`) files are decrypted transparently once keys are set.
+pub struct BackupSource {
+    location: BackupLocation,
+    encryption: Option<Encryption>,
+}
+
+impl BackupSource {
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(SupamigrateError::BackupNotFound(path.display().to_string()));
+        }
+
+        let location = if is_archive(path) {
+            BackupLocation::Archive(ArchiveReader::open(path)?)
+        } else if path.is_dir() {
+            BackupLocation::Dir(path.to_path_buf())
+        } else {
+            return Err(SupamigrateError::InvalidBackup(format!(
+                "{} is neither a backup directory nor a .tar.gz archive",
+                path.display()
+            )));
+        };
+
+        Ok(Self {
+            location,
+            encryption: None,
+        })
+    }
+
+    /// Keys for decrypting `.age` files
+    pub fn set_encryption(&mut self, encryption: Option<Encryption>) {
+        self.encryption = encryption;
+    }
+
+    pub fn location(&self) -> &BackupLocation {
+        &self.location
+    }
+
+    pub fn manifest(&self) -> Result<Option<Manifest>> {
+        match &self.location {
+            BackupLocation::Dir(dir) => Manifest::load(dir),
+            BackupLocation::Archive(archive) => Ok(archive.manifest().cloned()),
+        }
+    }
+
+    /// Integrity of the backup contents, or `None` for backups without a manifest.
+    /// Incremental backups are also checked for blobs missing from the object store.
+    pub fn verify(&self) -> Result<Option<IntegrityReport>> {
+        let mut report = match &self.location {
+            BackupLocation::Dir(dir) => Manifest::load(dir)?.map(|m| m.verify(dir)).transpose()?,
+            BackupLocation::Archive(archive) => archive.verify(),
+        };
+
+        if let (Some(report), Some(index), Some(store)) =
+            (report.as_mut(), self.storage_index()?, self.object_store())
+        {
+            report.checked += index.objects.len();
+            report
+                .problems
+                .extend(store.missing(&index).into_iter().map(|o| {
+                    IntegrityProblem::Missing(format!(
+                        "storage/{}/{} (blob {})",
+                        o.bucket, o.path, o.sha256
+                    ))
+                }));
+        }
+
+        Ok(report)
+    }
+
+    /// Storage objects of an incremental backup
+    pub fn storage_index(&self) -> Result<Option<StorageIndex>> {
+        match &self.location {
+            BackupLocation::Dir(dir) => StorageIndex::load(dir),
+            BackupLocation::Archive(_) => Ok(None),
+        }
+    }
+
+    /// Object store holding the blobs of an incremental backup
+    pub fn object_store(&self) -> Option<ObjectStore> {
+        match &self.location {
+            BackupLocation::Dir(dir) => Some(ObjectStore::for_backup(dir)),
+            BackupLocation::Archive(_) => None,
+        }
+    }
+
+    /// Contents of a top-level file such as `metadata.json`, if present.
+    /// Falls back to `<name>.age` and decrypts it.
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        if let Some(content) = self.read_raw(name)? {
+            return Ok(Some(content));
+        }
+
+        let encrypted_name = format!("{}.{}", name, ENCRYPTED_EXTENSION);
+        self.read_raw(&encrypted_name)?
+            .map(|content| self.decrypt(&encrypted_name, &content))
+            .transpose()
+    }
+
+    fn read_raw(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match &self.location {
+            BackupLocation::Dir(dir) => {
+                let path = dir.join(name);
+                if path.is_file() {
+                    Ok(Some(fs::read(path)?))
+                } else {
+                    Ok(None)
+                }
+            }
+            BackupLocation::Archive(archive) => archive.read(name),
+        }
+    }
+
+    /// Strip the `.age` suffix from an encrypted file and decrypt it; other files pass through
+    pub fn decode(&self, name: &str, content: Vec<u8>) -> Result<(String, Vec<u8>)> {
+        match name.strip_suffix(&format!(".{}", ENCRYPTED_EXTENSION)) {
+            Some(plain_name) if self.encryption.is_some() => {
+                Ok((plain_name.to_string(), self.decrypt(name, &content)?))
+            }
+            _ => Ok((name.to_string(), content)),
+        }
+    }
+
+    fn decrypt(&self, name: &str, content: &[u8]) -> Result<Vec<u8>> {
+        let encryption = self.encryption.as_ref().ok_or_else(|| {
+            SupamigrateError::Encryption(format!(
+                "{} is encrypted but no encryption keys are configured",
+                name
+            ))
+        })?;
+        encryption
+            .decrypt(content)
+            .map_err(|e| SupamigrateError::Encryption(format!("cannot decrypt {}: {}", name, e)))
+    }
+
+    pub fn metadata(&self) -> Result<BackupMetadata> {
+        let content = self.read_to_string(METADATA_FILE)?.ok_or_else(|| {
+            SupamigrateError::InvalidBackup(format!("{} not found", METADATA_FILE))
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The database dump as SQL, decompressed and decrypted
+    pub fn read_dump(&self, metadata: &BackupMetadata) -> Result<String> {
+        let dump_file = metadata.dump_file();
+        let content = self.read(dump_file)?.ok_or_else(|| {
+            SupamigrateError::InvalidBackup(format!("Database dump not found: {}", dump_file))
+        })?;
+
+        let content = if metadata.compressed {
+            let mut decoder = flate2::read::GzDecoder::new(content.as_slice());
+            let mut sql = Vec::new();
+            decoder.read_to_end(&mut sql)?;
+            sql
+        } else {
+            content
+        };
+
+        String::from_utf8(content).map_err(|_| {
+            SupamigrateError::InvalidBackup(format!("{} is not valid UTF-8", dump_file))
+        })
+    }
+
+    pub fn read_to_string(&self, name: &str) -> Result<Option<String>> {
+        self.read(name)?
+            .map(|content| {
+                String::from_utf8(content).map_err(|_| {
+                    SupamigrateError::InvalidBackup(format!("{} is not valid UTF-8", name))
+                })
+            })
+            .transpose()
+    }
+
+    /// All files below a top-level directory (e.g. `functions`), with paths relative to it
+    pub fn files_under(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let files = match &self.location {
+            BackupLocation::Dir(dir) => {
+                let mut files = Vec::new();
+                let base = dir.join(prefix);
+                if base.is_dir() {
+                    collect_files(&base, &base, &mut files)?;
+                }
+                files
+            }
+            BackupLocation::Archive(archive) => archive.files_under(prefix)?,
+        };
+
+        let mut files = files
+            .into_iter()
+            .map(|(path, content)| self.decode(&path, content))
+            .collect::<Result<Vec<_>>>()?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// Whether the backup has any files below a top-level directory
+    pub fn has_dir(&self, prefix: &str) -> bool {
+        match &self.location {
+            BackupLocation::Dir(dir) => dir.join(prefix).is_dir(),
+            BackupLocation::Archive(archive) => archive.manifest().map_or(true, |m| {
+                m.files
+                    .iter()
+                    .any(|e| e.path.starts_with(&format!("{}/", prefix)))
+            }),
+        }
+    }
+}
+
+fn collect_files(base: &Path, current: &Path, files: &mut Vec<(String, Vec<u8>)>) -> Result<()> {
+    for entry in fs::read_dir(current)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(base, &path, files)?;
+        } else if path.is_file() {
+            let relative = path
+                .strip_prefix(base)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            files.push((relative, fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
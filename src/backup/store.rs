@@ -0,0 +1,497 @@
+use crate::backup::encryption::{encrypted_path, Encryption};
+use crate::backup::manifest::sha256_reader;
+use crate::backup::retention::BackupEntry;
+use crate::error::{Result, SupamigrateError};
+use crate::storage::{LateObject, StorageClient};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// Directory next to the backups holding blobs shared by all incremental backups
+pub const STORE_DIR: &str = "objects";
+
+/// Per-backup list of storage objects and the blobs holding their contents
+pub const STORAGE_INDEX_FILE: &str = "storage.json";
+
+/// Storage objects of an incremental backup.
+/// Kept in plain text (like `metadata.json`) so later backups and pruning can read it
+/// without decryption keys; the blobs themselves are encrypted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageIndex {
+    pub objects: Vec<IndexedObject>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedObject {
+    pub bucket: String,
+    pub path: String,
+    /// SHA-256 of the plain contents; the blob's name in the store
+    pub sha256: String,
+    pub size: u64,
+    /// ETag reported by Storage; objects with an unchanged ETag and update time are not downloaded again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+/// Content-addressed blobs stored as `objects/<first 2 hex chars>/<sha256>[.age]`
+pub struct ObjectStore {
+    dir: PathBuf,
+}
+
+/// Objects stored by an incremental storage backup
+#[derive(Debug, Default)]
+pub struct IncrementalStats {
+    pub buckets: usize,
+    pub objects: usize,
+    pub downloaded: usize,
+    pub reused: usize,
+    pub new_blobs: usize,
+    pub bytes_downloaded: usize,
+    pub errors: usize,
+    /// Objects skipped because they changed after the database snapshot
+    pub late: Vec<LateObject>,
+}
+
+/// Blobs removed (or, in a dry run, removable) from the object store
+#[derive(Debug, Default)]
+pub struct GcStats {
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+impl StorageIndex {
+    /// Index of an incremental backup, or `None` for backups with plain `storage/` files
+    pub fn load(backup_dir: &Path) -> Result<Option<Self>> {
+        let path = backup_dir.join(STORAGE_INDEX_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn write(&self, backup_dir: &Path) -> Result<()> {
+        fs::write(
+            backup_dir.join(STORAGE_INDEX_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Index of the newest earlier backup of `project` in `output_dir` that has one
+    pub fn latest(output_dir: &Path, project: &str) -> Result<Option<Self>> {
+        if !output_dir.is_dir() {
+            return Ok(None);
+        }
+
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(output_dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            backups.extend(BackupEntry::parse(&name).filter(|b| b.project == project));
+        }
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created));
+
+        for backup in backups {
+            if let Some(index) = Self::load(&output_dir.join(&backup.name))? {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl ObjectStore {
+    /// Store shared by all backups written to `output_dir`
+    pub fn open(output_dir: &Path) -> Self {
+        Self {
+            dir: output_dir.join(STORE_DIR),
+        }
+    }
+
+    /// Store of the backups next to `backup_dir`
+    pub fn for_backup(backup_dir: &Path) -> Self {
+        Self::open(backup_dir.parent().unwrap_or(Path::new(".")))
+    }
+
+    pub fn exists(&self) -> bool {
+        self.dir.is_dir()
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join(&sha256[..2.min(sha256.len())]).join(sha256)
+    }
+
+    /// Path of a stored blob, plain or encrypted
+    fn find(&self, sha256: &str) -> Option<PathBuf> {
+        let path = self.blob_path(sha256);
+        [encrypted_path(&path), path]
+            .into_iter()
+            .find(|p| p.is_file())
+    }
+
+    pub fn contains(&self, sha256: &str) -> bool {
+        self.find(sha256).is_some()
+    }
+
+    /// Mark a stored blob as used by a running backup by setting its modification
+    /// time, so garbage collection started meanwhile keeps it; `false` if it is missing
+    pub fn reuse(&self, sha256: &str) -> bool {
+        self.find(sha256)
+            .and_then(|path| fs::File::options().write(true).open(path).ok())
+            .is_some_and(|file| file.set_modified(SystemTime::now()).is_ok())
+    }
+
+    /// Store `data` under its SHA-256 unless a blob with that hash already exists;
+    /// returns the hash and whether a new blob was written
+    pub fn put(&self, data: &[u8], encryption: Option<&Encryption>) -> Result<(String, bool)> {
+        let (sha256, _) = sha256_reader(&mut &data[..])?;
+        if self.reuse(&sha256) {
+            return Ok((sha256, false));
+        }
+
+        let path = self.blob_path(&sha256);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let (path, content) = match encryption {
+            Some(encryption) => (encrypted_path(&path), encryption.encrypt(data)?),
+            None => (path, data.to_vec()),
+        };
+
+        // Write under a temporary name so an interrupted backup never leaves a partial blob
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        fs::write(&partial, content)?;
+        fs::rename(&partial, &path)?;
+        Ok((sha256, true))
+    }
+
+    /// File name and raw (possibly encrypted) contents of a blob
+    pub fn read(&self, sha256: &str) -> Result<(String, Vec<u8>)> {
+        let path = self.find(sha256).ok_or_else(|| {
+            SupamigrateError::InvalidBackup(format!("blob {} missing from object store", sha256))
+        })?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok((name, fs::read(path)?))
+    }
+
+    /// Objects of `index` whose blob is not in the store
+    pub fn missing<'a>(&self, index: &'a StorageIndex) -> Vec<&'a IndexedObject> {
+        index
+            .objects
+            .iter()
+            .filter(|o| !self.contains(&o.sha256))
+            .collect()
+    }
+
+    /// Remove every blob not in `referenced`; with `dry_run` only count them.
+    /// Blobs written or reused at or after `in_use_since` may belong to a backup still
+    /// in progress, which no index references yet, and are kept.
+    pub fn collect_garbage(
+        &self,
+        referenced: &HashSet<String>,
+        in_use_since: SystemTime,
+        dry_run: bool,
+    ) -> Result<GcStats> {
+        let mut stats = GcStats::default();
+        if !self.exists() {
+            return Ok(stats);
+        }
+
+        for shard in fs::read_dir(&self.dir)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for blob in fs::read_dir(&shard)? {
+                let path = blob?.path();
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let sha256 = name.split('.').next().unwrap_or_default();
+                if referenced.contains(sha256) && !name.ends_with(".partial") {
+                    continue;
+                }
+                let metadata = fs::metadata(&path)?;
+                if metadata.modified()? >= in_use_since {
+                    continue;
+                }
+
+                stats.blobs += 1;
+                stats.bytes += metadata.len();
+                if !dry_run {
+                    fs::remove_file(&path)?;
+                }
+            }
+            if !dry_run && fs::read_dir(&shard)?.next().is_none() {
+                fs::remove_dir(&shard)?;
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Blobs referenced by any backup in `output_dir` other than those in `excluding`
+pub fn referenced_blobs(output_dir: &Path, excluding: &[BackupEntry]) -> Result<HashSet<String>> {
+    let mut referenced = HashSet::new();
+    for entry in fs::read_dir(output_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == STORE_DIR || excluding.iter().any(|b| b.name == name) {
+            continue;
+        }
+        if let Some(index) = StorageIndex::load(&entry.path())? {
+            referenced.extend(index.objects.into_iter().map(|o| o.sha256));
+        }
+    }
+    Ok(referenced)
+}
+
+/// Back up every bucket into `store`, downloading only objects that are new or whose
+/// ETag or update time changed since `previous`. With a `snapshot` time, objects
+/// changed after it are left out and listed in the stats.
+pub async fn backup_storage(
+    client: &StorageClient,
+    store: &ObjectStore,
+    previous: Option<&StorageIndex>,
+    encryption: Option<&Encryption>,
+    parallel: usize,
+    snapshot: Option<DateTime<Utc>>,
+) -> Result<(StorageIndex, IncrementalStats)> {
+    let previous: HashMap<(&str, &str), &IndexedObject> = previous
+        .map(|index| {
+            index
+                .objects
+                .iter()
+                .map(|o| ((o.bucket.as_str(), o.path.as_str()), o))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut index = StorageIndex::default();
+    let mut stats = IncrementalStats::default();
+
+    for bucket in client.list_buckets().await? {
+        stats.buckets += 1;
+        // Entries without an id are folders
+        let objects: Vec<_> = client
+            .list_objects(&bucket.name, None)
+            .await?
+            .into_iter()
+            .filter(|o| o.id.is_some())
+            .filter(|o| match snapshot {
+                Some(taken_at) if o.changed_after(taken_at) => {
+                    stats.late.push(o.late(&bucket.name));
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+        info!("Backing up {} objects from {}", objects.len(), bucket.name);
+
+        let pb = ProgressBar::new(objects.len() as u64);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        pb.set_message(format!("Backing up {}", bucket.name));
+
+        let results: Vec<Result<(IndexedObject, Option<bool>)>> = stream::iter(objects)
+            .map(|object| {
+                let bucket_name = bucket.name.clone();
+                let pb = pb.clone();
+                let unchanged = previous
+                    .get(&(bucket.name.as_str(), object.name.as_str()))
+                    .copied()
+                    .cloned();
+
+                async move {
+                    let etag = object
+                        .metadata
+                        .as_ref()
+                        .and_then(|m| m["eTag"].as_str())
+                        .map(String::from);
+                    let unchanged = unchanged.filter(|prev| {
+                        etag.is_some()
+                            && prev.etag == etag
+                            && prev.updated_at == object.updated_at
+                            && store.reuse(&prev.sha256)
+                    });
+
+                    let result = match unchanged {
+                        Some(prev) => (prev, None),
+                        None => {
+                            let data = client.download(&bucket_name, &object.name).await?;
+                            let (sha256, new_blob) = store.put(&data, encryption)?;
+                            let entry = IndexedObject {
+                                bucket: bucket_name,
+                                path: object.name,
+                                sha256,
+                                size: data.len() as u64,
+                                etag,
+                                updated_at: object.updated_at,
+                            };
+                            (entry, Some(new_blob))
+                        }
+                    };
+
+                    pb.inc(1);
+                    Ok(result)
+                }
+            })
+            .buffer_unordered(parallel)
+            .collect()
+            .await;
+
+        pb.finish_with_message("Done");
+
+        for result in results {
+            match result {
+                Ok((entry, downloaded)) => {
+                    stats.objects += 1;
+                    match downloaded {
+                        Some(new_blob) => {
+                            stats.downloaded += 1;
+                            stats.bytes_downloaded += entry.size as usize;
+                            if new_blob {
+                                stats.new_blobs += 1;
+                            }
+                        }
+                        None => stats.reused += 1,
+                    }
+                    index.objects.push(entry);
+                }
+                Err(e) => {
+                    stats.errors += 1;
+                    warn!("Download error: {}", e);
+                }
+            }
+        }
+    }
+
+    index
+        .objects
+        .sort_by(|a, b| (&a.bucket, &a.path).cmp(&(&b.bucket, &b.path)));
+    Ok((index, stats))
+}
+
+impl std::fmt::Display for IncrementalStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} buckets, {} objects ({} downloaded, {} unchanged, {} new blobs, {} bytes transferred)",
+            self.buckets,
+            self.objects,
+            self.downloaded,
+            self.reused,
+            self.new_blobs,
+            self.bytes_downloaded
+        )?;
+        if self.errors > 0 {
+            write!(f, " ({} errors)", self.errors)?;
+        }
+        if !self.late.is_empty() {
+            write!(f, " ({} changed after snapshot)", self.late.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for GcStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} blobs, {} bytes", self.blobs, self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed(path: &str, sha256: &str) -> IndexedObject {
+        IndexedObject {
+            bucket: "avatars".to_string(),
+            path: path.to_string(),
+            sha256: sha256.to_string(),
+            size: 3,
+            etag: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_object_store_dedup_and_gc() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ObjectStore::open(dir.path());
+
+        let (a, new) = store.put(b"abc", None).unwrap();
+        assert!(new);
+        assert_eq!(
+            a,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(!store.put(b"abc", None).unwrap().1);
+        let (b, _) = store.put(b"xyz", None).unwrap();
+        assert_eq!(store.read(&a).unwrap().1, b"abc");
+
+        // Two backups share blob a; the older one also references b
+        let old = dir.path().join("p_20240101_000000");
+        let new = dir.path().join("p_20240102_000000");
+        for (backup, objects) in [
+            (&old, vec![indexed("a.png", &a), indexed("b.png", &b)]),
+            (&new, vec![indexed("a.png", &a)]),
+        ] {
+            fs::create_dir_all(backup).unwrap();
+            StorageIndex { objects }.write(backup).unwrap();
+        }
+        assert_eq!(
+            StorageIndex::latest(dir.path(), "p")
+                .unwrap()
+                .unwrap()
+                .objects,
+            vec![indexed("a.png", &a)]
+        );
+
+        let deleted = vec![BackupEntry::parse("p_20240101_000000").unwrap()];
+        let referenced = referenced_blobs(dir.path(), &deleted).unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        let dry_run = store.collect_garbage(&referenced, later, true).unwrap();
+        assert_eq!((dry_run.blobs, dry_run.bytes), (1, 3));
+        assert!(store.contains(&b));
+
+        // Blobs written since a running backup started are kept
+        fs::remove_dir_all(&old).unwrap();
+        let referenced = referenced_blobs(dir.path(), &[]).unwrap();
+        let running = store
+            .collect_garbage(&referenced, SystemTime::UNIX_EPOCH, false)
+            .unwrap();
+        assert_eq!(running.blobs, 0);
+        assert!(store.contains(&b));
+
+        assert_eq!(
+            store
+                .collect_garbage(&referenced, later, false)
+                .unwrap()
+                .blobs,
+            1
+        );
+        assert!(!store.contains(&b));
+        assert!(store.contains(&a));
+        assert!(store
+            .missing(&StorageIndex::load(&new).unwrap().unwrap())
+            .is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -28,6 +28,12 @@ pub enum Commands {
     /// Migrate between two Supabase projects
     Migrate(MigrateArgs),
 
+    /// Continuously replicate a project with logical replication, then cut over
+    Replicate(ReplicateArgs),
+
+    /// Compare data between two projects (row counts, checksums, storage)
+    Verify(VerifyArgs),
+
     /// Backup a Supabase project
     Backup(BackupArgs),
 
@@ -37,12 +43,18 @@ pub enum Commands {
     /// Storage-only operations
     Storage(StorageArgs),
 
+    /// Manage edge functions
+    Functions(FunctionsArgs),
+
     /// Manage edge function secrets
     Secrets(SecretsArgs),
 
     /// Manage Supabase Vault secrets (encrypted database secrets)
     Vault(VaultArgs),
 
+    /// Inspect pg_cron scheduled jobs
+    Cron(CronArgs),
+
     /// Manage configuration
     Config(ConfigArgs),
 
@@ -83,6 +95,59 @@ pub struct MigrateArgs {
     #[arg(long, default_value = "false")]
     pub data_only: bool,
 
+    /// Load data with session_replication_role = replica so triggers don't fire
+    #[arg(long, default_value = "false", requires = "data_only")]
+    pub disable_triggers: bool,
+
+    /// Load tables one at a time, referenced tables first
+    #[arg(long, default_value = "false", requires = "data_only")]
+    pub order_tables: bool,
+
+    /// Load data in a single transaction with DEFERRABLE constraints deferred; tables are
+    /// loaded in foreign key order if the target has NOT DEFERRABLE foreign keys
+    #[arg(long, default_value = "false", requires = "data_only")]
+    pub defer_constraints: bool,
+
+    /// Upsert source rows into existing target tables by primary key instead of loading a dump
+    #[arg(long, default_value = "false", requires = "data_only")]
+    pub merge: bool,
+
+    /// With --merge, delete target rows that are missing from the source
+    #[arg(long, default_value = "false", requires = "merge")]
+    pub delete_missing: bool,
+
+    /// Only copy the rows of these tables (comma-separated, `table` or `schema.table`)
+    #[arg(long, value_delimiter = ',', requires = "data_only")]
+    pub tables: Option<Vec<String>>,
+
+    /// Skip recreating custom database roles and memberships
+    #[arg(long, default_value = "false")]
+    pub no_roles: bool,
+
+    /// Skip enabling the source's extensions on the target
+    #[arg(long, default_value = "false")]
+    pub no_extensions: bool,
+
+    /// Include pg_cron scheduled jobs
+    #[arg(long, default_value = "false")]
+    pub include_cron: bool,
+
+    /// Create migrated cron jobs inactive
+    #[arg(long, default_value = "false")]
+    pub cron_disabled: bool,
+
+    /// Skip database webhooks (triggers calling supabase_functions.http_request)
+    #[arg(long, default_value = "false")]
+    pub no_webhooks: bool,
+
+    /// Skip reconciling publications (e.g. supabase_realtime) on the target
+    #[arg(long, default_value = "false")]
+    pub no_publications: bool,
+
+    /// How to advance target sequences after a data-only migration
+    #[arg(long, value_enum, default_value = "source")]
+    pub sync_sequences: SequenceSync,
+
     /// Exclude specific tables (comma-separated)
     #[arg(long, value_delimiter = ',')]
     pub exclude_tables: Option<Vec<String>>,
@@ -91,6 +156,10 @@ pub struct MigrateArgs {
     #[arg(long, value_delimiter = ',')]
     pub exclude_schemas: Option<Vec<String>>,
 
+    /// Compare row counts of the migrated tables (and storage usage with --include-storage) after migrating
+    #[arg(long, default_value = "false")]
+    pub verify: bool,
+
     /// Dry run - show what would be done
     #[arg(long, default_value = "false")]
     pub dry_run: bool,
@@ -101,19 +170,146 @@ pub struct MigrateArgs {
 }
 
 #[derive(Parser)]
+pub struct VerifyArgs {
+    /// Source project reference or alias
+    #[arg(long, env = "SUPAMIGRATE_SOURCE")]
+    pub from: String,
+
+    /// Target project reference or alias
+    #[arg(long, env = "SUPAMIGRATE_TARGET")]
+    pub to: String,
+
+    /// Also compare content hashes of primary-key-ordered row chunks
+    #[arg(long, default_value = "false")]
+    pub checksums: bool,
+
+    /// Rows per checksum chunk
+    #[arg(long, default_value = "10000")]
+    pub chunk_size: u64,
+
+    /// Also compare storage object counts and sizes per bucket
+    #[arg(long, default_value = "false")]
+    pub include_storage: bool,
+
+    /// Exclude specific tables (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_tables: Option<Vec<String>>,
+
+    /// Exclude specific schemas (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_schemas: Option<Vec<String>>,
+}
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct ReplicateArgs {
+    #[command(subcommand)]
+    pub command: Option<ReplicateCommands>,
+
+    /// Source project reference or alias
+    #[arg(long, required = true, env = "SUPAMIGRATE_SOURCE")]
+    pub from: Option<String>,
+
+    /// Target project reference or alias
+    #[arg(long, required = true, env = "SUPAMIGRATE_TARGET")]
+    pub to: Option<String>,
+
+    /// Target already has the schema; skip the initial schema copy
+    #[arg(long, default_value = "false")]
+    pub skip_schema: bool,
+
+    /// Exclude specific tables (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_tables: Option<Vec<String>>,
+
+    /// Exclude specific schemas (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_schemas: Option<Vec<String>>,
+
+    /// Skip confirmation prompt
+    #[arg(short = 'y', long, default_value = "false")]
+    pub yes: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ReplicateCommands {
+    /// Show replication progress and lag
+    Status {
+        /// Source project
+        #[arg(long)]
+        from: String,
+
+        /// Target project
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Wait for replication to catch up, sync sequences and stop replicating
+    Cutover {
+        /// Source project
+        #[arg(long)]
+        from: String,
+
+        /// Target project
+        #[arg(long)]
+        to: String,
+
+        /// Seconds to wait for replication to catch up
+        #[arg(long, default_value = "600")]
+        timeout: u64,
+
+        /// Tables excluded when replication was started (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude_tables: Option<Vec<String>>,
+
+        /// Schemas excluded when replication was started (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude_schemas: Option<Vec<String>>,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SequenceSync {
+    /// Copy last_value from the source sequences, never lowering a target sequence
+    Source,
+    /// Recalculate from the maximum value of each owning column
+    Max,
+    /// Leave sequences untouched
+    Skip,
+}
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct BackupArgs {
+    #[command(subcommand)]
+    pub command: Option<BackupCommands>,
+
     /// Project reference or alias to backup
-    #[arg(long, env = "SUPAMIGRATE_PROJECT")]
-    pub project: String,
+    #[arg(long, required = true, env = "SUPAMIGRATE_PROJECT")]
+    pub project: Option<String>,
 
-    /// Output directory for backup files
+    /// Output directory for backup files, or an s3://bucket/prefix location
     #[arg(short, long, default_value = "./backup")]
-    pub output: PathBuf,
+    pub output: String,
 
     /// Include storage objects in backup
     #[arg(long, default_value = "false")]
     pub include_storage: bool,
 
+    /// Keep storage objects in a content-addressed store shared by all backups in the
+    /// output directory, downloading only objects changed since the previous backup
+    #[arg(
+        long,
+        default_value = "false",
+        requires = "include_storage",
+        conflicts_with = "archive"
+    )]
+    pub incremental: bool,
+
     /// Include Supabase Vault secrets (with actual values)
     #[arg(long, default_value = "false")]
     pub include_vault: bool,
@@ -122,6 +318,10 @@ pub struct BackupArgs {
     #[arg(long, default_value = "false")]
     pub no_functions: bool,
 
+    /// Exclude custom database roles from backup (roles included by default)
+    #[arg(long, default_value = "false")]
+    pub no_roles: bool,
+
     /// Schema only (no data)
     #[arg(long, default_value = "false")]
     pub schema_only: bool,
@@ -129,17 +329,117 @@ pub struct BackupArgs {
     /// Compress output with gzip
     #[arg(long, default_value = "true")]
     pub compress: bool,
+
+    /// Write a single .tar.gz archive instead of a directory
+    #[arg(long, default_value = "false")]
+    pub archive: bool,
+
+    /// Do not apply the project's retention policy after the backup
+    #[arg(long, default_value = "false")]
+    pub no_prune: bool,
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommands {
+    /// Check every file of a backup against its manifest
+    Verify {
+        /// Backup directory, .tar.gz archive or s3:// location
+        dir: String,
+    },
+
+    /// Delete old backups according to the project's retention policy
+    Prune(PruneArgs),
+
+    /// List backups with their components, size and integrity
+    List(BackupListArgs),
+
+    /// Show tables, buckets, functions and secrets in a backup
+    Inspect {
+        /// Backup name (as shown by `backup list`), directory, archive or s3:// location
+        backup: String,
+
+        /// Backup directory or s3://bucket/prefix location holding named backups
+        #[arg(short, long, default_value = "./backup")]
+        output: String,
+    },
+
+    /// Show schema, data and object changes between two backups
+    Diff {
+        /// Older backup (name, directory, archive or s3:// location)
+        from: String,
+
+        /// Newer backup (name, directory, archive or s3:// location)
+        to: String,
+
+        /// Backup directory or s3://bucket/prefix location holding named backups
+        #[arg(short, long, default_value = "./backup")]
+        output: String,
+    },
 }
 
 #[derive(Parser)]
-pub struct RestoreArgs {
-    /// Backup directory or file to restore from
+pub struct BackupListArgs {
+    /// Backup directory or s3://bucket/prefix location
+    #[arg(short, long, default_value = "./backup", visible_aliases = ["dir", "dest"])]
+    pub output: String,
+
+    /// Only list backups of this project alias
     #[arg(long)]
-    pub from: PathBuf,
+    pub project: Option<String>,
+
+    /// Check every file against its manifest checksum instead of only its size
+    #[arg(long, default_value = "false")]
+    pub verify: bool,
+}
+
+#[derive(Parser)]
+pub struct PruneArgs {
+    /// Project reference or alias whose backups to prune
+    #[arg(long, env = "SUPAMIGRATE_PROJECT")]
+    pub project: String,
+
+    /// Backup directory or s3://bucket/prefix location
+    #[arg(short, long, default_value = "./backup")]
+    pub output: String,
+
+    /// Keep the N most recent backups (overrides config)
+    #[arg(long)]
+    pub keep_last: Option<u32>,
+
+    /// Keep one backup per day for this many days (overrides config)
+    #[arg(long)]
+    pub keep_daily: Option<u32>,
+
+    /// Keep one backup per week for this many weeks (overrides config)
+    #[arg(long)]
+    pub keep_weekly: Option<u32>,
+
+    /// Keep one backup per month for this many months (overrides config)
+    #[arg(long)]
+    pub keep_monthly: Option<u32>,
+
+    /// List what would be deleted without deleting anything
+    #[arg(long, default_value = "false")]
+    pub dry_run: bool,
+
+    /// Skip confirmation prompt
+    #[arg(short = 'y', long, default_value = "false")]
+    pub yes: bool,
+}
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct RestoreArgs {
+    #[command(subcommand)]
+    pub command: Option<RestoreCommands>,
+
+    /// Backup directory, .tar.gz archive or s3:// location to restore from
+    #[arg(long, required = true)]
+    pub from: Option<String>,
 
     /// Target project reference or alias
-    #[arg(long, env = "SUPAMIGRATE_TARGET")]
-    pub to: String,
+    #[arg(long, required = true, env = "SUPAMIGRATE_TARGET")]
+    pub to: Option<String>,
 
     /// Include storage objects
     #[arg(long, default_value = "false")]
@@ -161,11 +461,87 @@ pub struct RestoreArgs {
     #[arg(long, default_value = "false")]
     pub include_vault: bool,
 
+    /// Skip recreating custom database roles from backup
+    #[arg(long, default_value = "false")]
+    pub no_roles: bool,
+
+    /// Skip enabling the backed up extensions on the target
+    #[arg(long, default_value = "false")]
+    pub no_extensions: bool,
+
+    /// Only restore these tables with their indexes, constraints and sequences
+    /// (comma-separated, `table` or `schema.table`)
+    #[arg(long = "table", value_delimiter = ',')]
+    pub tables: Vec<String>,
+
+    /// Only restore the objects in these schemas (comma-separated)
+    #[arg(long = "schema", value_delimiter = ',')]
+    pub schemas: Vec<String>,
+
+    /// Only restore table data and sequence values into existing tables
+    #[arg(long, default_value = "false", conflicts_with = "into")]
+    pub data_only: bool,
+
+    /// Drop and recreate the selected --table or --schema objects, replacing their live rows;
+    /// without it they are restored with --data-only
+    #[arg(long, default_value = "false", conflicts_with_all = ["data_only", "into", "into_schema"])]
+    pub replace: bool,
+
+    /// Restore the single --table into this new table (`table` or `schema.table`) for comparison
+    #[arg(long, requires = "tables")]
+    pub into: Option<String>,
+
+    /// Restore into this new schema beside the live data instead of over it
+    #[arg(long, conflicts_with_all = ["into", "data_only"])]
+    pub into_schema: Option<String>,
+
+    /// Only restore these storage buckets (comma-separated)
+    #[arg(long = "bucket", value_delimiter = ',')]
+    pub buckets: Vec<String>,
+
+    /// Only restore these edge functions (comma-separated slugs)
+    #[arg(long = "function", value_delimiter = ',')]
+    pub functions: Vec<String>,
+
+    /// Include pg_cron scheduled jobs from backup
+    #[arg(long, default_value = "false")]
+    pub include_cron: bool,
+
+    /// Create restored cron jobs inactive
+    #[arg(long, default_value = "false")]
+    pub cron_disabled: bool,
+
+    /// Skip database webhooks (triggers calling supabase_functions.http_request)
+    #[arg(long, default_value = "false")]
+    pub no_webhooks: bool,
+
+    /// Restore even if files do not match the backup manifest
+    #[arg(long, default_value = "false")]
+    pub ignore_integrity: bool,
+
     /// Skip confirmation prompt
     #[arg(short = 'y', long, default_value = "false")]
     pub yes: bool,
 }
 
+#[derive(Subcommand)]
+pub enum RestoreCommands {
+    /// List the schemas created by `restore --into-schema`, or drop one of them
+    Cleanup {
+        /// Project reference or alias
+        #[arg(long, env = "SUPAMIGRATE_TARGET")]
+        to: String,
+
+        /// Recovery schema to drop
+        #[arg(long)]
+        schema: Option<String>,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+    },
+}
+
 #[derive(Parser)]
 pub struct StorageArgs {
     #[command(subcommand)]
@@ -280,6 +656,82 @@ pub enum SecretsCommands {
     },
 }
 
+#[derive(Parser)]
+pub struct FunctionsArgs {
+    #[command(subcommand)]
+    pub command: FunctionsCommands,
+}
+
+#[derive(Subcommand)]
+pub enum FunctionsCommands {
+    /// List edge functions in a project
+    List {
+        /// Project reference or alias
+        #[arg(long)]
+        project: String,
+    },
+
+    /// Download a function's source files
+    Download {
+        /// Project reference or alias
+        #[arg(long)]
+        project: String,
+
+        /// Function slug
+        #[arg(long)]
+        slug: String,
+
+        /// Directory the function's folder is written to
+        #[arg(short, long, default_value = "./functions")]
+        output: PathBuf,
+    },
+
+    /// Compare function files and settings between projects
+    Diff {
+        /// Source project
+        #[arg(long)]
+        from: String,
+
+        /// Target project
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Delete an edge function
+    Delete {
+        /// Project reference or alias
+        #[arg(long)]
+        project: String,
+
+        /// Function slug
+        #[arg(long)]
+        slug: String,
+
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long, default_value = "false")]
+        yes: bool,
+    },
+
+    /// Deploy functions from a local supabase/functions directory
+    Deploy {
+        /// Project reference or alias
+        #[arg(long)]
+        project: String,
+
+        /// Functions directory; per-function settings are read from config.toml next to it
+        #[arg(long, default_value = "supabase/functions")]
+        dir: PathBuf,
+
+        /// Only deploy these functions (comma-separated slugs)
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+
+        /// Number of functions deployed at once
+        #[arg(long, default_value = "4")]
+        parallel: usize,
+    },
+}
+
 #[derive(Parser)]
 pub struct VaultArgs {
     #[command(subcommand)]
@@ -329,6 +781,33 @@ pub enum VaultCommands {
     },
 }
 
+#[derive(Parser)]
+pub struct CronArgs {
+    #[command(subcommand)]
+    pub command: CronCommands,
+}
+
+#[derive(Subcommand)]
+pub enum CronCommands {
+    /// List scheduled jobs in a project
+    List {
+        /// Project reference or alias
+        #[arg(long)]
+        project: String,
+    },
+
+    /// Compare scheduled jobs between projects
+    Diff {
+        /// Source project
+        #[arg(long)]
+        from: String,
+
+        /// Target project
+        #[arg(long)]
+        to: String,
+    },
+}
+
 #[derive(Parser)]
 pub struct ConfigArgs {
     #[command(subcommand)]
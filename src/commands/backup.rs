@@ -1,75 +1,229 @@
-use crate::cli::BackupArgs;
+use crate::backup::{
+    backup_storage, create_archive, list_backups, retention_plan, BackupContents,
+    BackupDestination, BackupEntry, BackupMetadata, BackupSource, Change, Encryption, Integrity,
+    Manifest, ObjectStore, S3Client, S3Url, StorageIndex, ARCHIVE_EXTENSION, LATE_OBJECTS_FILE,
+    MANIFEST_FILE, METADATA_FILE, PARTIAL_SUFFIX, STORAGE_HASHES_FILE,
+};
+use crate::cli::{BackupArgs, BackupCommands, BackupListArgs, PruneArgs};
+use crate::commands::common::warn_skipped_superusers;
 use crate::commands::secrets::backup_secrets;
 use crate::commands::vault::backup_vault;
-use crate::config::Config;
-use crate::db::PgDump;
+use crate::config::{Config, RetentionConfig};
+use crate::db::{CronClient, DbSnapshot, ExtensionsClient, PgDump, RolesClient};
 use crate::functions::FunctionsClient;
-use crate::storage::{StorageClient, StorageTransfer};
+use crate::storage::{human_bytes, StorageClient, StorageTransfer};
 use anyhow::Result;
 use chrono::Utc;
 use console::style;
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::Write;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
 use tracing::info;
 
 pub async fn run(args: BackupArgs) -> Result<()> {
+    match args.command {
+        Some(BackupCommands::Verify { dir }) => verify(&dir).await,
+        Some(BackupCommands::Prune(args)) => prune(args).await,
+        Some(BackupCommands::List(args)) => list(args).await,
+        Some(BackupCommands::Inspect { backup, output }) => inspect(&backup, &output).await,
+        Some(BackupCommands::Diff { from, to, output }) => diff(&from, &to, &output).await,
+        None => backup(args).await,
+    }
+}
+
+async fn backup(args: BackupArgs) -> Result<()> {
     let config = Config::load(None)?;
-    let project = config.get_project(&args.project)?;
 
-    // Create output directory with timestamp
+    // clap requires the project when no subcommand is given
+    let project_name = args.project.clone().unwrap_or_default();
+    let project = config.get_project(&project_name)?;
+
+    // Remote backups are written to a staging directory first, then uploaded
+    let remote = S3Url::parse(&args.output);
+    let s3 = remote
+        .as_ref()
+        .map(|_| S3Client::from_config(config.s3.as_ref()))
+        .transpose()?;
+    if remote.is_some() && args.incremental {
+        return Err(anyhow::anyhow!(
+            "--incremental needs a local output directory to hold the shared object store"
+        ));
+    }
+    // Functions are backed up by default, so check their credential before any work
+    if !args.no_functions {
+        project
+            .management_token()
+            .map_err(|e| anyhow::anyhow!("{} (pass --no-functions to skip edge functions)", e))?;
+    }
+    let staging = remote.as_ref().map(|_| tempfile::tempdir()).transpose()?;
+    let output_dir = staging
+        .as_ref()
+        .map_or_else(|| PathBuf::from(&args.output), |d| d.path().to_path_buf());
+
+    // Create output directory with timestamp; it keeps a partial name until the
+    // backup is complete, so a failed run is never taken for a usable backup
     let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-    let backup_dir = args.output.join(format!("{}_{}", args.project, timestamp));
+    let backup_name = format!("{}_{}", project_name, timestamp);
+    let backup_dir = partial_path(&output_dir.join(&backup_name));
     fs::create_dir_all(&backup_dir)?;
 
     let include_functions = !args.no_functions;
+    let include_roles = !args.no_roles;
+    // The archive is gzipped as a whole, so keep the dump inside it plain
+    let compress = args.compress && !args.archive;
+    let encryption = Encryption::from_configs([project.encryption.as_ref()])?;
 
     println!("\n{} Backup Plan", style("📋").bold());
-    println!("  Project: {} ({})", args.project, project.project_ref);
-    println!("  Output: {}", backup_dir.display());
+    println!("  Project: {} ({})", project_name, project.project_ref);
+    match &remote {
+        Some(url) => println!("  Output: {}", url),
+        None => println!("  Output: {}", output_dir.join(&backup_name).display()),
+    }
     println!("  Schema only: {}", args.schema_only);
-    println!("  Include storage: {}", args.include_storage);
+    println!(
+        "  Include storage: {}{}",
+        args.include_storage,
+        if args.incremental {
+            " (incremental)"
+        } else {
+            ""
+        }
+    );
     println!("  Include functions: {}", include_functions);
+    println!("  Include roles: {}", include_roles);
     println!("  Include vault: {}", args.include_vault);
-    println!("  Compress: {}", args.compress);
+    println!("  Compress: {}", compress);
+    println!("  Archive: {}", args.archive);
+    println!(
+        "  Encrypt: {}",
+        encryption
+            .as_ref()
+            .map_or_else(|| "no".to_string(), Encryption::describe)
+    );
 
     // Database backup
     println!("\n{} Backing up database...", style("🗄️").bold());
 
-    let dump_file = if args.compress {
+    let dump_file = if compress {
         backup_dir.join("database.sql.gz")
     } else {
         backup_dir.join("database.sql")
     };
 
+    // Dump from an exported snapshot so its WAL position and time are exact;
+    // storage objects changed after it are left out of the backup
+    let snapshot = match DbSnapshot::export(&project.db_url()) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            println!(
+                "  {} Could not export a database snapshot, using the time the dump started: {}",
+                style("⚠").yellow(),
+                e
+            );
+            DbSnapshot::current(&project.db_url())?
+        }
+    };
+
     let dump = PgDump::new(project.db_url())
         .exclude_schemas(config.defaults.excluded_schemas.clone())
         .schema_only(args.schema_only)
+        .snapshot(snapshot.id.clone())
         .dump_to_string()?;
+    // End the snapshot's transaction now, so it does not hold back vacuum on the
+    // source while the rest of the backup runs
+    let info = snapshot.info.clone();
+    drop(snapshot);
 
-    if args.compress {
-        use std::io::BufWriter;
-        let file = fs::File::create(&dump_file)?;
-        let mut encoder =
-            flate2::write::GzEncoder::new(BufWriter::new(file), flate2::Compression::default());
+    if compress {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
         encoder.write_all(dump.as_bytes())?;
-        encoder.finish()?;
+        write_file(&dump_file, &encoder.finish()?, encryption.as_ref())?;
     } else {
-        fs::write(&dump_file, &dump)?;
+        write_file(&dump_file, dump.as_bytes(), encryption.as_ref())?;
     }
 
     info!("Database backup saved to: {}", dump_file.display());
-    println!("{} Database backup complete!", style("✓").green());
+
+    // Extensions live in excluded schemas, so record them for restore
+    let extensions = ExtensionsClient::new(project.db_url()).list_installed()?;
+    let extensions_file = backup_dir.join("extensions.json");
+    write_file(
+        &extensions_file,
+        serde_json::to_string_pretty(&extensions)?.as_bytes(),
+        encryption.as_ref(),
+    )?;
+    info!(
+        "{} extensions saved to: {}",
+        extensions.len(),
+        extensions_file.display()
+    );
+
+    println!(
+        "{} Database backup complete! (snapshot at {}, LSN {})",
+        style("✓").green(),
+        info.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+        info.lsn
+    );
+
+    // Custom roles backup (pg_dump does not include role definitions)
+    let mut roles_count = 0;
+    if include_roles {
+        println!("\n{} Backing up custom roles...", style("👥").bold());
+
+        let roles_backup = RolesClient::new(project.db_url()).backup()?;
+        roles_count = roles_backup.roles.len();
+        warn_skipped_superusers(&roles_backup.skipped_superusers);
+        if roles_backup.is_empty() {
+            println!("{} No custom roles found", style("ℹ").blue());
+        } else {
+            let roles_file = backup_dir.join("roles.json");
+            write_file(
+                &roles_file,
+                serde_json::to_string_pretty(&roles_backup)?.as_bytes(),
+                encryption.as_ref(),
+            )?;
+            info!("Roles backup saved to: {}", roles_file.display());
+            println!(
+                "{} Roles backup complete: {} roles, {} memberships",
+                style("✓").green(),
+                roles_count,
+                roles_backup.memberships.len()
+            );
+        }
+    }
+
+    // Cron jobs backup (if pg_cron is enabled)
+    let mut cron_count = 0;
+    let cron_client = CronClient::new(project.db_url());
+    if cron_client.is_cron_enabled()? {
+        println!("\n{} Backing up cron jobs...", style("⏰").bold());
+
+        let cron_backup = cron_client.backup()?;
+        cron_count = cron_backup.jobs.len();
+        let cron_file = backup_dir.join("cron_jobs.json");
+        write_file(
+            &cron_file,
+            serde_json::to_string_pretty(&cron_backup)?.as_bytes(),
+            encryption.as_ref(),
+        )?;
+        info!("Cron jobs backup saved to: {}", cron_file.display());
+        println!(
+            "{} Cron jobs backup complete: {} jobs",
+            style("✓").green(),
+            cron_count
+        );
+    }
 
     // Edge Functions backup (included by default)
     if include_functions {
         println!("\n{} Backing up edge functions...", style("⚡").bold());
 
-        let service_key = project.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Project requires service_key for edge functions backup")
-        })?;
+        let access_token = project.management_token()?;
 
         let functions_client =
-            FunctionsClient::new(project.project_ref.clone(), service_key.clone());
+            FunctionsClient::new(project.project_ref.clone(), access_token.to_string());
 
         let functions = functions_client.backup_all().await?;
         let functions_dir = backup_dir.join("functions");
@@ -87,9 +241,10 @@ pub async fn run(args: BackupArgs) -> Result<()> {
                 "entrypoint_path": func.entrypoint_path,
                 "import_map_path": func.import_map_path,
             });
-            fs::write(
-                func_dir.join("metadata.json"),
-                serde_json::to_string_pretty(&metadata)?,
+            write_file(
+                &func_dir.join("metadata.json"),
+                serde_json::to_string_pretty(&metadata)?.as_bytes(),
+                encryption.as_ref(),
             )?;
 
             // Save function files
@@ -98,7 +253,7 @@ pub async fn run(args: BackupArgs) -> Result<()> {
                 if let Some(parent) = file_path.parent() {
                     fs::create_dir_all(parent)?;
                 }
-                fs::write(&file_path, &file.content)?;
+                write_file(&file_path, &file.content, encryption.as_ref())?;
             }
 
             info!("Backed up function: {}", func.slug);
@@ -116,13 +271,14 @@ pub async fn run(args: BackupArgs) -> Result<()> {
     if project.has_secrets_access() {
         println!("\n{} Backing up secrets...", style("🔐").bold());
 
-        match backup_secrets(&args.project).await? {
+        match backup_secrets(&project_name).await? {
             Some(secrets_backup) => {
                 secrets_count = secrets_backup.secrets.len();
                 let secrets_file = backup_dir.join("secrets.json");
-                fs::write(
+                write_file(
                     &secrets_file,
-                    serde_json::to_string_pretty(&secrets_backup)?,
+                    serde_json::to_string_pretty(&secrets_backup)?.as_bytes(),
+                    encryption.as_ref(),
                 )?;
                 info!("Secrets backup saved to: {}", secrets_file.display());
                 println!(
@@ -153,21 +309,27 @@ pub async fn run(args: BackupArgs) -> Result<()> {
     if args.include_vault {
         println!("\n{} Backing up vault secrets...", style("🔐").bold());
 
-        match backup_vault(&args.project) {
+        match backup_vault(&project_name) {
             Ok(Some(vault_backup)) => {
                 vault_count = vault_backup.secrets.len();
                 let vault_file = backup_dir.join("vault_secrets.json");
-                fs::write(&vault_file, serde_json::to_string_pretty(&vault_backup)?)?;
+                write_file(
+                    &vault_file,
+                    serde_json::to_string_pretty(&vault_backup)?.as_bytes(),
+                    encryption.as_ref(),
+                )?;
                 info!("Vault backup saved to: {}", vault_file.display());
                 println!(
                     "{} Vault backup complete: {} secrets (with values)",
                     style("✓").green(),
                     vault_count
                 );
-                println!(
-                    "  {} vault_secrets.json contains decrypted values - store securely!",
-                    style("⚠").yellow()
-                );
+                if encryption.is_none() {
+                    println!(
+                        "  {} vault_secrets.json contains decrypted values - store securely!",
+                        style("⚠").yellow()
+                    );
+                }
             }
             Ok(None) => {
                 println!(
@@ -182,22 +344,65 @@ pub async fn run(args: BackupArgs) -> Result<()> {
     }
 
     // Storage backup
+    let mut late_objects = Vec::new();
     if args.include_storage {
         println!("\n{} Backing up storage...", style("📦").bold());
 
-        let service_key = project
-            .service_key
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("Project requires service_key for storage backup"))?;
+        let service_key = project.storage_key()?;
+
+        let storage = StorageClient::new(project.api_url(), service_key.to_string());
 
-        let storage = StorageClient::new(project.api_url(), service_key.clone());
-        let storage_dir = backup_dir.join("storage");
-        fs::create_dir_all(&storage_dir)?;
+        if args.incremental {
+            let store = ObjectStore::open(&output_dir);
+            let previous = StorageIndex::latest(&output_dir, &project_name)?;
+            if previous.is_none() {
+                info!("No earlier incremental backup found, downloading every object");
+            }
 
-        let transfer = StorageTransfer::new(storage).parallel(config.defaults.parallel_transfers);
+            let (index, stats) = backup_storage(
+                &storage,
+                &store,
+                previous.as_ref(),
+                encryption.as_ref(),
+                config.defaults.parallel_transfers,
+                Some(info.timestamp),
+            )
+            .await?;
+            index.write(&backup_dir)?;
+            println!("{} Storage backup complete: {}", style("✓").green(), stats);
+            late_objects = stats.late;
+        } else {
+            let storage_dir = backup_dir.join("storage");
+            fs::create_dir_all(&storage_dir)?;
+
+            let transfer = StorageTransfer::new(storage)
+                .parallel(config.defaults.parallel_transfers)
+                .snapshot(Some(info.timestamp))
+                .encryption(encryption.as_ref());
+
+            let stats = transfer.download_all(&storage_dir).await?;
+            write_file(
+                &backup_dir.join(STORAGE_HASHES_FILE),
+                serde_json::to_string_pretty(&stats.hashes)?.as_bytes(),
+                encryption.as_ref(),
+            )?;
+            println!("{} Storage backup complete: {}", style("✓").green(), stats);
+            late_objects = stats.late;
+        }
 
-        let stats = transfer.download_all(&storage_dir).await?;
-        println!("{} Storage backup complete: {}", style("✓").green(), stats);
+        if !late_objects.is_empty() {
+            write_file(
+                &backup_dir.join(LATE_OBJECTS_FILE),
+                serde_json::to_string_pretty(&late_objects)?.as_bytes(),
+                encryption.as_ref(),
+            )?;
+            println!(
+                "  {} {} objects changed after the database snapshot were left out (listed in {})",
+                style("⚠").yellow(),
+                late_objects.len(),
+                LATE_OBJECTS_FILE
+            );
+        }
     }
 
     // Write metadata
@@ -206,33 +411,540 @@ pub async fn run(args: BackupArgs) -> Result<()> {
         timestamp: Utc::now().to_rfc3339(),
         schema_only: args.schema_only,
         include_storage: args.include_storage,
+        incremental_storage: args.incremental,
         include_functions,
+        include_roles: roles_count > 0,
+        roles_count,
+        include_cron: cron_count > 0,
+        cron_count,
         include_secrets: secrets_count > 0,
         secrets_count,
         include_vault: vault_count > 0,
         vault_count,
-        compressed: args.compress,
+        compressed: compress,
+        encrypted: encryption.is_some(),
+        snapshot: Some(info),
+        late_objects: late_objects.len(),
     };
 
-    let metadata_file = backup_dir.join("metadata.json");
+    let metadata_file = backup_dir.join(METADATA_FILE);
     fs::write(&metadata_file, serde_json::to_string_pretty(&metadata)?)?;
 
+    // Manifest last, so it covers every other file
+    let manifest = Manifest::generate(&backup_dir)?;
+    manifest.write(&backup_dir)?;
+    info!(
+        "Manifest saved: {} files, {} bytes",
+        manifest.files.len(),
+        manifest.total_size()
+    );
+
+    let location = if args.archive {
+        let archive_path = output_dir.join(format!("{}.{}", backup_name, ARCHIVE_EXTENSION));
+        info!("Packing backup into {}", archive_path.display());
+        let partial_archive = partial_path(&archive_path);
+        create_archive(&backup_dir, &partial_archive)?;
+        fs::rename(&partial_archive, &archive_path)?;
+        fs::remove_dir_all(&backup_dir)?;
+        archive_path
+    } else {
+        let path = output_dir.join(&backup_name);
+        fs::rename(&backup_dir, &path)?;
+        path
+    };
+
+    let location = match (&s3, &remote) {
+        (Some(s3), Some(url)) => {
+            println!("\n{} Uploading to {}...", style("☁️").bold(), url);
+            s3.upload_backup(&location, url).await?.to_string()
+        }
+        _ => location.display().to_string(),
+    };
+
     println!("\n{} Backup completed successfully!", style("🎉").bold());
-    println!("  Location: {}", backup_dir.display());
+    println!("  Location: {}", location);
+
+    if let Some(policy) = project
+        .retention
+        .as_ref()
+        .filter(|p| !p.is_empty() && !args.no_prune)
+    {
+        println!(
+            "\n{} Applying retention policy ({})...",
+            style("🧹").bold(),
+            policy
+        );
+        match apply_retention(&args.output, &project_name, policy, &config).await {
+            Ok((kept, deleted)) => println!(
+                "{} Pruned {} old backups, {} kept",
+                style("✓").green(),
+                deleted,
+                kept
+            ),
+            Err(e) => println!("{} Pruning failed: {}", style("⚠").yellow(), e),
+        }
+    }
+
+    Ok(())
+}
+
+/// `<path>.partial`, where a backup is written until it is complete
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Delete the backups of `project` at `location` that `policy` does not keep;
+/// returns how many were kept and deleted
+async fn apply_retention(
+    location: &str,
+    project: &str,
+    policy: &RetentionConfig,
+    config: &Config,
+) -> Result<(usize, usize)> {
+    let destination = BackupDestination::open(location, config.s3.as_ref())?;
+    let plan = retention_plan(
+        policy,
+        destination.list(Some(project)).await?,
+        Utc::now().naive_utc(),
+    );
+    delete_backups(&destination, &plan.delete).await?;
+    Ok((plan.keep.len(), plan.delete.len()))
+}
+
+/// Delete `backups`, then garbage-collect blobs of the object store nothing refers to anymore
+async fn delete_backups(destination: &BackupDestination, backups: &[BackupEntry]) -> Result<()> {
+    for backup in backups {
+        info!("Deleting backup {}", backup.name);
+        destination.delete(backup).await?;
+    }
+
+    if let Some(gc) = destination.collect_garbage(backups, false)? {
+        if gc.blobs > 0 {
+            println!(
+                "{} Removed unreferenced objects from the object store: {}",
+                style("✓").green(),
+                gc
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn prune(args: PruneArgs) -> Result<()> {
+    let config = Config::load(None)?;
+    let project = config.get_project(&args.project)?;
+
+    let mut policy = project.retention.clone().unwrap_or_default();
+    policy.keep_last = args.keep_last.unwrap_or(policy.keep_last);
+    policy.keep_daily = args.keep_daily.unwrap_or(policy.keep_daily);
+    policy.keep_weekly = args.keep_weekly.unwrap_or(policy.keep_weekly);
+    policy.keep_monthly = args.keep_monthly.unwrap_or(policy.keep_monthly);
+    if policy.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No retention policy for '{}': add [projects.{}.retention] to the config or pass --keep-* options",
+            args.project,
+            args.project
+        ));
+    }
+
+    let destination = BackupDestination::open(&args.output, config.s3.as_ref())?;
+    let plan = retention_plan(
+        &policy,
+        destination.list(Some(&args.project)).await?,
+        Utc::now().naive_utc(),
+    );
+
+    println!("\n{} Prune Plan", style("📋").bold());
+    println!("  Project: {}", args.project);
+    println!("  Location: {}", args.output);
+    println!("  Policy: {}", policy);
+    println!("  Keep: {} backups", plan.keep.len());
+    for backup in &plan.keep {
+        println!("    {} {}", style("✓").green(), backup.name);
+    }
+    println!("  Delete: {} backups", plan.delete.len());
+    for backup in &plan.delete {
+        println!("    {} {}", style("✗").red(), backup.name);
+    }
+    let garbage = destination
+        .collect_garbage(&plan.delete, true)?
+        .unwrap_or_default();
+    if garbage.blobs > 0 {
+        println!("  Unreferenced objects: {}", garbage);
+    }
+
+    if plan.delete.is_empty() && garbage.blobs == 0 {
+        println!("\n{} Nothing to prune", style("✓").green());
+        return Ok(());
+    }
+
+    if args.dry_run {
+        println!("\n{} Dry run - no changes will be made", style("ℹ️").cyan());
+        return Ok(());
+    }
+
+    if !args.yes {
+        print!("\nDelete {} backups? [y/N] ", plan.delete.len());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Prune cancelled.");
+            return Ok(());
+        }
+    }
+
+    delete_backups(&destination, &plan.delete).await?;
+
+    println!(
+        "\n{} Pruned {} backups, {} kept",
+        style("✓").green(),
+        plan.delete.len(),
+        plan.keep.len()
+    );
+    Ok(())
+}
+
+async fn verify(location: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let (dir, _staging) = fetch_backup(location, &config).await?;
+    let source = BackupSource::open(&dir)?;
+    let manifest = source
+        .manifest()?
+        .ok_or_else(|| anyhow::anyhow!("{} not found in {}", MANIFEST_FILE, location))?;
+
+    println!("\n{} Verifying backup {}", style("🔍").bold(), location);
+    println!("  Format version: {}", manifest.format_version);
+    println!("  Created: {}", manifest.created_at);
+
+    let report = source.verify()?.unwrap_or_default();
+    for problem in &report.problems {
+        println!("  {} {}", style("✗").red(), problem);
+    }
 
+    if !report.is_ok() {
+        return Err(anyhow::anyhow!(
+            "Backup verification failed: {} problems in {} files",
+            report.problems.len(),
+            report.checked
+        ));
+    }
+
+    println!(
+        "\n{} All {} files match ({} bytes)",
+        style("✓").green(),
+        report.checked,
+        manifest.total_size()
+    );
     Ok(())
 }
 
-#[derive(serde::Serialize)]
-struct BackupMetadata {
-    project_ref: String,
-    timestamp: String,
-    schema_only: bool,
-    include_storage: bool,
-    include_functions: bool,
-    include_secrets: bool,
-    secrets_count: usize,
-    include_vault: bool,
-    vault_count: usize,
-    compressed: bool,
+async fn list(args: BackupListArgs) -> Result<()> {
+    let config = Config::load(None)?;
+    let destination = BackupDestination::open(&args.output, config.s3.as_ref())?;
+    let summaries = list_backups(&destination, args.project.as_deref(), args.verify).await?;
+
+    println!(
+        "\n{} Backups in {} ({} found)",
+        style("📦").bold(),
+        args.output,
+        summaries.len()
+    );
+    println!("{:-<60}", "");
+
+    if summaries.is_empty() {
+        println!("  No backups found");
+        return Ok(());
+    }
+
+    for summary in &summaries {
+        let components = summary.metadata.as_ref().map_or_else(
+            || "unknown contents".to_string(),
+            |m| {
+                let mut components = m.components().join(", ");
+                if m.incremental_storage {
+                    components.push_str(" (incremental)");
+                }
+                if m.encrypted {
+                    components.push_str(", encrypted");
+                }
+                components
+            },
+        );
+        let integrity = match summary.integrity {
+            Integrity::SizesMatch | Integrity::Verified => {
+                style(summary.integrity.to_string()).green()
+            }
+            Integrity::Problems(_) => style(summary.integrity.to_string()).red(),
+            Integrity::NoManifest | Integrity::NotChecked => {
+                style(summary.integrity.to_string()).yellow()
+            }
+        };
+
+        println!(
+            "  {} {} ({})",
+            style("•").cyan(),
+            style(&summary.entry.name).bold(),
+            summary.entry.project
+        );
+        println!(
+            "    {} · {} · {} · {}",
+            summary.entry.created.format("%Y-%m-%d %H:%M:%S"),
+            human_bytes(summary.size as usize),
+            style(components).dim(),
+            integrity
+        );
+    }
+
+    let damaged = summaries.iter().filter(|s| !s.integrity.is_ok()).count();
+    if damaged > 0 {
+        println!(
+            "\n{} {} backups failed the integrity check; run `supamigrate backup verify <backup>` for details",
+            style("⚠").yellow(),
+            damaged
+        );
+    }
+    Ok(())
+}
+
+async fn inspect(backup: &str, output: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let (location, contents) = load_contents(backup, output, &config).await?;
+    let metadata = &contents.metadata;
+
+    println!("\n{} Backup {}", style("🔍").bold(), location);
+    println!("  Project: {}", metadata.project_ref);
+    println!("  Created: {}", metadata.timestamp);
+    println!("  Components: {}", metadata.components().join(", "));
+    println!("  Encrypted: {}", metadata.encrypted);
+    if let Some(snapshot) = &metadata.snapshot {
+        println!(
+            "  Snapshot: {} (LSN {}{})",
+            snapshot.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            snapshot.lsn,
+            if snapshot.exported {
+                ""
+            } else {
+                ", approximate"
+            }
+        );
+    }
+    if metadata.late_objects > 0 {
+        println!(
+            "  Storage objects changed after the snapshot (not included): {}",
+            metadata.late_objects
+        );
+    }
+
+    println!(
+        "\n{} Tables ({})",
+        style("🗄️").bold(),
+        contents.toc.rows.len()
+    );
+    if metadata.schema_only {
+        println!("  Schema only - no table data");
+    }
+    for (table, rows) in &contents.toc.rows {
+        println!("  {} {} - {} rows", style("•").cyan(), table, rows);
+    }
+
+    let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+    for key in contents.toc.objects.keys() {
+        // Keys are `"{TYPE} {name}"`, where the type is one or more upper-case words
+        let end = key
+            .match_indices(' ')
+            .map(|(i, _)| i)
+            .find(|&i| !key[i + 1..].starts_with(|c: char| c.is_ascii_uppercase()))
+            .unwrap_or(key.len());
+        *kinds.entry(&key[..end]).or_default() += 1;
+    }
+    println!(
+        "\n{} Schema objects ({})",
+        style("📐").bold(),
+        contents.toc.objects.len()
+    );
+    for (kind, count) in &kinds {
+        println!("  {} {}: {}", style("•").cyan(), kind, count);
+    }
+
+    println!(
+        "\n{} Buckets ({})",
+        style("📦").bold(),
+        contents.buckets.len()
+    );
+    for (bucket, objects) in &contents.buckets {
+        println!(
+            "  {} {} - {} objects",
+            style("•").cyan(),
+            bucket,
+            objects.len()
+        );
+    }
+
+    println!(
+        "\n{} Functions ({})",
+        style("⚡").bold(),
+        contents.functions.len()
+    );
+    for slug in contents.functions.keys() {
+        println!("  {} {}", style("•").cyan(), slug);
+    }
+
+    println!(
+        "\n{} Secrets ({})",
+        style("🔐").bold(),
+        contents.secrets.len()
+    );
+    for name in &contents.secrets {
+        println!("  {} {}", style("•").cyan(), name);
+    }
+    if !contents.vault_secrets.is_empty() {
+        println!(
+            "\n{} Vault secrets ({})",
+            style("🔐").bold(),
+            contents.vault_secrets.len()
+        );
+        for name in &contents.vault_secrets {
+            println!("  {} {}", style("•").cyan(), name);
+        }
+    }
+
+    Ok(())
+}
+
+async fn diff(from: &str, to: &str, output: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let (from_location, older) = load_contents(from, output, &config).await?;
+    let (to_location, newer) = load_contents(to, output, &config).await?;
+    let diff = older.diff(&newer);
+
+    println!(
+        "\n{} Comparing {} → {}",
+        style("🔍").bold(),
+        from_location,
+        to_location
+    );
+
+    if diff.is_empty() {
+        println!("\n{} Backups are identical", style("✓").green());
+        return Ok(());
+    }
+
+    print_changes("📐", "Schema", &diff.schema);
+    if !diff.rows.is_empty() {
+        println!("\n{} Row counts", style("🗄️").bold());
+        for (table, before, after) in &diff.rows {
+            let count =
+                |rows: &Option<u64>| rows.map_or_else(|| "-".to_string(), |r| r.to_string());
+            println!(
+                "  {} {}: {} → {}",
+                style("~").yellow(),
+                table,
+                count(before),
+                count(after)
+            );
+        }
+    }
+    print_changes("📦", "Storage objects", &diff.storage);
+    print_changes("⚡", "Functions", &diff.functions);
+    print_changes("🔐", "Secrets", &diff.secrets);
+    print_changes("🔐", "Vault secrets", &diff.vault_secrets);
+
+    Ok(())
+}
+
+fn print_changes(icon: &str, title: &str, changes: &[Change]) {
+    if changes.is_empty() {
+        return;
+    }
+
+    println!(
+        "\n{} {} ({} changes)",
+        style(icon).bold(),
+        title,
+        changes.len()
+    );
+    for change in changes {
+        match change {
+            Change::Added(name) => println!("  {} {}", style("+").green(), name),
+            Change::Removed(name) => println!("  {} {}", style("-").red(), name),
+            Change::Changed(name) => println!("  {} {}", style("~").yellow(), name),
+        }
+    }
+}
+
+/// Location of a backup given by name (looked up in `output`), path or s3:// URL
+fn resolve_backup(backup: &str, output: &str) -> String {
+    if S3Url::parse(backup).is_some() || Path::new(backup).exists() {
+        return backup.to_string();
+    }
+    if let Some(url) = S3Url::parse(output) {
+        return url.join(backup).to_string();
+    }
+
+    let dir = Path::new(output);
+    [
+        dir.join(backup),
+        dir.join(format!("{}.{}", backup, ARCHIVE_EXTENSION)),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+    .map_or_else(|| backup.to_string(), |p| p.display().to_string())
+}
+
+/// Fetch and read a backup, decrypting it with the keys of the project it was taken from
+async fn load_contents(
+    backup: &str,
+    output: &str,
+    config: &Config,
+) -> Result<(String, BackupContents)> {
+    let location = resolve_backup(backup, output);
+    let (dir, _staging) = fetch_backup(&location, config).await?;
+    let mut source = BackupSource::open(&dir)?;
+
+    let metadata = source.metadata()?;
+    if metadata.encrypted {
+        let project = config.get_project(&metadata.project_ref).ok();
+        let encryption = Encryption::from_configs([project.and_then(|p| p.encryption.as_ref())])?
+            .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Backup {} is encrypted but {} has no encryption configured",
+                location,
+                metadata.project_ref
+            )
+        })?;
+        source.set_encryption(Some(encryption));
+    }
+
+    let contents = BackupContents::load(&source)?;
+    Ok((location, contents))
+}
+
+/// Local path of a backup; `s3://` locations are downloaded into a temporary
+/// directory that lives as long as the returned guard
+pub async fn fetch_backup(location: &str, config: &Config) -> Result<(PathBuf, Option<TempDir>)> {
+    let Some(url) = S3Url::parse(location) else {
+        return Ok((PathBuf::from(location), None));
+    };
+
+    println!("\n{} Downloading {}...", style("☁️").bold(), url);
+    let s3 = S3Client::from_config(config.s3.as_ref())?;
+    let staging = tempfile::tempdir()?;
+    let path = s3.download_backup(&url, staging.path()).await?;
+    Ok((path, Some(staging)))
+}
+
+/// Write a backup file, encrypted to `<path>.age` when the project has encryption configured
+fn write_file(path: &Path, content: &[u8], encryption: Option<&Encryption>) -> Result<()> {
+    match encryption {
+        Some(encryption) => {
+            encryption.write_file(path, content)?;
+        }
+        None => fs::write(path, content)?,
+    }
+    Ok(())
 }
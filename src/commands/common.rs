@@ -0,0 +1,206 @@
+use crate::cli::SequenceSync;
+use crate::db::sequences::{self, plan_from_source};
+use crate::db::{
+    CronBackup, CronClient, Extension, ExtensionsClient, SequenceChange, SequencesClient,
+};
+use anyhow::Result;
+use console::style;
+
+/// Passwords cannot be read from the catalog, so login roles need a new one
+pub fn warn_login_roles(login_roles: &[&str]) {
+    if login_roles.is_empty() {
+        return;
+    }
+    println!(
+        "  {} Login roles were created without passwords: {}",
+        style("⚠").yellow(),
+        login_roles.join(", ")
+    );
+    println!("  Set them with: ALTER ROLE <name> WITH PASSWORD '...'");
+}
+
+/// Superuser roles cannot be created on Supabase, so they are not copied
+pub fn warn_skipped_superusers(superusers: &[String]) {
+    if superusers.is_empty() {
+        return;
+    }
+    println!(
+        "  {} Superuser roles were skipped: {}",
+        style("⚠").yellow(),
+        superusers.join(", ")
+    );
+    println!("  Recreate them by hand without SUPERUSER if they are still needed");
+}
+
+/// Webhook headers keep source keys that have no configured target counterpart
+pub fn warn_source_keys(webhooks: usize) {
+    if webhooks == 0 {
+        return;
+    }
+    println!(
+        "  {} {} webhooks still send an API key of the source project",
+        style("⚠").yellow(),
+        webhooks
+    );
+    println!(
+        "  Configure anon_key and service_key for both projects, or update the headers by hand"
+    );
+}
+
+/// Enable the source's extensions on the target and report the ones that are
+/// unavailable or fail to install. Only fails when the schema depends on one of them.
+pub fn enable_extensions(extensions: &[Extension], target_db_url: String) -> Result<()> {
+    let client = ExtensionsClient::new(target_db_url);
+    let plan = client.plan(extensions)?;
+
+    let mut enabled = 0;
+    let mut failed = Vec::new();
+    for extension in &plan.to_enable {
+        match client.enable(extension) {
+            Ok(()) => {
+                enabled += 1;
+                println!(
+                    "  {} Enabled {} ({}) in schema {}",
+                    style("✓").green(),
+                    extension.name,
+                    extension.version,
+                    extension.schema
+                );
+            }
+            Err(e) => {
+                println!(
+                    "  {} Failed to enable {}: {}",
+                    style("⚠").yellow(),
+                    extension.name,
+                    e.to_string().trim()
+                );
+                failed.push(extension);
+            }
+        }
+    }
+
+    if !plan.unavailable.is_empty() {
+        let version = client
+            .server_version()
+            .map_or_else(|| "unknown".to_string(), |v| v.to_string());
+        for extension in &plan.unavailable {
+            println!(
+                "  {} {} is not available on the target (PostgreSQL {})",
+                style("⚠").yellow(),
+                extension.name,
+                version
+            );
+        }
+    }
+
+    println!(
+        "{} Extensions: {} enabled, {} already installed, {} unavailable, {} failed",
+        style("✓").green(),
+        enabled,
+        plan.installed.len(),
+        plan.unavailable.len(),
+        failed.len()
+    );
+
+    let required: Vec<&str> = failed
+        .into_iter()
+        .chain(&plan.unavailable)
+        .filter(|e| e.required)
+        .map(|e| e.name.as_str())
+        .collect();
+    if !required.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The schema depends on extensions that could not be enabled on the target: {}",
+            required.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Advance the target's sequences to match the source and report changed ones
+pub fn sync_sequences(
+    mode: SequenceSync,
+    source_db_url: String,
+    target_db_url: String,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) -> Result<()> {
+    let changes = plan_sequences(
+        mode,
+        source_db_url,
+        target_db_url.clone(),
+        excluded_schemas,
+        excluded_tables,
+    )?;
+    SequencesClient::new(target_db_url).apply(&changes)?;
+
+    for change in &changes {
+        println!(
+            "  {} {}.{}: {} → {}",
+            style("•").cyan(),
+            change.schema,
+            change.name,
+            change
+                .from
+                .map_or_else(|| "unset".to_string(), |v| v.to_string()),
+            change.to
+        );
+    }
+    println!(
+        "{} Sequences synchronized: {} changed",
+        style("✓").green(),
+        changes.len()
+    );
+
+    Ok(())
+}
+
+/// Changes needed to advance the target's sequences past the migrated data
+fn plan_sequences(
+    mode: SequenceSync,
+    source_db_url: String,
+    target_db_url: String,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) -> Result<Vec<SequenceChange>> {
+    let client = SequencesClient::new(target_db_url);
+    let mut target = client.list_sequences()?;
+    sequences::filter_excluded(&mut target, excluded_schemas, excluded_tables);
+
+    match mode {
+        SequenceSync::Source => {
+            let source = SequencesClient::new(source_db_url).list_sequences()?;
+            Ok(plan_from_source(&source, &target, |s| {
+                client.column_bound(s)
+            })?)
+        }
+        SequenceSync::Max => Ok(client.plan_from_max(&target)?),
+        SequenceSync::Skip => Ok(vec![]),
+    }
+}
+
+/// Schedule backed up cron jobs on the target
+pub fn restore_cron_jobs(
+    backup: &CronBackup,
+    target_db_url: String,
+    disabled: bool,
+) -> Result<usize> {
+    let client = CronClient::new(target_db_url);
+
+    if !client.is_cron_enabled()? {
+        return Err(anyhow::anyhow!(
+            "pg_cron extension is not enabled in target project. Enable it with: CREATE EXTENSION IF NOT EXISTS pg_cron"
+        ));
+    }
+
+    let count = client.restore(backup, disabled)?;
+    if disabled {
+        println!(
+            "  {} Jobs were created inactive. Enable them with: SELECT cron.alter_job(<jobid>, active := true)",
+            style("ℹ").blue()
+        );
+    }
+
+    Ok(count)
+}
@@ -67,10 +67,13 @@ fn add_project(
         project_ref: project_ref.to_string(),
         db_password: db_password.to_string(),
         service_key,
+        anon_key: None,
         db_host: None,
         db_port: None,
         api_url: None,
         access_token,
+        encryption: None,
+        retention: None,
     };
 
     config.add_project(alias.to_string(), project);
@@ -165,6 +168,21 @@ fn show_config() -> Result<()> {
         if let Some(port) = &project.db_port {
             println!("    db_port: {}", port);
         }
+        if let Some(encryption) = &project.encryption {
+            println!(
+                "    encryption: {} recipients, {} identity files{}",
+                encryption.recipients.len(),
+                encryption.identity_files.len(),
+                if encryption.passphrase_env.is_some() {
+                    ", passphrase"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(retention) = &project.retention {
+            println!("    retention: {}", retention);
+        }
     }
 
     Ok(())
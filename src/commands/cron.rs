@@ -0,0 +1,144 @@
+use crate::cli::{CronArgs, CronCommands};
+use crate::config::Config;
+use crate::db::cron::{diff_jobs, CronJob};
+use crate::db::CronClient;
+use anyhow::Result;
+use console::style;
+
+pub fn run(args: CronArgs) -> Result<()> {
+    match args.command {
+        CronCommands::List { project } => list_jobs(&project),
+        CronCommands::Diff { from, to } => diff_projects(&from, &to),
+    }
+}
+
+fn list_jobs(project_name: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let project = config.get_project(project_name)?;
+
+    let client = CronClient::new(project.db_url());
+
+    if !client.is_cron_enabled()? {
+        println!(
+            "{} pg_cron extension is not enabled in project '{}'",
+            style("ℹ").blue(),
+            project_name
+        );
+        return Ok(());
+    }
+
+    let jobs = client.list_jobs()?;
+
+    println!(
+        "\n{} Cron jobs in {} ({} found)",
+        style("⏰").bold(),
+        project_name,
+        jobs.len()
+    );
+    println!("{:-<60}", "");
+
+    if jobs.is_empty() {
+        println!("  No cron jobs found");
+    } else {
+        for job in &jobs {
+            print_job(job);
+        }
+    }
+
+    Ok(())
+}
+
+fn diff_projects(from_name: &str, to_name: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let source = config.get_project(from_name)?;
+    let target = config.get_project(to_name)?;
+
+    let source_jobs = CronClient::new(source.db_url()).list_jobs()?;
+    let target_jobs = CronClient::new(target.db_url()).list_jobs()?;
+
+    let diff = diff_jobs(&source_jobs, &target_jobs);
+
+    println!(
+        "\n{} Cron jobs: {} → {}",
+        style("⏰").bold(),
+        from_name,
+        to_name
+    );
+    println!("{:-<60}", "");
+
+    for job in &diff.only_source {
+        println!(
+            "  {} {} (only in {})",
+            style("+").green(),
+            job.display_name(),
+            from_name
+        );
+    }
+    for job in &diff.only_target {
+        println!(
+            "  {} {} (only in {})",
+            style("-").red(),
+            job.display_name(),
+            to_name
+        );
+    }
+    for (source_job, target_job) in &diff.changed {
+        println!("  {} {}", style("~").yellow(), source_job.display_name());
+        if source_job.schedule != target_job.schedule {
+            println!(
+                "      schedule: {} → {}",
+                source_job.schedule, target_job.schedule
+            );
+        }
+        if source_job.command != target_job.command {
+            println!(
+                "      command: {} → {}",
+                source_job.command, target_job.command
+            );
+        }
+        if source_job.active != target_job.active {
+            println!(
+                "      active: {} → {}",
+                source_job.active, target_job.active
+            );
+        }
+        if source_job.database != target_job.database || source_job.username != target_job.username
+        {
+            println!(
+                "      runs as: {}@{} → {}@{}",
+                source_job.username, source_job.database, target_job.username, target_job.database
+            );
+        }
+    }
+
+    println!(
+        "\n{} {} only in source, {} only in target, {} changed, {} unchanged",
+        style("ℹ").blue(),
+        diff.only_source.len(),
+        diff.only_target.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
+
+    Ok(())
+}
+
+fn print_job(job: &CronJob) {
+    let status = if job.active {
+        style("active").green()
+    } else {
+        style("inactive").dim()
+    };
+    println!(
+        "  {} {} [{}] ({})",
+        style("•").cyan(),
+        style(job.display_name()).bold(),
+        job.schedule,
+        status
+    );
+    println!(
+        "      {} {}",
+        style(format!("{}@{}:", job.username, job.database)).dim(),
+        job.command
+    );
+}
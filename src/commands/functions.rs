@@ -0,0 +1,306 @@
+use crate::cli::{FunctionsArgs, FunctionsCommands};
+use crate::config::{Config, ProjectConfig};
+use crate::functions::{diff_functions, load_local_functions, FunctionBackup, FunctionsClient};
+use anyhow::Result;
+use console::style;
+use futures::stream::{self, StreamExt};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+
+pub async fn run(args: FunctionsArgs) -> Result<()> {
+    match args.command {
+        FunctionsCommands::List { project } => list_functions(&project).await,
+        FunctionsCommands::Download {
+            project,
+            slug,
+            output,
+        } => download_function(&project, &slug, &output).await,
+        FunctionsCommands::Diff { from, to } => diff_projects(&from, &to).await,
+        FunctionsCommands::Delete { project, slug, yes } => {
+            delete_function(&project, &slug, yes).await
+        }
+        FunctionsCommands::Deploy {
+            project,
+            dir,
+            only,
+            parallel,
+        } => deploy(&project, &dir, &only, parallel).await,
+    }
+}
+
+fn client(project: &ProjectConfig) -> Result<FunctionsClient> {
+    Ok(FunctionsClient::new(
+        project.project_ref.clone(),
+        project.management_token()?.to_string(),
+    ))
+}
+
+async fn list_functions(project_name: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let project = config.get_project(project_name)?;
+
+    let functions = client(project)?.list_functions().await?;
+
+    println!(
+        "\n{} Edge functions in {} ({} found)",
+        style("⚡").bold(),
+        project_name,
+        functions.len()
+    );
+    println!("{:-<60}", "");
+
+    if functions.is_empty() {
+        println!("  No edge functions found");
+    }
+    for function in &functions {
+        let status = if function.status == "ACTIVE" {
+            style(function.status.as_str()).green()
+        } else {
+            style(function.status.as_str()).yellow()
+        };
+        println!(
+            "  {} {} v{} {} verify_jwt: {}",
+            style("•").cyan(),
+            style(&function.slug).bold(),
+            function.version,
+            status,
+            function.verify_jwt
+        );
+    }
+
+    Ok(())
+}
+
+async fn download_function(project_name: &str, slug: &str, output: &Path) -> Result<()> {
+    let config = Config::load(None)?;
+    let project = config.get_project(project_name)?;
+    let client = client(project)?;
+
+    let function = client
+        .list_functions()
+        .await?
+        .into_iter()
+        .find(|f| f.slug == slug)
+        .ok_or_else(|| anyhow::anyhow!("Function {} not found in {}", slug, project_name))?;
+    let files = client.download_function_source(slug).await?;
+
+    let function_dir = output.join(slug);
+    for file in &files {
+        // File names come from the API; never write outside the function's folder
+        let name = Path::new(&file.name);
+        if !name.components().all(|c| matches!(c, Component::Normal(_))) {
+            println!(
+                "  {} Skipping {} (path outside the function)",
+                style("⚠").yellow(),
+                file.name
+            );
+            continue;
+        }
+        let path = function_dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &file.content)?;
+        println!("  {} {}", style("✓").green(), file.name);
+    }
+
+    println!(
+        "\n{} Downloaded {} v{} to {} ({} files)",
+        style("✓").green(),
+        slug,
+        function.version,
+        function_dir.display(),
+        files.len()
+    );
+    println!(
+        "  verify_jwt: {}, entrypoint: {}, import_map: {}",
+        function.verify_jwt,
+        function.entrypoint_path.as_deref().unwrap_or("none"),
+        function.import_map_path.as_deref().unwrap_or("none")
+    );
+
+    Ok(())
+}
+
+async fn diff_projects(from_name: &str, to_name: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let source = config.get_project(from_name)?;
+    let target = config.get_project(to_name)?;
+
+    let source_functions = client(source)?.backup_all().await?;
+    let target_functions = client(target)?.backup_all().await?;
+    let diff = diff_functions(&source_functions, &target_functions);
+
+    println!(
+        "\n{} Edge functions: {} → {}",
+        style("⚡").bold(),
+        from_name,
+        to_name
+    );
+    println!("{:-<60}", "");
+
+    for function in &diff.only_source {
+        println!(
+            "  {} {} (only in {})",
+            style("+").green(),
+            function.slug,
+            from_name
+        );
+    }
+    for function in &diff.only_target {
+        println!(
+            "  {} {} (only in {})",
+            style("-").red(),
+            function.slug,
+            to_name
+        );
+    }
+    for changes in &diff.changed {
+        println!("  {} {}", style("~").yellow(), changes.slug);
+        for (setting, from, to) in &changes.settings {
+            println!("      {}: {} → {}", setting, from, to);
+        }
+        for file in &changes.only_source {
+            println!(
+                "      {} {} (only in {})",
+                style("+").green(),
+                file,
+                from_name
+            );
+        }
+        for file in &changes.only_target {
+            println!("      {} {} (only in {})", style("-").red(), file, to_name);
+        }
+        for file in &changes.changed {
+            println!("      {} {} (contents differ)", style("~").yellow(), file);
+        }
+    }
+
+    println!(
+        "\n{} {} only in source, {} only in target, {} changed, {} unchanged",
+        style("ℹ").blue(),
+        diff.only_source.len(),
+        diff.only_target.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
+
+    Ok(())
+}
+
+async fn delete_function(project_name: &str, slug: &str, yes: bool) -> Result<()> {
+    let config = Config::load(None)?;
+    let project = config.get_project(project_name)?;
+    let client = client(project)?;
+
+    if !client
+        .list_functions()
+        .await?
+        .iter()
+        .any(|f| f.slug == slug)
+    {
+        return Err(anyhow::anyhow!(
+            "Function {} not found in {}",
+            slug,
+            project_name
+        ));
+    }
+
+    if !yes {
+        print!(
+            "\nDelete function {} from {} ({})? [y/N] ",
+            slug, project_name, project.project_ref
+        );
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Delete cancelled.");
+            return Ok(());
+        }
+    }
+
+    client.delete_function(slug).await?;
+    println!(
+        "{} Deleted function {} from {}",
+        style("✓").green(),
+        slug,
+        project_name
+    );
+    Ok(())
+}
+
+async fn deploy(project_name: &str, dir: &Path, only: &[String], parallel: usize) -> Result<()> {
+    let config = Config::load(None)?;
+    let project = config.get_project(project_name)?;
+
+    let client = client(project)?;
+
+    let functions = load_local_functions(dir, only)?;
+    if functions.is_empty() {
+        println!(
+            "{} No functions found in {}",
+            style("ℹ").blue(),
+            dir.display()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "\n{} Deploying {} functions to {} ({})",
+        style("⚡").bold(),
+        functions.len(),
+        project_name,
+        project.project_ref
+    );
+
+    let mut results: Vec<(&FunctionBackup, crate::error::Result<()>)> =
+        stream::iter(functions.iter())
+            .map(|function| {
+                let client = client.clone();
+                async move { (function, client.deploy_function(function).await) }
+            })
+            .buffer_unordered(parallel.max(1))
+            .collect()
+            .await;
+    results.sort_by(|a, b| a.0.slug.cmp(&b.0.slug));
+
+    println!("\n  {:<30} {:<10} Details", "Function", "Result");
+    println!("{:-<60}", "");
+    for (function, result) in &results {
+        match result {
+            Ok(()) => println!(
+                "  {:<30} {} deployed {} files, verify_jwt: {}",
+                function.slug,
+                style("✓").green(),
+                function.files.len(),
+                function.verify_jwt
+            ),
+            Err(e) => println!(
+                "  {:<30} {} failed   {}",
+                function.slug,
+                style("✗").red(),
+                e
+            ),
+        }
+    }
+
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} functions failed to deploy",
+            failed,
+            results.len()
+        ));
+    }
+
+    println!(
+        "\n{} Deployed {} functions",
+        style("✓").green(),
+        results.len()
+    );
+    Ok(())
+}
@@ -1,6 +1,19 @@
-use crate::cli::MigrateArgs;
+use crate::cli::{MigrateArgs, SequenceSync};
+use crate::commands::common::{
+    enable_extensions, restore_cron_jobs, sync_sequences, warn_login_roles,
+    warn_skipped_superusers, warn_source_keys,
+};
+use crate::commands::verify::{verify_projects, VerifyOptions};
 use crate::config::Config;
-use crate::db::{PgDump, PgRestore, SqlTransformer};
+use crate::config::ProjectConfig;
+use crate::db::publications::{self, diff_publications};
+use crate::db::{
+    CronClient, ExtensionsClient, MergeClient, PgDump, PgRestore, ProjectRewrite,
+    PublicationChange, PublicationsClient, RolesClient, SqlTransformer, TableName, TablesClient,
+    WebhooksClient,
+};
+use crate::functions::secrets::{referenced_secrets, SecretsClient};
+use crate::functions::FunctionsClient;
 use crate::storage::{StorageClient, StorageTransfer};
 use anyhow::Result;
 use console::style;
@@ -13,15 +26,70 @@ pub async fn run(args: MigrateArgs) -> Result<()> {
 
     let source = config.get_project(&args.from)?;
     let target = config.get_project(&args.to)?;
+    if args.include_functions {
+        source.management_token()?;
+        target.management_token()?;
+    }
 
     println!("\n{} Migration Plan", style("📋").bold());
     println!("  Source: {} ({})", args.from, source.project_ref);
     println!("  Target: {} ({})", args.to, target.project_ref);
     println!("  Schema only: {}", args.schema_only);
     println!("  Data only: {}", args.data_only);
+    if args.data_only {
+        println!("  Disable triggers: {}", args.disable_triggers);
+        println!("  Order tables by foreign keys: {}", args.order_tables);
+        println!("  Defer constraints: {}", args.defer_constraints);
+        println!(
+            "  Merge by primary key: {}{}",
+            args.merge,
+            if args.delete_missing {
+                " (deleting missing rows)"
+            } else {
+                ""
+            }
+        );
+    }
+    println!("  Include roles: {}", !args.no_roles && !args.data_only);
+    println!("  Enable extensions: {}", !args.no_extensions);
+    println!(
+        "  Include cron jobs: {}{}",
+        args.include_cron,
+        if args.include_cron && args.cron_disabled {
+            " (created inactive)"
+        } else {
+            ""
+        }
+    );
+    println!("  Reconcile publications: {}", !args.no_publications);
     println!("  Include storage: {}", args.include_storage);
+    println!("  Include functions: {}", args.include_functions);
+    println!("  Verify: {}", args.verify);
+
+    let excluded_schemas = args
+        .exclude_schemas
+        .clone()
+        .unwrap_or_else(|| config.defaults.excluded_schemas.clone());
+
+    let excluded_tables = args.exclude_tables.clone().unwrap_or_default();
 
     if args.dry_run {
+        if !args.no_publications {
+            let changes = plan_publications(
+                source.db_url(),
+                target.db_url(),
+                &excluded_schemas,
+                &excluded_tables,
+            )?;
+            println!("\n{} Publication changes:", style("📡").bold());
+            if changes.is_empty() {
+                println!("  Publications already match");
+            }
+            for change in &changes {
+                println!("  {} {}", style("•").cyan(), change);
+            }
+        }
+
         println!("\n{} Dry run - no changes will be made", style("ℹ️").cyan());
         return Ok(());
     }
@@ -39,52 +107,214 @@ pub async fn run(args: MigrateArgs) -> Result<()> {
         }
     }
 
+    // Database webhooks call back into the project, so they must point at the target
+    let rewrite = ProjectRewrite::new(source, target);
+    let webhooks = if args.no_webhooks {
+        vec![]
+    } else {
+        WebhooksClient::new(source.db_url()).list_webhooks()?
+    };
+    if !webhooks.is_empty() && !WebhooksClient::new(target.db_url()).is_enabled()? {
+        return Err(anyhow::anyhow!(
+            "Source has {} database webhooks but webhooks are not enabled in target project. \
+             Enable them in the dashboard (Database → Webhooks) or pass --no-webhooks",
+            webhooks.len()
+        ));
+    }
+
+    // Custom roles must exist before the schema restore grants privileges to them
+    if !args.no_roles && !args.data_only {
+        println!("\n{} Migrating custom roles...", style("👥").bold());
+
+        let roles = RolesClient::new(source.db_url()).backup()?;
+        warn_skipped_superusers(&roles.skipped_superusers);
+        if roles.is_empty() {
+            println!("{} No custom roles found", style("ℹ").blue());
+        } else {
+            PgRestore::new(target.db_url()).restore_from_string(&roles.to_sql())?;
+            println!(
+                "{} Roles migration complete: {} roles, {} memberships",
+                style("✓").green(),
+                roles.roles.len(),
+                roles.memberships.len()
+            );
+            warn_login_roles(&roles.login_roles());
+        }
+    }
+
+    // Extensions must be enabled before tables using their types are restored
+    if !args.no_extensions {
+        println!("\n{} Checking extensions...", style("🧩").bold());
+
+        let extensions = ExtensionsClient::new(source.db_url()).list_installed()?;
+        enable_extensions(&extensions, target.db_url())?;
+    }
+
     // Database migration
     println!("\n{} Starting database migration...", style("🗄️").bold());
 
-    let excluded_schemas = args
-        .exclude_schemas
-        .unwrap_or_else(|| config.defaults.excluded_schemas.clone());
+    // Tables whose rows this run copied, when not every source table
+    let mut migrated_tables = None;
+    if args.merge {
+        migrated_tables = Some(merge_tables(
+            &args,
+            source.db_url(),
+            target.db_url(),
+            &excluded_schemas,
+            &excluded_tables,
+        )?);
+    } else {
+        // Dump source database
+        info!("Dumping source database...");
+        let mut pg_dump = PgDump::new(source.db_url())
+            .exclude_schemas(excluded_schemas.clone())
+            .exclude_tables(excluded_tables.clone())
+            .schema_only(args.schema_only)
+            .data_only(args.data_only);
+        // SET CONSTRAINTS only defers DEFERRABLE foreign keys; the others are checked
+        // per row, so referenced tables have to be loaded first
+        let mut order_tables = args.order_tables;
+        if args.defer_constraints && !order_tables && !args.disable_triggers {
+            let immediate = TablesClient::new(target.db_url())
+                .immediate_foreign_keys(&excluded_schemas, &excluded_tables)?
+                .len();
+            if immediate > 0 {
+                println!(
+                    "  {} {} foreign keys on the target are NOT DEFERRABLE; loading tables in foreign key order",
+                    style("ℹ").blue(),
+                    immediate
+                );
+                order_tables = true;
+            }
+        }
+        if order_tables || args.tables.is_some() {
+            let order = select_tables(
+                TablesClient::new(source.db_url())
+                    .load_order(&excluded_schemas, &excluded_tables)?,
+                args.tables.as_deref(),
+            )?;
+            info!("Dumping {} tables in foreign key order", order.len());
+            pg_dump = pg_dump.table_order(order.iter().map(|t| t.qualified_name()).collect());
+            if args.tables.is_some() {
+                migrated_tables = Some(order);
+            }
+        }
+        let dump = pg_dump.dump_to_string()?;
 
-    let excluded_tables = args.exclude_tables.unwrap_or_default();
+        // Transform SQL for Supabase compatibility
+        info!("Transforming SQL...");
+        let mut transformed = SqlTransformer::transform(&dump);
+        if args.no_webhooks {
+            transformed = SqlTransformer::strip_webhook_triggers(&transformed);
+        } else {
+            transformed = SqlTransformer::rewrite_triggers(&transformed, &rewrite);
+        }
 
-    // Dump source database
-    info!("Dumping source database...");
-    let dump = PgDump::new(source.db_url())
-        .exclude_schemas(excluded_schemas)
-        .exclude_tables(excluded_tables)
-        .schema_only(args.schema_only)
-        .data_only(args.data_only)
-        .dump_to_string()?;
+        // Write to temp file
+        let temp_file = NamedTempFile::new()?;
+        std::fs::write(temp_file.path(), &transformed)?;
 
-    // Transform SQL for Supabase compatibility
-    info!("Transforming SQL...");
-    let transformed = SqlTransformer::transform(&dump);
+        // Restore to target
+        info!("Restoring to target database...");
+        let restore = PgRestore::new(target.db_url())
+            .disable_triggers(args.disable_triggers)
+            .defer_constraints(args.defer_constraints);
+        restore.restore_from_file(temp_file.path())?;
+    }
 
-    // Write to temp file
-    let temp_file = NamedTempFile::new()?;
-    std::fs::write(temp_file.path(), &transformed)?;
+    println!("{} Database migration complete!", style("✓").green());
 
-    // Restore to target
-    info!("Restoring to target database...");
-    let restore = PgRestore::new(target.db_url());
-    restore.restore_from_file(temp_file.path())?;
+    // Rows copied into an existing schema leave its sequences behind the data
+    if args.data_only && args.sync_sequences != SequenceSync::Skip {
+        println!("\n{} Synchronizing sequences...", style("🔢").bold());
 
-    println!("{} Database migration complete!", style("✓").green());
+        sync_sequences(
+            args.sync_sequences,
+            source.db_url(),
+            target.db_url(),
+            &excluded_schemas,
+            &excluded_tables,
+        )?;
+    }
+
+    // Webhooks are part of the schema dump; data-only runs recreate them explicitly
+    if !webhooks.is_empty() {
+        println!("\n{} Migrating database webhooks...", style("🪝").bold());
+
+        if args.data_only {
+            WebhooksClient::new(target.db_url()).apply(&webhooks, &rewrite)?;
+        }
+        for webhook in &webhooks {
+            let url = webhook.url().map(|u| rewrite.apply(u)).unwrap_or_default();
+            println!(
+                "  {} {}.{} {} → {}",
+                style("•").cyan(),
+                webhook.schema,
+                webhook.table,
+                webhook.name,
+                url
+            );
+        }
+        warn_source_keys(
+            webhooks
+                .iter()
+                .filter(|w| rewrite.has_source_key(&rewrite.apply(&w.definition)))
+                .count(),
+        );
+        println!(
+            "{} Webhooks migration complete: {} webhooks",
+            style("✓").green(),
+            webhooks.len()
+        );
+    }
+
+    // Publication membership is not reliably carried by the dump, so reconcile it explicitly
+    if !args.no_publications {
+        println!("\n{} Reconciling publications...", style("📡").bold());
+
+        let changes = plan_publications(
+            source.db_url(),
+            target.db_url(),
+            &excluded_schemas,
+            &excluded_tables,
+        )?;
+        PublicationsClient::new(target.db_url()).apply(&changes)?;
+        for change in &changes {
+            println!("  {} {}", style("•").cyan(), change);
+        }
+        println!(
+            "{} Publications reconciled: {} changes",
+            style("✓").green(),
+            changes.len()
+        );
+    }
+
+    // Cron jobs live in the cron schema, which is never dumped
+    if args.include_cron {
+        println!("\n{} Migrating cron jobs...", style("⏰").bold());
+
+        let cron_backup = CronClient::new(source.db_url()).backup()?;
+        if cron_backup.jobs.is_empty() {
+            println!("{} No cron jobs found", style("ℹ").blue());
+        } else {
+            let count = restore_cron_jobs(&cron_backup, target.db_url(), args.cron_disabled)?;
+            println!(
+                "{} Cron migration complete: {} jobs scheduled",
+                style("✓").green(),
+                count
+            );
+        }
+    }
 
     // Storage migration
     if args.include_storage {
         println!("\n{} Starting storage migration...", style("📦").bold());
 
-        let source_key = source.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Source project requires service_key for storage migration")
-        })?;
-        let target_key = target.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Target project requires service_key for storage migration")
-        })?;
+        let source_key = source.storage_key()?;
+        let target_key = target.storage_key()?;
 
-        let source_storage = StorageClient::new(source.api_url(), source_key.clone());
-        let target_storage = StorageClient::new(target.api_url(), target_key.clone());
+        let source_storage = StorageClient::new(source.api_url(), source_key.to_string());
+        let target_storage = StorageClient::new(target.api_url(), target_key.to_string());
 
         let transfer = StorageTransfer::new(source_storage)
             .with_target(target_storage)
@@ -98,7 +328,197 @@ pub async fn run(args: MigrateArgs) -> Result<()> {
         );
     }
 
+    if args.include_functions {
+        println!("\n{} Migrating edge functions...", style("⚡").bold());
+        migrate_functions(source, target, &args.from, &args.to).await?;
+    }
+
+    if args.verify {
+        // Only compare what this run copied: a schema-only run copies no rows, and a
+        // merge only touches the selected tables and keeps extra target rows
+        let options = VerifyOptions {
+            row_counts: !args.schema_only,
+            checksums: false,
+            chunk_size: 10_000,
+            storage: args.include_storage,
+            tables: migrated_tables,
+            allow_extra_rows: args.merge && !args.delete_missing,
+        };
+        let mismatches = verify_projects(
+            source,
+            target,
+            &excluded_schemas,
+            &excluded_tables,
+            &options,
+        )?;
+        if mismatches > 0 {
+            return Err(anyhow::anyhow!(
+                "Migration finished but verification found {} mismatches",
+                mismatches
+            ));
+        }
+    }
+
     println!("\n{} Migration completed successfully!", style("🎉").bold());
 
     Ok(())
 }
+
+/// Deploy every source function to the target and report secrets they read that
+/// the target lacks
+async fn migrate_functions(
+    source: &ProjectConfig,
+    target: &ProjectConfig,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    let source_client = FunctionsClient::new(
+        source.project_ref.clone(),
+        source.management_token()?.to_string(),
+    );
+    let target_client = FunctionsClient::new(
+        target.project_ref.clone(),
+        target.management_token()?.to_string(),
+    );
+
+    let functions = source_client.backup_all().await?;
+    if functions.is_empty() {
+        println!("{} No edge functions found", style("ℹ").blue());
+        return Ok(());
+    }
+
+    for function in &functions {
+        target_client.deploy_function(function).await?;
+        println!(
+            "  {} {} ({} files, verify_jwt: {})",
+            style("•").cyan(),
+            function.slug,
+            function.files.len(),
+            function.verify_jwt
+        );
+    }
+    println!(
+        "{} Edge functions migration complete: {} functions",
+        style("✓").green(),
+        functions.len()
+    );
+
+    // Secret values cannot be read back, so only their presence is checked
+    let referenced = referenced_secrets(&functions);
+    if referenced.is_empty() {
+        return Ok(());
+    }
+    let existing: Vec<String> = SecretsClient::new(
+        target.project_ref.clone(),
+        target.management_token()?.to_string(),
+    )
+    .list_secrets()
+    .await?
+    .into_iter()
+    .map(|s| s.name)
+    .collect();
+
+    let missing: Vec<_> = referenced
+        .iter()
+        .filter(|(name, _)| !existing.contains(name))
+        .collect();
+    for (name, slugs) in &missing {
+        println!(
+            "  {} Secret {} is not set in {} (used by {})",
+            style("⚠").yellow(),
+            name,
+            to,
+            slugs.join(", ")
+        );
+    }
+    if !missing.is_empty() {
+        println!(
+            "  Set them with: supamigrate secrets copy --from {} --to {}",
+            from, to
+        );
+    }
+
+    Ok(())
+}
+
+/// Changes needed for the target's publications to match the migrated source tables
+fn plan_publications(
+    source_db_url: String,
+    target_db_url: String,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) -> Result<Vec<PublicationChange>> {
+    let mut source = PublicationsClient::new(source_db_url).list_publications()?;
+    let mut target = PublicationsClient::new(target_db_url).list_publications()?;
+    publications::filter_excluded(&mut source, excluded_schemas, excluded_tables);
+    publications::filter_excluded(&mut target, excluded_schemas, excluded_tables);
+
+    Ok(diff_publications(&source, &target))
+}
+
+/// The `--tables` selection (`table` or `schema.table`) out of the source tables,
+/// keeping their order; every table without a selection
+fn select_tables(
+    mut tables: Vec<TableName>,
+    selected: Option<&[String]>,
+) -> Result<Vec<TableName>> {
+    let Some(selected) = selected else {
+        return Ok(tables);
+    };
+    let matches = |t: &TableName, s: &str| s == t.table || s == format!("{}.{}", t.schema, t.table);
+    let unknown: Vec<&str> = selected
+        .iter()
+        .filter(|s| !tables.iter().any(|t| matches(t, s)))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Tables not found in source: {}",
+            unknown.join(", ")
+        ));
+    }
+    tables.retain(|t| selected.iter().any(|s| matches(t, s)));
+    Ok(tables)
+}
+
+/// Upsert each migrated table (referenced tables first), then with `--delete-missing`
+/// delete rows gone from the source (referencing tables first), and report row counts;
+/// returns the merged tables
+fn merge_tables(
+    args: &MigrateArgs,
+    source_db_url: String,
+    target_db_url: String,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) -> Result<Vec<TableName>> {
+    let tables = select_tables(
+        TablesClient::new(source_db_url.clone()).load_order(excluded_schemas, excluded_tables)?,
+        args.tables.as_deref(),
+    )?;
+
+    let client =
+        MergeClient::new(source_db_url, target_db_url).disable_triggers(args.disable_triggers);
+
+    let mut stats = Vec::with_capacity(tables.len());
+    for table in &tables {
+        stats.push(client.merge_table(table)?);
+    }
+    if args.delete_missing {
+        for (table, stats) in tables.iter().zip(stats.iter_mut()).rev() {
+            stats.deleted = client.delete_missing(table)?;
+        }
+    }
+
+    for (table, stats) in tables.iter().zip(&stats) {
+        println!(
+            "  {} {}.{}: {}",
+            style("•").cyan(),
+            table.schema,
+            table.table,
+            stats
+        );
+    }
+    println!("{} Merged {} tables", style("✓").green(), tables.len());
+
+    Ok(tables)
+}
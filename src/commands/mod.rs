@@ -1,8 +1,13 @@
 pub mod backup;
+pub mod common;
 pub mod config;
+pub mod cron;
 pub mod doctor;
+pub mod functions;
 pub mod migrate;
+pub mod replicate;
 pub mod restore;
 pub mod secrets;
 pub mod storage;
 pub mod vault;
+pub mod verify;
@@ -0,0 +1,326 @@
+use crate::cli::{ReplicateArgs, ReplicateCommands, SequenceSync};
+use crate::commands::common::{
+    enable_extensions, sync_sequences, warn_login_roles, warn_skipped_superusers, warn_source_keys,
+};
+use crate::config::{Config, ProjectConfig};
+use crate::db::{
+    ExtensionsClient, PgDump, PgRestore, ProjectRewrite, Replication, RolesClient, SqlTransformer,
+    TablesClient,
+};
+use anyhow::Result;
+use console::style;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use tracing::info;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub async fn run(args: ReplicateArgs) -> Result<()> {
+    match args.command {
+        Some(ReplicateCommands::Status { from, to }) => status(&from, &to),
+        Some(ReplicateCommands::Cutover {
+            from,
+            to,
+            timeout,
+            exclude_tables,
+            exclude_schemas,
+            yes,
+        }) => {
+            let config = Config::load(None)?;
+            let (excluded_schemas, excluded_tables) =
+                exclusions(&config, exclude_schemas, exclude_tables);
+            cutover(
+                &config,
+                &from,
+                &to,
+                timeout,
+                &excluded_schemas,
+                &excluded_tables,
+                yes,
+            )
+            .await
+        }
+        None => start(args),
+    }
+}
+
+fn start(args: ReplicateArgs) -> Result<()> {
+    let config = Config::load(None)?;
+
+    // clap requires both when no subcommand is given
+    let from = args.from.unwrap_or_default();
+    let to = args.to.unwrap_or_default();
+    let source = config.get_project(&from)?;
+    let target = config.get_project(&to)?;
+    let replication = replication(source, target);
+
+    let (excluded_schemas, excluded_tables) = exclusions(
+        &config,
+        args.exclude_schemas.clone(),
+        args.exclude_tables.clone(),
+    );
+
+    println!("\n{} Replication Plan", style("📋").bold());
+    println!("  Source: {} ({})", from, source.project_ref);
+    println!("  Target: {} ({})", to, target.project_ref);
+    println!("  Publication/subscription: {}", replication.name());
+    println!("  Copy schema: {}", !args.skip_schema);
+
+    if !args.yes && !confirm("\nStart replication?")? {
+        println!("Replication cancelled.");
+        return Ok(());
+    }
+
+    if !args.skip_schema {
+        copy_schema(source, target, &excluded_schemas, &excluded_tables)?;
+    }
+
+    println!("\n{} Setting up logical replication...", style("🔁").bold());
+
+    let mut tables =
+        TablesClient::new(source.db_url()).load_order(&excluded_schemas, &excluded_tables)?;
+
+    // Publishing a table without a replica identity makes UPDATE/DELETE fail on the source
+    let unpublishable = replication.tables_without_identity(&tables)?;
+    if !unpublishable.is_empty() {
+        println!(
+            "  {} Skipping tables without a primary key or replica identity:",
+            style("⚠").yellow()
+        );
+        for table in &unpublishable {
+            println!("      {}.{}", table.schema, table.table);
+        }
+        // Without a primary key they cannot be merged either, so they are copied once
+        // writes have stopped
+        let names: Vec<String> = unpublishable
+            .iter()
+            .map(|t| format!("{}.{}", t.schema, t.table))
+            .collect();
+        println!(
+            "  Copy them at cutover, after writes stop: supamigrate migrate --from {} --to {} --data-only --tables {}",
+            from,
+            to,
+            names.join(",")
+        );
+        tables.retain(|t| !unpublishable.contains(t));
+    }
+
+    replication.create_publication(&tables)?;
+    println!(
+        "  {} Created publication {} on source ({} tables)",
+        style("✓").green(),
+        replication.name(),
+        tables.len()
+    );
+
+    replication.create_subscription()?;
+    println!(
+        "  {} Created subscription {} on target",
+        style("✓").green(),
+        replication.name()
+    );
+
+    println!(
+        "\n{} Replication started. The initial copy runs in the background.",
+        style("🎉").bold()
+    );
+    println!(
+        "  Check progress: supamigrate replicate status --from {} --to {}",
+        from, to
+    );
+    // cutover skips the sequences of excluded tables, so it needs the same exclusions
+    let mut cutover = format!("supamigrate replicate cutover --from {} --to {}", from, to);
+    if let Some(schemas) = &args.exclude_schemas {
+        cutover.push_str(&format!(" --exclude-schemas {}", schemas.join(",")));
+    }
+    if !excluded_tables.is_empty() {
+        cutover.push_str(&format!(" --exclude-tables {}", excluded_tables.join(",")));
+    }
+    println!("  Switch over:    {}", cutover);
+
+    Ok(())
+}
+
+fn status(from: &str, to: &str) -> Result<()> {
+    let config = Config::load(None)?;
+    let source = config.get_project(from)?;
+    let target = config.get_project(to)?;
+    let replication = replication(source, target);
+
+    let status = replication.status()?;
+
+    println!(
+        "\n{} Replication {} → {} ({})",
+        style("🔁").bold(),
+        from,
+        to,
+        replication.name()
+    );
+    println!("{:-<60}", "");
+    println!("  {}", status);
+
+    if status.is_synchronized() {
+        println!(
+            "\n{} Initial copy finished, ready for cutover",
+            style("✓").green()
+        );
+    }
+
+    Ok(())
+}
+
+async fn cutover(
+    config: &Config,
+    from: &str,
+    to: &str,
+    timeout: u64,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+    yes: bool,
+) -> Result<()> {
+    let source = config.get_project(from)?;
+    let target = config.get_project(to)?;
+    let replication = replication(source, target);
+
+    println!("\n{} Cutover Plan", style("📋").bold());
+    println!("  Source: {} ({})", from, source.project_ref);
+    println!("  Target: {} ({})", to, target.project_ref);
+    println!(
+        "  {} Stop writes to the source before continuing",
+        style("⚠").yellow()
+    );
+
+    if !yes && !confirm("\nProceed with cutover?")? {
+        println!("Cutover cancelled.");
+        return Ok(());
+    }
+
+    // Writes are stopped now, so everything up to this position must reach the target
+    let cutover_lsn = replication.source_lsn()?;
+    println!(
+        "\n{} Waiting for replication to catch up...",
+        style("⏳").bold()
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    loop {
+        let status = replication.status()?;
+        if !status.subscription_exists {
+            return Err(anyhow::anyhow!(
+                "No subscription {} found on target. Start replication with: supamigrate replicate --from {} --to {}",
+                replication.name(),
+                from,
+                to
+            ));
+        }
+        if status.is_caught_up_to(cutover_lsn) {
+            println!("{} {}", style("✓").green(), status);
+            break;
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!(
+                "Replication did not catch up within {}s ({})",
+                timeout,
+                status
+            ));
+        }
+        info!("{}", status);
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    // Logical replication does not carry sequence values
+    println!("\n{} Synchronizing sequences...", style("🔢").bold());
+    sync_sequences(
+        SequenceSync::Source,
+        source.db_url(),
+        target.db_url(),
+        excluded_schemas,
+        excluded_tables,
+    )?;
+
+    println!("\n{} Stopping replication...", style("🔁").bold());
+    replication.drop()?;
+    println!(
+        "{} Dropped subscription and publication {}",
+        style("✓").green(),
+        replication.name()
+    );
+
+    println!(
+        "\n{} Cutover complete! Point your applications at {}.",
+        style("🎉").bold(),
+        to
+    );
+
+    Ok(())
+}
+
+/// Schemas and tables left out of replication; `start` and `cutover` must agree
+fn exclusions(
+    config: &Config,
+    exclude_schemas: Option<Vec<String>>,
+    exclude_tables: Option<Vec<String>>,
+) -> (Vec<String>, Vec<String>) {
+    (
+        exclude_schemas.unwrap_or_else(|| config.defaults.excluded_schemas.clone()),
+        exclude_tables.unwrap_or_default(),
+    )
+}
+
+fn replication(source: &ProjectConfig, target: &ProjectConfig) -> Replication {
+    Replication::new(
+        source.db_url(),
+        target.db_url(),
+        &source.project_ref,
+        &target.project_ref,
+    )
+}
+
+/// Roles, extensions and schema; replication only carries rows
+fn copy_schema(
+    source: &ProjectConfig,
+    target: &ProjectConfig,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) -> Result<()> {
+    println!("\n{} Copying schema...", style("🗄️").bold());
+
+    let roles = RolesClient::new(source.db_url()).backup()?;
+    warn_skipped_superusers(&roles.skipped_superusers);
+    if !roles.is_empty() {
+        PgRestore::new(target.db_url()).restore_from_string(&roles.to_sql())?;
+        warn_login_roles(&roles.login_roles());
+    }
+
+    let extensions = ExtensionsClient::new(source.db_url()).list_installed()?;
+    enable_extensions(&extensions, target.db_url())?;
+
+    info!("Dumping source schema...");
+    let dump = PgDump::new(source.db_url())
+        .exclude_schemas(excluded_schemas.to_vec())
+        .exclude_tables(excluded_tables.to_vec())
+        .schema_only(true)
+        .dump_to_string()?;
+
+    let rewrite = ProjectRewrite::new(source, target);
+    let transformed = SqlTransformer::rewrite_triggers(&SqlTransformer::transform(&dump), &rewrite);
+    warn_source_keys(SqlTransformer::count_source_keys(&transformed, &rewrite));
+
+    let temp_file = NamedTempFile::new()?;
+    std::fs::write(temp_file.path(), &transformed)?;
+    PgRestore::new(target.db_url()).restore_from_file(temp_file.path())?;
+
+    println!("{} Schema copy complete!", style("✓").green());
+    Ok(())
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
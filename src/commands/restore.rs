@@ -1,68 +1,148 @@
-use crate::cli::RestoreArgs;
+use crate::backup::{
+    storage_object, ArchiveReader, BackupLocation, BackupMetadata, BackupSource, Encryption,
+    ObjectStore, StorageIndex, LATE_OBJECTS_FILE,
+};
+use crate::cli::{RestoreArgs, RestoreCommands};
+use crate::commands::backup::fetch_backup;
+use crate::commands::common::{
+    enable_extensions, restore_cron_jobs, warn_login_roles, warn_skipped_superusers,
+    warn_source_keys,
+};
 use crate::commands::secrets::restore_secrets;
 use crate::commands::vault::restore_vault;
 use crate::config::Config;
-use crate::db::{PgRestore, SqlTransformer, VaultBackup};
+use crate::db::{
+    CronBackup, DumpFilter, Extension, PgRestore, ProjectRewrite, RecoveryClient, RolesBackup,
+    SqlTransformer, VaultBackup, WebhooksClient,
+};
 use crate::error::SupamigrateError;
 use crate::functions::secrets::SecretsBackup;
 use crate::functions::{FunctionBackup, FunctionFile, FunctionsClient};
 use crate::storage::StorageClient;
 use anyhow::Result;
 use console::style;
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{self, Read, Write};
 use tracing::info;
 
-#[derive(serde::Deserialize)]
-struct BackupMetadata {
-    #[allow(dead_code)]
-    project_ref: String,
-    #[allow(dead_code)]
-    timestamp: String,
-    #[allow(dead_code)]
-    schema_only: bool,
-    include_storage: bool,
-    #[serde(default)]
-    include_functions: bool,
-    #[serde(default)]
-    include_secrets: bool,
-    #[serde(default)]
-    secrets_count: usize,
-    #[serde(default)]
-    include_vault: bool,
-    #[serde(default)]
-    vault_count: usize,
-    compressed: bool,
+pub async fn run(args: RestoreArgs) -> Result<()> {
+    match args.command {
+        Some(RestoreCommands::Cleanup { to, schema, yes }) => cleanup(&to, schema.as_deref(), yes),
+        None => restore(args).await,
+    }
 }
 
-pub async fn run(args: RestoreArgs) -> Result<()> {
+async fn restore(args: RestoreArgs) -> Result<()> {
     let config = Config::load(None)?;
-    let target = config.get_project(&args.to)?;
 
-    // Validate backup exists
-    if !args.from.exists() {
-        return Err(SupamigrateError::BackupNotFound(args.from.display().to_string()).into());
+    // clap requires both when no subcommand is given
+    let from = args.from.clone().unwrap_or_default();
+    let to = args.to.clone().unwrap_or_default();
+    let target = config.get_project(&to)?;
+
+    if args.into.is_some() && args.tables.len() != 1 {
+        return Err(anyhow::anyhow!("--into needs exactly one --table"));
     }
 
+    // With any table, schema, bucket or function filter only the selected parts are restored
+    let selective = !args.tables.is_empty()
+        || !args.schemas.is_empty()
+        || !args.buckets.is_empty()
+        || !args.functions.is_empty();
+    let restore_database = !selective || !args.tables.is_empty() || !args.schemas.is_empty();
+    // A recovery copy beside live data must not touch roles, extensions or webhooks
+    let recovery = args.into_schema.is_some();
+    let restore_roles = !args.no_roles && !selective && !recovery;
+    let restore_extensions = !args.no_extensions && !selective && !recovery;
+    let no_webhooks = args.no_webhooks || recovery;
+    // Dropping a live table loses the rows written since the backup, so only
+    // load rows into the selected tables unless asked to replace them
+    let data_only = args.data_only
+        || (restore_database && selective && !args.replace && args.into.is_none() && !recovery);
+    let include_storage = args.include_storage || !args.buckets.is_empty();
+    let include_functions = args.include_functions || !args.functions.is_empty();
+    let filter = DumpFilter::new()
+        .tables(args.tables.clone())
+        .schemas(args.schemas.clone())
+        .data_only(data_only)
+        .replace(args.replace)
+        .rename_to(args.into.clone());
+
+    // Archives are read in one streaming pass; storage objects are streamed again later
+    let (backup_path, _staging) = fetch_backup(&from, &config).await?;
+    let mut source = BackupSource::open(&backup_path)?;
+
     // Load metadata
-    let metadata_path = args.from.join("metadata.json");
-    let metadata: BackupMetadata = if metadata_path.exists() {
-        let content = fs::read_to_string(&metadata_path)?;
-        serde_json::from_str(&content)?
-    } else {
-        return Err(SupamigrateError::InvalidBackup("metadata.json not found".to_string()).into());
-    };
+    let metadata = source.metadata()?;
+
+    check_integrity(&source, args.ignore_integrity)?;
+
+    // Keys of the project the backup was taken from, or of the target
+    if metadata.encrypted {
+        let backed_up = config.get_project(&metadata.project_ref).ok();
+        let encryption = Encryption::from_configs([
+            backed_up.and_then(|p| p.encryption.as_ref()),
+            target.encryption.as_ref(),
+        ])?
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Backup is encrypted but neither {} nor {} has encryption configured",
+                metadata.project_ref,
+                to
+            )
+        })?;
+        source.set_encryption(Some(encryption));
+    }
 
     println!("\n{} Restore Plan", style("📋").bold());
-    println!("  From: {}", args.from.display());
-    println!("  Target: {} ({})", args.to, target.project_ref);
+    println!("  From: {}", from);
+    println!("  Encrypted: {}", metadata.encrypted);
+    println!("  Target: {} ({})", to, target.project_ref);
     println!(
-        "  Include storage: {}",
-        args.include_storage && metadata.include_storage
+        "  Database: {}",
+        database_plan(restore_database, &args.tables, &args.schemas, data_only)
     );
+    if data_only && !args.data_only {
+        println!(
+            "  {} Only rows are loaded into the existing tables; pass --replace to drop and recreate them, or --into to restore beside them",
+            style("ℹ").blue()
+        );
+    }
+    if let Some(into) = &args.into {
+        println!("  Into table: {}", into);
+    }
+    if let Some(schema) = &args.into_schema {
+        println!("  Into schema: {} (beside the live data)", schema);
+    }
     println!(
-        "  Include functions: {}",
-        args.include_functions && metadata.include_functions
+        "  Include roles: {} ({})",
+        restore_roles && metadata.include_roles,
+        if metadata.include_roles {
+            format!("{} roles in backup", metadata.roles_count)
+        } else {
+            "no roles in backup".to_string()
+        }
+    );
+    println!(
+        "  Include cron jobs: {} ({})",
+        args.include_cron && metadata.include_cron,
+        if metadata.include_cron {
+            format!("{} jobs in backup", metadata.cron_count)
+        } else {
+            "no cron jobs in backup".to_string()
+        }
+    );
+    let include_storage = include_storage && metadata.include_storage;
+    let include_functions = include_functions && metadata.include_functions;
+    println!(
+        "  Include storage: {}{}",
+        include_storage,
+        selection(include_storage, &args.buckets)
+    );
+    println!(
+        "  Include functions: {}{}",
+        include_functions,
+        selection(include_functions, &args.functions)
     );
     println!(
         "  Include secrets: {} ({})",
@@ -103,83 +183,197 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
         }
     }
 
-    // Database restore
-    println!("\n{} Restoring database...", style("🗄️").bold());
-
-    let dump_file = if metadata.compressed {
-        args.from.join("database.sql.gz")
+    // Load the database dump up front so problems surface before the target is touched
+    let sql = if restore_database {
+        load_dump(&source, &metadata, &filter)?
     } else {
-        args.from.join("database.sql")
+        String::new()
     };
-
-    if !dump_file.exists() {
-        return Err(SupamigrateError::InvalidBackup(format!(
-            "Database dump not found: {}",
-            dump_file.display()
-        ))
-        .into());
-    }
-
-    let sql = if metadata.compressed {
-        let file = fs::File::open(&dump_file)?;
-        let mut decoder = flate2::read::GzDecoder::new(file);
-        let mut content = String::new();
-        decoder.read_to_string(&mut content)?;
-        content
-    } else {
-        fs::read_to_string(&dump_file)?
+    let moved_schema = match &args.into_schema {
+        Some(into) if restore_database => Some((single_schema(&sql)?, into.as_str())),
+        _ => None,
     };
 
     // Transform SQL for Supabase compatibility
     info!("Transforming SQL...");
-    let transformed = SqlTransformer::transform(&sql);
+    let mut transformed = SqlTransformer::transform(&sql);
+    if let Some((schema, into)) = &moved_schema {
+        transformed = SqlTransformer::move_schema(&transformed, schema, into);
+    }
 
-    // Restore to target
-    info!("Restoring to target database...");
-    let restore = PgRestore::new(target.db_url());
-    restore.restore_from_string(&transformed)?;
+    // Point database webhooks at the target project instead of the backed up one
+    let webhook_count = SqlTransformer::count_webhook_triggers(&transformed);
+    if no_webhooks {
+        transformed = SqlTransformer::strip_webhook_triggers(&transformed);
+    } else if webhook_count > 0 {
+        if !WebhooksClient::new(target.db_url()).is_enabled()? {
+            return Err(anyhow::anyhow!(
+                "Backup has {} database webhooks but webhooks are not enabled in target project. \
+                 Enable them in the dashboard (Database → Webhooks) or pass --no-webhooks",
+                webhook_count
+            ));
+        }
 
-    println!("{} Database restore complete!", style("✓").green());
+        let source = config.get_project(&metadata.project_ref).ok();
+        let rewrite = ProjectRewrite::from_ref(&metadata.project_ref, source, target);
+        transformed = SqlTransformer::rewrite_triggers(&transformed, &rewrite);
+        warn_source_keys(SqlTransformer::count_source_keys(&transformed, &rewrite));
+        if source.is_none() {
+            println!(
+                "  {} Source project {} is not configured; webhook keys were not rewritten",
+                style("⚠").yellow(),
+                metadata.project_ref
+            );
+        }
+    }
 
-    // Storage restore
-    if args.include_storage && metadata.include_storage {
-        println!("\n{} Restoring storage...", style("📦").bold());
+    // Roles restore (before the schema so GRANTs to custom roles succeed)
+    if restore_roles && metadata.include_roles {
+        if let Some(roles_content) = source.read_to_string("roles.json")? {
+            println!("\n{} Restoring custom roles...", style("👥").bold());
+
+            let roles_backup: RolesBackup = serde_json::from_str(&roles_content)?;
+
+            PgRestore::new(target.db_url()).restore_from_string(&roles_backup.to_sql())?;
+            println!(
+                "{} Roles restore complete: {} roles, {} memberships",
+                style("✓").green(),
+                roles_backup.roles.len(),
+                roles_backup.memberships.len()
+            );
+            warn_login_roles(&roles_backup.login_roles());
+            warn_skipped_superusers(&roles_backup.skipped_superusers);
+        } else {
+            println!("{} No roles backup found, skipping", style("⚠️").yellow());
+        }
+    }
 
-        let service_key = target.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Target project requires service_key for storage restore")
+    // Extensions restore (before the schema so extension types exist)
+    if restore_extensions {
+        if let Some(extensions_content) = source.read_to_string("extensions.json")? {
+            println!("\n{} Checking extensions...", style("🧩").bold());
+
+            let extensions: Vec<Extension> = serde_json::from_str(&extensions_content)?;
+            enable_extensions(&extensions, target.db_url())?;
+        }
+    }
+
+    // Database restore
+    if restore_database {
+        println!("\n{} Restoring database...", style("🗄️").bold());
+
+        if let Some((schema, into)) = &moved_schema {
+            info!("Restoring schema {} into {}", schema, into);
+            RecoveryClient::new(target.db_url()).create_schema(into, &from)?;
+        }
+
+        // Restore to target
+        info!("Restoring to target database...");
+        // A selection runs in one transaction, so a failure leaves the live tables as they were
+        let restore = PgRestore::new(target.db_url()).atomic(selective);
+        restore.restore_from_string(&transformed).map_err(|e| {
+            if selective {
+                anyhow::anyhow!("{}\nThe database was left unchanged.", e)
+            } else {
+                e.into()
+            }
         })?;
 
-        let storage = StorageClient::new(target.api_url(), service_key.clone());
-        let storage_dir = args.from.join("storage");
+        if webhook_count > 0 && !no_webhooks {
+            println!(
+                "{} Database restore complete! ({} webhooks pointed at {})",
+                style("✓").green(),
+                webhook_count,
+                target.api_url()
+            );
+        } else {
+            println!("{} Database restore complete!", style("✓").green());
+        }
+    }
+
+    // Cron jobs restore
+    if args.include_cron && metadata.include_cron {
+        println!("\n{} Restoring cron jobs...", style("⏰").bold());
+
+        if let Some(cron_content) = source.read_to_string("cron_jobs.json")? {
+            let cron_backup: CronBackup = serde_json::from_str(&cron_content)?;
+
+            let count = restore_cron_jobs(&cron_backup, target.db_url(), args.cron_disabled)?;
+            println!(
+                "{} Cron jobs restore complete: {} jobs scheduled",
+                style("✓").green(),
+                count
+            );
+        } else {
+            println!(
+                "{} No cron jobs backup found, skipping",
+                style("⚠️").yellow()
+            );
+        }
+    }
 
-        if storage_dir.exists() {
-            let stats = restore_storage(&storage, &storage_dir).await?;
+    // Storage restore
+    if include_storage {
+        println!("\n{} Restoring storage...", style("📦").bold());
+
+        let service_key = target.storage_key()?;
+
+        let storage = StorageClient::new(target.api_url(), service_key.to_string());
+
+        let stats = if let (Some(index), Some(store)) =
+            (source.storage_index()?, source.object_store())
+        {
+            Some(
+                restore_storage_incremental(&storage, &index, &store, &source, &args.buckets)
+                    .await?,
+            )
+        } else if source.has_dir("storage") {
+            Some(match source.location() {
+                BackupLocation::Dir(dir) => {
+                    restore_storage(&storage, &dir.join("storage"), &source, &args.buckets).await?
+                }
+                BackupLocation::Archive(archive) => {
+                    restore_storage_archive(&storage, archive.path(), &source, &args.buckets)
+                        .await?
+                }
+            })
+        } else {
+            None
+        };
+
+        if let Some(stats) = stats {
             println!("{} Storage restore complete: {}", style("✓").green(), stats);
+            warn_missing("Bucket", &args.buckets, &stats.buckets);
+            if metadata.late_objects > 0 {
+                println!(
+                    "{} {} objects changed after the database snapshot are not in this backup (listed in {})",
+                    style("⚠").yellow(),
+                    metadata.late_objects,
+                    LATE_OBJECTS_FILE
+                );
+            }
         } else {
             println!("{} No storage backup found, skipping", style("⚠️").yellow());
         }
     }
 
     // Edge Functions restore
-    if args.include_functions && metadata.include_functions {
+    if include_functions {
         println!("\n{} Restoring edge functions...", style("⚡").bold());
 
-        let service_key = target.service_key.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Target project requires service_key for edge functions restore")
-        })?;
+        let access_token = target.management_token()?;
 
         let functions_client =
-            FunctionsClient::new(target.project_ref.clone(), service_key.clone());
+            FunctionsClient::new(target.project_ref.clone(), access_token.to_string());
 
-        let functions_dir = args.from.join("functions");
-
-        if functions_dir.exists() {
-            let stats = restore_functions(&functions_client, &functions_dir).await?;
+        if source.has_dir("functions") {
+            let stats = restore_functions(&functions_client, &source, &args.functions).await?;
             println!(
                 "{} Edge functions restore complete: {}",
                 style("✓").green(),
                 stats
             );
+            warn_missing("Function", &args.functions, &stats.functions);
         } else {
             println!(
                 "{} No functions backup found, skipping",
@@ -192,18 +386,14 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
     if args.include_secrets && metadata.include_secrets {
         println!("\n{} Restoring secrets...", style("🔐").bold());
 
-        let secrets_file = args.from.join("secrets.json");
-
-        if secrets_file.exists() {
-            let secrets_content = fs::read_to_string(&secrets_file)?;
+        if let Some(secrets_content) = source.read_to_string("secrets.json")? {
             let secrets_backup: SecretsBackup = serde_json::from_str(&secrets_content)?;
 
             if secrets_backup.secrets.is_empty() {
                 println!("{} No secrets in backup, skipping", style("ℹ").blue());
             } else {
                 let count =
-                    restore_secrets(&secrets_backup, &args.to, args.secrets_file.as_deref())
-                        .await?;
+                    restore_secrets(&secrets_backup, &to, args.secrets_file.as_deref()).await?;
 
                 if count > 0 {
                     println!(
@@ -227,16 +417,13 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
     if args.include_vault && metadata.include_vault {
         println!("\n{} Restoring vault secrets...", style("🔐").bold());
 
-        let vault_file = args.from.join("vault_secrets.json");
-
-        if vault_file.exists() {
-            let vault_content = fs::read_to_string(&vault_file)?;
+        if let Some(vault_content) = source.read_to_string("vault_secrets.json")? {
             let vault_backup: VaultBackup = serde_json::from_str(&vault_content)?;
 
             if vault_backup.secrets.is_empty() {
                 println!("{} No vault secrets in backup, skipping", style("ℹ").blue());
             } else {
-                match restore_vault(&vault_backup, &args.to) {
+                match restore_vault(&vault_backup, &to) {
                     Ok(count) => {
                         println!(
                             "{} Vault restore complete: {} secrets created (skipped {} existing)",
@@ -257,99 +444,137 @@ pub async fn run(args: RestoreArgs) -> Result<()> {
 
     println!("\n{} Restore completed successfully!", style("🎉").bold());
 
+    if let Some((schema, into)) = &moved_schema {
+        println!(
+            "\n{} {} was restored into schema {}; copy what you need with INSERT ... SELECT, then drop it with:",
+            style("ℹ").blue(),
+            schema,
+            into
+        );
+        println!(
+            "  supamigrate restore cleanup --to {} --schema {}",
+            to, into
+        );
+    }
+
     Ok(())
 }
 
-async fn restore_functions(
-    client: &FunctionsClient,
-    functions_dir: &std::path::Path,
-) -> Result<FunctionsRestoreStats> {
-    let mut stats = FunctionsRestoreStats::default();
-
-    let entries = fs::read_dir(functions_dir)?;
-    for entry in entries {
-        let entry = entry?;
-        if entry.file_type()?.is_dir() {
-            let func_dir = entry.path();
-            let metadata_path = func_dir.join("metadata.json");
-
-            if !metadata_path.exists() {
-                continue;
-            }
-
-            // Read function metadata
-            let metadata_content = fs::read_to_string(&metadata_path)?;
-            let metadata: serde_json::Value = serde_json::from_str(&metadata_content)?;
-
-            let slug = metadata["slug"].as_str().unwrap_or_default().to_string();
-            let name = metadata["name"].as_str().unwrap_or(&slug).to_string();
-            let verify_jwt = metadata["verify_jwt"].as_bool().unwrap_or(true);
-            let entrypoint_path = metadata["entrypoint_path"].as_str().map(String::from);
-            let import_map_path = metadata["import_map_path"].as_str().map(String::from);
-
-            // Read function files
-            let mut files = Vec::new();
-            read_function_files(&func_dir, &func_dir, &mut files)?;
+/// List recovery schemas, or drop the given one
+fn cleanup(to: &str, schema: Option<&str>, yes: bool) -> Result<()> {
+    let config = Config::load(None)?;
+    let target = config.get_project(to)?;
+    let client = RecoveryClient::new(target.db_url());
+
+    let Some(schema) = schema else {
+        let schemas = client.list()?;
+        println!(
+            "\n{} Recovery schemas in {} ({} found)",
+            style("🗂️").bold(),
+            to,
+            schemas.len()
+        );
+        println!("{:-<50}", "");
+        for schema in &schemas {
+            println!(
+                "  {} {} - {} tables ({})",
+                style("•").cyan(),
+                schema.name,
+                schema.tables,
+                schema.comment
+            );
+        }
+        return Ok(());
+    };
 
-            // Filter out metadata.json
-            let files: Vec<FunctionFile> = files
-                .into_iter()
-                .filter(|f| f.name != "metadata.json")
-                .collect();
+    if !yes {
+        print!(
+            "\n⚠️  This will drop schema {} and everything in it from {}. Proceed? [y/N] ",
+            schema, to
+        );
+        io::stdout().flush()?;
 
-            if files.is_empty() {
-                continue;
-            }
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
 
-            let backup = FunctionBackup {
-                slug: slug.clone(),
-                name,
-                verify_jwt,
-                entrypoint_path,
-                import_map_path,
-                files,
-            };
-
-            info!("Deploying function: {}", slug);
-            client.deploy_function(&backup).await?;
-            stats.functions += 1;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cleanup cancelled.");
+            return Ok(());
         }
     }
 
-    Ok(stats)
+    client.drop_schema(schema)?;
+    println!("{} Dropped recovery schema {}", style("✓").green(), schema);
+
+    Ok(())
 }
 
-fn read_function_files(
-    base_dir: &std::path::Path,
-    current_dir: &std::path::Path,
-    files: &mut Vec<FunctionFile>,
-) -> Result<()> {
-    for entry in fs::read_dir(current_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.is_dir() {
-            read_function_files(base_dir, &path, files)?;
-        } else if path.is_file() {
-            let relative_path = path
-                .strip_prefix(base_dir)
-                .unwrap_or(&path)
-                .to_string_lossy()
-                .to_string();
-
-            let content = fs::read_to_string(&path)?;
-            files.push(FunctionFile {
-                name: relative_path,
+async fn restore_functions(
+    client: &FunctionsClient,
+    source: &BackupSource,
+    selected: &[String],
+) -> Result<FunctionsRestoreStats> {
+    let mut stats = FunctionsRestoreStats::default();
+
+    // functions/<slug>/metadata.json plus the function's source files
+    let mut functions: BTreeMap<String, (Option<Vec<u8>>, Vec<FunctionFile>)> = BTreeMap::new();
+    for (path, content) in source.files_under("functions")? {
+        let Some((slug, name)) = path.split_once('/') else {
+            continue;
+        };
+        if !is_selected(selected, slug) {
+            continue;
+        }
+        let function = functions.entry(slug.to_string()).or_default();
+        if name == "metadata.json" {
+            function.0 = Some(content);
+        } else {
+            function.1.push(FunctionFile {
+                name: name.to_string(),
                 content,
             });
         }
     }
-    Ok(())
+
+    for (_, (metadata, files)) in functions {
+        let Some(metadata) = metadata else {
+            continue;
+        };
+        if files.is_empty() {
+            continue;
+        }
+
+        // Read function metadata
+        let metadata: serde_json::Value = serde_json::from_slice(&metadata)?;
+
+        let slug = metadata["slug"].as_str().unwrap_or_default().to_string();
+        let name = metadata["name"].as_str().unwrap_or(&slug).to_string();
+        let verify_jwt = metadata["verify_jwt"].as_bool().unwrap_or(true);
+        let entrypoint_path = metadata["entrypoint_path"].as_str().map(String::from);
+        let import_map_path = metadata["import_map_path"].as_str().map(String::from);
+
+        let backup = FunctionBackup {
+            slug: slug.clone(),
+            name,
+            verify_jwt,
+            entrypoint_path,
+            import_map_path,
+            files,
+        };
+
+        info!("Deploying function: {}", slug);
+        client.deploy_function(&backup).await?;
+        stats.functions.insert(slug);
+    }
+
+    Ok(stats)
 }
 
 async fn restore_storage(
     client: &StorageClient,
     storage_dir: &std::path::Path,
+    source: &BackupSource,
+    selected: &[String],
 ) -> Result<RestoreStats> {
     use tokio::fs;
 
@@ -359,10 +584,13 @@ async fn restore_storage(
     while let Some(entry) = entries.next_entry().await? {
         if entry.file_type().await?.is_dir() {
             let bucket_name = entry.file_name().to_string_lossy().to_string();
+            if !is_selected(selected, &bucket_name) {
+                continue;
+            }
 
             // Create bucket (assume public for now, could store in metadata)
             client.create_bucket(&bucket_name, false).await?;
-            stats.buckets += 1;
+            stats.buckets.insert(bucket_name.clone());
 
             // Upload files
             let bucket_dir = entry.path();
@@ -372,6 +600,7 @@ async fn restore_storage(
                 if file_entry.file_type().await?.is_file() {
                     let file_name = file_entry.file_name().to_string_lossy().to_string();
                     let data = fs::read(file_entry.path()).await?;
+                    let (file_name, data) = source.decode(&file_name, data)?;
                     let data_len = data.len();
 
                     client.upload(&bucket_name, &file_name, data.into()).await?;
@@ -385,9 +614,78 @@ async fn restore_storage(
     Ok(stats)
 }
 
+/// Upload the objects of an incremental backup from the shared object store
+async fn restore_storage_incremental(
+    client: &StorageClient,
+    index: &StorageIndex,
+    store: &ObjectStore,
+    source: &BackupSource,
+    selected: &[String],
+) -> Result<RestoreStats> {
+    let mut stats = RestoreStats::default();
+
+    for object in &index.objects {
+        if !is_selected(selected, &object.bucket) {
+            continue;
+        }
+        if stats.buckets.insert(object.bucket.clone()) {
+            client.create_bucket(&object.bucket, false).await?;
+        }
+
+        let (name, data) = store.read(&object.sha256)?;
+        let (_, data) = source.decode(&name, data)?;
+        let data_len = data.len();
+
+        client
+            .upload(&object.bucket, &object.path, data.into())
+            .await?;
+        stats.objects += 1;
+        stats.bytes += data_len;
+    }
+
+    Ok(stats)
+}
+
+/// Upload storage objects straight from the archive stream
+async fn restore_storage_archive(
+    client: &StorageClient,
+    archive_path: &std::path::Path,
+    source: &BackupSource,
+    selected: &[String],
+) -> Result<RestoreStats> {
+    let mut stats = RestoreStats::default();
+
+    let mut archive = ArchiveReader::entries(archive_path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let Some((bucket_name, object)) = storage_object(&path) else {
+            continue;
+        };
+        if !is_selected(selected, bucket_name) {
+            continue;
+        }
+
+        if stats.buckets.insert(bucket_name.to_string()) {
+            client.create_bucket(bucket_name, false).await?;
+        }
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        let (object, data) = source.decode(object, data)?;
+        let data_len = data.len();
+
+        client.upload(bucket_name, &object, data.into()).await?;
+        stats.objects += 1;
+        stats.bytes += data_len;
+    }
+
+    Ok(stats)
+}
+
 #[derive(Default)]
 struct RestoreStats {
-    buckets: usize,
+    buckets: BTreeSet<String>,
     objects: usize,
     bytes: usize,
 }
@@ -397,18 +695,141 @@ impl std::fmt::Display for RestoreStats {
         write!(
             f,
             "{} buckets, {} objects restored",
-            self.buckets, self.objects
+            self.buckets.len(),
+            self.objects
         )
     }
 }
 
 #[derive(Default)]
 struct FunctionsRestoreStats {
-    functions: usize,
+    functions: BTreeSet<String>,
 }
 
 impl std::fmt::Display for FunctionsRestoreStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} functions deployed", self.functions)
+        write!(f, "{} functions deployed", self.functions.len())
+    }
+}
+
+/// Whether a bucket or function is restored: all are when none were selected
+fn is_selected(selected: &[String], name: &str) -> bool {
+    selected.is_empty() || selected.iter().any(|s| s == name)
+}
+
+/// ` (only a, b)` for an included, non-empty selection
+fn selection(included: bool, selected: &[String]) -> String {
+    if !included || selected.is_empty() {
+        String::new()
+    } else {
+        format!(" (only {})", selected.join(", "))
+    }
+}
+
+fn database_plan(restore: bool, tables: &[String], schemas: &[String], data_only: bool) -> String {
+    if !restore {
+        return "skipped".to_string();
+    }
+
+    let mut parts = Vec::new();
+    if !tables.is_empty() {
+        parts.push(format!("tables {}", tables.join(", ")));
+    }
+    if !schemas.is_empty() {
+        parts.push(format!("schemas {}", schemas.join(", ")));
+    }
+    let mut plan = if parts.is_empty() {
+        "everything".to_string()
+    } else {
+        parts.join(", ")
+    };
+    if data_only {
+        plan.push_str(" (data only)");
+    }
+    plan
+}
+
+fn warn_missing(kind: &str, selected: &[String], restored: &BTreeSet<String>) {
+    for name in selected.iter().filter(|s| !restored.contains(*s)) {
+        println!(
+            "{} {} {} not found in backup",
+            style("⚠").yellow(),
+            kind,
+            name
+        );
+    }
+}
+
+/// The schema every object of a dump is in, for `--into-schema`
+fn single_schema(sql: &str) -> Result<String> {
+    let schemas = SqlTransformer::dump_schemas(sql);
+    match schemas.len() {
+        1 => Ok(schemas.into_iter().next().unwrap_or_default()),
+        0 => Err(anyhow::anyhow!(
+            "The backup has no database objects to restore"
+        )),
+        _ => Err(anyhow::anyhow!(
+            "--into-schema restores one schema at a time, but the backup has objects in {}; \
+             pick one with --schema or --table",
+            schemas.into_iter().collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Read the database dump and keep only the selected tables and schemas
+fn load_dump(
+    source: &BackupSource,
+    metadata: &BackupMetadata,
+    filter: &DumpFilter,
+) -> Result<String> {
+    let sql = source.read_dump(metadata)?;
+    if filter.is_empty() {
+        return Ok(sql);
+    }
+
+    let filtered = filter.apply(&sql);
+    if !filtered.missing_tables.is_empty() {
+        return Err(SupamigrateError::InvalidBackup(format!(
+            "Tables not found in backup: {}",
+            filtered.missing_tables.join(", ")
+        ))
+        .into());
+    }
+    info!("Selected {} dump entries", filtered.entries);
+    Ok(filtered.sql)
+}
+
+/// Check the backup against its manifest before anything touches the target
+fn check_integrity(source: &BackupSource, ignore_integrity: bool) -> Result<()> {
+    let Some(report) = source.verify()? else {
+        println!(
+            "{} No manifest.json in backup, skipping integrity check",
+            style("ℹ").blue()
+        );
+        return Ok(());
+    };
+
+    if report.is_ok() {
+        info!("Backup integrity verified: {} files", report.checked);
+        return Ok(());
+    }
+
+    for problem in &report.problems {
+        println!("  {} {}", style("✗").red(), problem);
+    }
+
+    if ignore_integrity {
+        println!(
+            "{} Backup failed integrity check ({} problems), continuing because of --ignore-integrity",
+            style("⚠").yellow(),
+            report.problems.len()
+        );
+        Ok(())
+    } else {
+        Err(SupamigrateError::InvalidBackup(format!(
+            "{} files do not match manifest.json (use --ignore-integrity to restore anyway)",
+            report.problems.len()
+        ))
+        .into())
     }
 }
@@ -21,12 +21,9 @@ async fn list_secrets(project_name: &str) -> Result<()> {
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let access_token = project
-        .access_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires access_token for secrets operations. Get one at: https://supabase.com/dashboard/account/tokens"))?;
+    let access_token = project.management_token()?;
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::new(project.project_ref.clone(), access_token.to_string());
     let secrets = client.list_secrets().await?;
 
     println!(
@@ -57,12 +54,9 @@ async fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let access_token = project
-        .access_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires access_token for secrets operations"))?;
+    let access_token = project.management_token()?;
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::new(project.project_ref.clone(), access_token.to_string());
     let secrets = client.list_secrets().await?;
 
     let template = generate_env_template(&secrets);
@@ -87,10 +81,7 @@ async fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let access_token = project
-        .access_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires access_token for secrets operations"))?;
+    let access_token = project.management_token()?;
 
     let content = std::fs::read_to_string(file)?;
     let secrets = parse_env_file(&content);
@@ -136,7 +127,7 @@ async fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
         return Ok(());
     }
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::new(project.project_ref.clone(), access_token.to_string());
 
     let secrets_to_create: Vec<Secret> = secrets_with_values
         .into_iter()
@@ -162,16 +153,10 @@ async fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
     let source = config.get_project(from_name)?;
     let target = config.get_project(to_name)?;
 
-    let source_token = source
-        .access_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Source project requires access_token"))?;
-    let target_token = target
-        .access_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Target project requires access_token"))?;
+    let source_token = source.management_token()?;
+    let target_token = target.management_token()?;
 
-    let source_client = SecretsClient::new(source.project_ref.clone(), source_token.clone());
+    let source_client = SecretsClient::new(source.project_ref.clone(), source_token.to_string());
     let secrets = source_client.list_secrets().await?;
 
     if secrets.is_empty() {
@@ -216,7 +201,7 @@ async fn copy_secrets(from_name: &str, to_name: &str) -> Result<()> {
         return Ok(());
     }
 
-    let target_client = SecretsClient::new(target.project_ref.clone(), target_token.clone());
+    let target_client = SecretsClient::new(target.project_ref.clone(), target_token.to_string());
     target_client.create_secrets(&secrets_to_create).await?;
 
     println!(
@@ -241,11 +226,12 @@ pub async fn backup_secrets(project_name: &str) -> Result<Option<SecretsBackup>>
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let Some(access_token) = project.access_token.as_ref() else {
+    if !project.has_secrets_access() {
         return Ok(None);
-    };
+    }
+    let access_token = project.management_token()?;
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::new(project.project_ref.clone(), access_token.to_string());
     let backup = client.backup().await?;
 
     Ok(Some(backup))
@@ -260,10 +246,7 @@ pub async fn restore_secrets(
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let access_token = project
-        .access_token
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires access_token for secrets operations"))?;
+    let access_token = project.management_token()?;
 
     if backup.secrets.is_empty() {
         return Ok(0);
@@ -317,7 +300,7 @@ pub async fn restore_secrets(
         return Ok(0);
     }
 
-    let client = SecretsClient::new(project.project_ref.clone(), access_token.clone());
+    let client = SecretsClient::new(project.project_ref.clone(), access_token.to_string());
     client.create_secrets(&secrets_to_create).await?;
 
     Ok(secrets_to_create.len())
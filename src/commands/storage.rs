@@ -26,12 +26,9 @@ async fn list_buckets(project_name: &str) -> Result<()> {
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let service_key = project
-        .service_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires service_key for storage operations"))?;
+    let service_key = project.storage_key()?;
 
-    let client = StorageClient::new(project.api_url(), service_key.clone());
+    let client = StorageClient::new(project.api_url(), service_key.to_string());
     let buckets = client.list_buckets().await?;
 
     println!("\n{} Buckets in {}", style("📦").bold(), project_name);
@@ -54,17 +51,11 @@ async fn sync_storage(from: &str, to: &str, bucket: Option<&str>, parallel: usiz
     let source = config.get_project(from)?;
     let target = config.get_project(to)?;
 
-    let source_key = source
-        .service_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Source project requires service_key"))?;
-    let target_key = target
-        .service_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Target project requires service_key"))?;
+    let source_key = source.storage_key()?;
+    let target_key = target.storage_key()?;
 
-    let source_client = StorageClient::new(source.api_url(), source_key.clone());
-    let target_client = StorageClient::new(target.api_url(), target_key.clone());
+    let source_client = StorageClient::new(source.api_url(), source_key.to_string());
+    let target_client = StorageClient::new(target.api_url(), target_key.to_string());
 
     println!(
         "\n{} Syncing storage: {} → {}",
@@ -79,8 +70,8 @@ async fn sync_storage(from: &str, to: &str, bucket: Option<&str>, parallel: usiz
 
     let stats = if let Some(bucket_name) = bucket {
         let target = config.get_project(to)?;
-        let target_key = target.service_key.as_ref().unwrap();
-        let target_client = StorageClient::new(target.api_url(), target_key.clone());
+        let target_key = target.storage_key()?;
+        let target_client = StorageClient::new(target.api_url(), target_key.to_string());
         transfer.sync_bucket(bucket_name, &target_client).await?
     } else {
         transfer.sync_all().await?
@@ -98,12 +89,9 @@ async fn download_storage(
     let config = Config::load(None)?;
     let project = config.get_project(project_name)?;
 
-    let service_key = project
-        .service_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires service_key"))?;
+    let service_key = project.storage_key()?;
 
-    let client = StorageClient::new(project.api_url(), service_key.clone());
+    let client = StorageClient::new(project.api_url(), service_key.to_string());
 
     println!(
         "\n{} Downloading storage from {} to {}",
@@ -137,12 +125,9 @@ async fn upload_storage(from: &std::path::Path, to: &str, bucket: &str) -> Resul
     let config = Config::load(None)?;
     let project = config.get_project(to)?;
 
-    let service_key = project
-        .service_key
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Project requires service_key"))?;
+    let service_key = project.storage_key()?;
 
-    let client = StorageClient::new(project.api_url(), service_key.clone());
+    let client = StorageClient::new(project.api_url(), service_key.to_string());
 
     println!(
         "\n{} Uploading {} to {}/{}",
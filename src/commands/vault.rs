@@ -1,3 +1,4 @@
+use crate::backup::{is_encrypted, Encryption};
 use crate::cli::{VaultArgs, VaultCommands};
 use crate::config::Config;
 use crate::db::{VaultBackup, VaultClient};
@@ -86,13 +87,23 @@ fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
         return Ok(());
     }
 
+    let encryption = Encryption::from_configs([project.encryption.as_ref()])?;
+
     // Security warning
-    println!(
-        "\n{} {} This file will contain DECRYPTED secret values!",
-        style("⚠").yellow().bold(),
-        style("WARNING:").yellow().bold()
-    );
-    println!("  Store it securely and delete after use.\n");
+    if let Some(encryption) = &encryption {
+        println!(
+            "\n{} Secret values will be encrypted ({}) before being written\n",
+            style("🔐").bold(),
+            encryption.describe()
+        );
+    } else {
+        println!(
+            "\n{} {} This file will contain DECRYPTED secret values!",
+            style("⚠").yellow().bold(),
+            style("WARNING:").yellow().bold()
+        );
+        println!("  Store it securely and delete after use.\n");
+    }
 
     print!("Proceed with export? [y/N] ");
     io::stdout().flush()?;
@@ -106,7 +117,14 @@ fn export_secrets(project_name: &str, output: &Path) -> Result<()> {
     }
 
     let json = serde_json::to_string_pretty(&backup)?;
-    fs::write(output, json)?;
+    let output = match &encryption {
+        // Encrypted in memory, so the values never reach disk in clear
+        Some(encryption) => encryption.write_file(output, json.as_bytes())?,
+        None => {
+            fs::write(output, json)?;
+            output.to_path_buf()
+        }
+    };
 
     println!(
         "\n{} Exported {} vault secrets to {}",
@@ -134,8 +152,19 @@ fn import_secrets(project_name: &str, file: &Path) -> Result<()> {
         return Ok(());
     }
 
-    let content = fs::read_to_string(file)?;
-    let backup: VaultBackup = serde_json::from_str(&content)?;
+    let mut content = fs::read(file)?;
+    if is_encrypted(&content) {
+        let encryption =
+            Encryption::from_configs([project.encryption.as_ref()])?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} is encrypted but project '{}' has no encryption configured",
+                    file.display(),
+                    project_name
+                )
+            })?;
+        content = encryption.decrypt(&content)?;
+    }
+    let backup: VaultBackup = serde_json::from_slice(&content)?;
 
     if backup.secrets.is_empty() {
         println!("{} No secrets found in file", style("ℹ").blue());
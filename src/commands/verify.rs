@@ -0,0 +1,200 @@
+use crate::cli::VerifyArgs;
+use crate::config::{Config, ProjectConfig};
+use crate::db::verify::{compare_row_counts, compare_storage, first_differing_chunk, Mismatch};
+use crate::db::{TableName, TablesClient, VerifyClient};
+use anyhow::Result;
+use console::style;
+
+/// What to compare
+pub struct VerifyOptions {
+    pub row_counts: bool,
+    pub checksums: bool,
+    pub chunk_size: u64,
+    pub storage: bool,
+    /// Only these tables instead of every source table
+    pub tables: Option<Vec<TableName>>,
+    /// Target tables may hold more rows than the source (a merge without deletes)
+    pub allow_extra_rows: bool,
+}
+
+pub fn run(args: VerifyArgs) -> Result<()> {
+    let config = Config::load(None)?;
+    let source = config.get_project(&args.from)?;
+    let target = config.get_project(&args.to)?;
+
+    let excluded_schemas = args
+        .exclude_schemas
+        .unwrap_or_else(|| config.defaults.excluded_schemas.clone());
+    let excluded_tables = args.exclude_tables.unwrap_or_default();
+
+    let options = VerifyOptions {
+        row_counts: true,
+        checksums: args.checksums,
+        chunk_size: args.chunk_size.max(1),
+        storage: args.include_storage,
+        tables: None,
+        allow_extra_rows: false,
+    };
+
+    let mismatches = verify_projects(
+        source,
+        target,
+        &excluded_schemas,
+        &excluded_tables,
+        &options,
+    )?;
+    if mismatches > 0 {
+        return Err(anyhow::anyhow!(
+            "Verification failed: {} mismatches between {} and {}",
+            mismatches,
+            args.from,
+            args.to
+        ));
+    }
+
+    println!("\n{} Source and target match", style("✓").green());
+    Ok(())
+}
+
+/// Compare source and target data, printing every mismatch; returns the mismatch count
+pub fn verify_projects(
+    source: &ProjectConfig,
+    target: &ProjectConfig,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+    options: &VerifyOptions,
+) -> Result<usize> {
+    println!("\n{} Verifying data...", style("🔍").bold());
+
+    let mut tables = match &options.tables {
+        Some(tables) => tables.clone(),
+        None => TablesClient::new(source.db_url()).load_order(excluded_schemas, excluded_tables)?,
+    };
+    tables.sort();
+    let target_tables = TablesClient::new(target.db_url()).list_tables()?;
+    let present: Vec<_> = tables
+        .iter()
+        .filter(|t| target_tables.contains(t))
+        .cloned()
+        .collect();
+
+    let mut total = 0;
+    let mut count_mismatches = Vec::new();
+    if options.row_counts {
+        let source_counts = VerifyClient::new(source.db_url()).row_counts(&tables)?;
+        let target_counts = VerifyClient::new(target.db_url()).row_counts(&present)?;
+        count_mismatches =
+            compare_row_counts(&source_counts, &target_counts, options.allow_extra_rows);
+        print_mismatches(&count_mismatches, "rows");
+        println!(
+            "{} Row counts: {} tables, {} mismatched",
+            status_mark(count_mismatches.is_empty()),
+            tables.len(),
+            count_mismatches.len()
+        );
+        total += count_mismatches.len();
+    }
+
+    if options.checksums {
+        let source_verify = VerifyClient::new(source.db_url());
+        let target_verify = VerifyClient::new(target.db_url());
+        let mut differing = 0;
+        let mut skipped = Vec::new();
+
+        for table in &present {
+            let name = format!("{}.{}", table.schema, table.table);
+            // Hashes of tables with different row counts are already known to differ
+            if count_mismatches.iter().any(|m| m.object == name) {
+                continue;
+            }
+
+            let source_columns = TablesClient::new(source.db_url()).columns(table)?;
+            if source_columns.primary_key.is_empty() {
+                skipped.push(name);
+                continue;
+            }
+            let target_columns = TablesClient::new(target.db_url()).columns(table)?;
+            let columns: Vec<String> = source_columns
+                .columns
+                .into_iter()
+                .filter(|c| target_columns.columns.contains(c))
+                .collect();
+
+            let source_hashes = source_verify.checksums(
+                table,
+                &columns,
+                &source_columns.primary_key,
+                options.chunk_size,
+            )?;
+            let target_hashes = target_verify.checksums(
+                table,
+                &columns,
+                &source_columns.primary_key,
+                options.chunk_size,
+            )?;
+
+            if let Some(chunk) = first_differing_chunk(&source_hashes, &target_hashes) {
+                let first_row = chunk as u64 * options.chunk_size + 1;
+                println!(
+                    "  {} {}: content differs from row {} (chunk {}, ordered by primary key)",
+                    style("✗").red(),
+                    name,
+                    first_row,
+                    chunk
+                );
+                differing += 1;
+            }
+        }
+
+        if !skipped.is_empty() {
+            println!(
+                "  {} No primary key, checksums skipped: {}",
+                style("⚠").yellow(),
+                skipped.join(", ")
+            );
+        }
+        println!(
+            "{} Checksums: {} tables differ",
+            status_mark(differing == 0),
+            differing
+        );
+        total += differing;
+    }
+
+    if options.storage {
+        let source_usage = VerifyClient::new(source.db_url()).storage_usage()?;
+        let target_usage = VerifyClient::new(target.db_url()).storage_usage()?;
+        let storage_mismatches = compare_storage(&source_usage, &target_usage);
+        print_mismatches(&storage_mismatches, "bucket");
+        println!(
+            "{} Storage: {} buckets, {} mismatched",
+            status_mark(storage_mismatches.is_empty()),
+            source_usage.len(),
+            storage_mismatches.len()
+        );
+        total += storage_mismatches.len();
+    }
+
+    Ok(total)
+}
+
+fn print_mismatches(mismatches: &[Mismatch], kind: &str) {
+    for mismatch in mismatches {
+        println!(
+            "  {} {} ({}): source {}, target {}",
+            style("✗").red(),
+            mismatch.object,
+            kind,
+            mismatch.source,
+            mismatch.target
+        );
+    }
+}
+
+fn status_mark(ok: bool) -> console::StyledObject<&'static str> {
+    if ok {
+        style("✓").green()
+    } else {
+        style("✗").red()
+    }
+}
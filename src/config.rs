@@ -16,6 +16,10 @@ pub struct Config {
 
     #[serde(default)]
     pub defaults: DefaultsConfig,
+
+    /// S3-compatible object storage for `s3://` backup locations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<S3Config>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +34,10 @@ pub struct ProjectConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub service_key: Option<String>,
 
+    /// Anon key; rewritten alongside the service key in database webhook headers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anon_key: Option<String>,
+
     /// Custom database host (defaults to db.{project_ref}.supabase.co)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub db_host: Option<String>,
@@ -42,10 +50,107 @@ pub struct ProjectConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_url: Option<String>,
 
-    /// Personal access token (required for secrets operations)
+    /// Personal access token for the Management API (required for edge functions
+    /// and secrets operations)
     /// Generate at: <https://supabase.com/dashboard/account/tokens>
     #[serde(skip_serializing_if = "Option::is_none")]
     pub access_token: Option<String>,
+
+    /// Encrypt backups and vault exports of this project with age
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<EncryptionConfig>,
+
+    /// Which timestamped backups of this project to keep when pruning
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// age X25519 recipients (public keys starting with "age1")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+
+    /// age identity files (private keys) used to decrypt; their public keys are also recipients
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identity_files: Vec<String>,
+
+    /// Environment variable holding a passphrase, used when no recipients are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passphrase_env: Option<String>,
+}
+
+/// Backup retention policy; a backup is kept if any rule selects it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Keep the N most recent backups
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub keep_last: u32,
+
+    /// Keep the newest backup of each day for this many days
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub keep_daily: u32,
+
+    /// Keep the newest backup of each ISO week for this many weeks
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub keep_weekly: u32,
+
+    /// Keep the newest backup of each month for this many months
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub keep_monthly: u32,
+}
+
+impl RetentionConfig {
+    /// Whether no rule is set
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl std::fmt::Display for RetentionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rules: Vec<String> = [
+            (self.keep_last, "last"),
+            (self.keep_daily, "daily"),
+            (self.keep_weekly, "weekly"),
+            (self.keep_monthly, "monthly"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, rule)| format!("{} {}", rule, count))
+        .collect();
+
+        if rules.is_empty() {
+            write!(f, "keep all")
+        } else {
+            write!(f, "keep {}", rules.join(", "))
+        }
+    }
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Credentials for `s3://` backup locations; unset fields fall back to the AWS_* environment variables
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct S3Config {
+    /// Endpoint of an S3-compatible service (MinIO, Supabase Storage S3); AWS when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_key_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_access_key: Option<String>,
+
+    /// Address buckets as `<endpoint>/<bucket>` (default when an endpoint is set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_style: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -175,6 +280,54 @@ impl ProjectConfig {
     pub fn has_secrets_access(&self) -> bool {
         self.access_token.is_some()
     }
+
+    /// Service role key for the storage API
+    pub fn storage_key(&self) -> Result<&str> {
+        let key = self.service_key.as_deref().ok_or_else(|| {
+            SupamigrateError::Config(format!(
+                "Project {} has no service_key; storage operations need the service_role key \
+                 from Project Settings → API",
+                self.project_ref
+            ))
+        })?;
+        if is_access_token(key) {
+            return Err(SupamigrateError::Config(format!(
+                "service_key of project {} is a personal access token; storage operations need \
+                 the service_role key (personal access tokens belong in access_token)",
+                self.project_ref
+            )));
+        }
+        Ok(key)
+    }
+
+    /// Personal access token for Management API calls (edge functions, secrets)
+    pub fn management_token(&self) -> Result<&str> {
+        let token = self.access_token.as_deref().ok_or_else(|| {
+            SupamigrateError::Config(format!(
+                "Project {} has no access_token; edge functions and secrets use the Management API, \
+                 which needs a personal access token: https://supabase.com/dashboard/account/tokens",
+                self.project_ref
+            ))
+        })?;
+        if is_api_key(token) {
+            return Err(SupamigrateError::Config(format!(
+                "access_token of project {} is a project API key; the Management API needs a \
+                 personal access token (sbp_...): https://supabase.com/dashboard/account/tokens",
+                self.project_ref
+            )));
+        }
+        Ok(token)
+    }
+}
+
+/// Personal access tokens are prefixed with `sbp_`
+fn is_access_token(key: &str) -> bool {
+    key.starts_with("sbp_")
+}
+
+/// Project API keys (anon, service_role) are JWTs
+fn is_api_key(key: &str) -> bool {
+    key.starts_with("eyJ")
 }
 
 /// Generate a sample config file
@@ -187,7 +340,21 @@ pub fn generate_sample_config() -> String {
 project_ref = "your-prod-project-ref"
 db_password = "your-db-password"
 service_key = "your-service-role-key"  # Optional, needed for storage
-access_token = "sbp_xxx"  # Optional, needed for secrets (https://supabase.com/dashboard/account/tokens)
+anon_key = "your-anon-key"  # Optional, rewritten in database webhook headers
+access_token = "sbp_xxx"  # Optional, needed for edge functions and secrets (https://supabase.com/dashboard/account/tokens)
+
+# Optional: encrypt backups and vault exports with age (https://age-encryption.org)
+# [projects.production.encryption]
+# recipients = ["age1..."]  # Public keys to encrypt to
+# identity_files = ["~/.config/supamigrate/backup.key"]  # Private keys for restore
+# passphrase_env = "SUPAMIGRATE_BACKUP_PASSPHRASE"  # Used when no recipients are set
+
+# Optional: prune old backups after each backup and with `supamigrate backup prune`
+# [projects.production.retention]
+# keep_last = 3      # Most recent backups
+# keep_daily = 7     # One per day for 7 days
+# keep_weekly = 4    # One per week for 4 weeks
+# keep_monthly = 12  # One per month for 12 months
 
 [projects.staging]
 project_ref = "your-staging-project-ref"
@@ -216,3 +383,42 @@ excluded_schemas = [
 "#
     .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(service_key: Option<&str>, access_token: Option<&str>) -> ProjectConfig {
+        ProjectConfig {
+            project_ref: "abc".to_string(),
+            db_password: "secret".to_string(),
+            service_key: service_key.map(str::to_string),
+            anon_key: None,
+            db_host: None,
+            db_port: None,
+            api_url: None,
+            access_token: access_token.map(str::to_string),
+            encryption: None,
+            retention: None,
+        }
+    }
+
+    #[test]
+    fn test_credentials() {
+        let ok = project(Some("eyJhbGciOiJIUzI1NiJ9.x.y"), Some("sbp_123"));
+        assert_eq!(ok.storage_key().unwrap(), "eyJhbGciOiJIUzI1NiJ9.x.y");
+        assert_eq!(ok.management_token().unwrap(), "sbp_123");
+
+        let swapped = project(Some("sbp_123"), Some("eyJhbGciOiJIUzI1NiJ9.x.y"));
+        assert!(swapped.storage_key().is_err());
+        assert!(swapped
+            .management_token()
+            .unwrap_err()
+            .to_string()
+            .contains("project API key"));
+
+        let missing = project(None, None);
+        assert!(missing.storage_key().is_err());
+        assert!(missing.management_token().is_err());
+    }
+}
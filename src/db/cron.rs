@@ -0,0 +1,236 @@
+use crate::db::psql::{self, quote_literal};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// A scheduled job from `cron.job`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CronJob {
+    pub jobid: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jobname: Option<String>,
+    pub schedule: String,
+    pub command: String,
+    pub database: String,
+    pub username: String,
+    pub active: bool,
+}
+
+/// Backup structure for pg_cron jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CronBackup {
+    pub jobs: Vec<CronJob>,
+    pub exported_at: String,
+}
+
+/// Differences between the jobs of two projects
+#[derive(Debug, Default)]
+pub struct CronDiff<'a> {
+    pub only_source: Vec<&'a CronJob>,
+    pub only_target: Vec<&'a CronJob>,
+    pub changed: Vec<(&'a CronJob, &'a CronJob)>,
+    pub unchanged: usize,
+}
+
+/// Client for reading and scheduling pg_cron jobs via SQL
+pub struct CronClient {
+    db_url: String,
+}
+
+impl CronJob {
+    /// Identity used to match jobs across projects (name, or schedule + command if unnamed)
+    fn key(&self) -> (Option<&str>, &str, &str) {
+        match &self.jobname {
+            Some(name) => (Some(name.as_str()), "", ""),
+            None => (None, self.schedule.as_str(), self.command.as_str()),
+        }
+    }
+
+    fn same_definition(&self, other: &CronJob) -> bool {
+        self.schedule == other.schedule
+            && self.command == other.command
+            && self.database == other.database
+            && self.username == other.username
+            && self.active == other.active
+    }
+
+    /// Display name (job name, or `#id` for unnamed jobs)
+    pub fn display_name(&self) -> String {
+        self.jobname
+            .clone()
+            .unwrap_or_else(|| format!("#{}", self.jobid))
+    }
+}
+
+impl CronClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// Check if the pg_cron extension is enabled in the database
+    pub fn is_cron_enabled(&self) -> Result<bool> {
+        psql::has_extension(&self.db_url, "pg_cron")
+    }
+
+    /// List all scheduled jobs
+    pub fn list_jobs(&self) -> Result<Vec<CronJob>> {
+        if !self.is_cron_enabled()? {
+            return Ok(vec![]);
+        }
+
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'jobid', jobid,
+                        'jobname', jobname,
+                        'schedule', schedule,
+                        'command', command,
+                        'database', database,
+                        'username', username,
+                        'active', active
+                    )
+                    ORDER BY jobid
+                ),
+                '[]'::json
+            )::text
+            FROM cron.job
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// Schedule a job, optionally forcing it to be created inactive
+    pub fn create_job(&self, job: &CronJob, disabled: bool) -> Result<()> {
+        let schedule_call = match &job.jobname {
+            Some(name) => format!(
+                "cron.schedule({}, {}, {})",
+                quote_literal(name),
+                quote_literal(&job.schedule),
+                quote_literal(&job.command)
+            ),
+            None => format!(
+                "cron.schedule({}, {})",
+                quote_literal(&job.schedule),
+                quote_literal(&job.command)
+            ),
+        };
+
+        // A NULL username leaves the job owned by the connecting role
+        let sql = format!(
+            "SELECT cron.alter_job({}, database := {}, username := NULLIF({}, current_user::text), active := {})",
+            schedule_call,
+            quote_literal(&job.database),
+            quote_literal(&job.username),
+            job.active && !disabled
+        );
+
+        debug!("Scheduling cron job: {}", job.display_name());
+        psql::query(&self.db_url, &sql)?;
+        Ok(())
+    }
+
+    /// Backup all scheduled jobs
+    pub fn backup(&self) -> Result<CronBackup> {
+        Ok(CronBackup {
+            jobs: self.list_jobs()?,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Restore jobs from a backup; named jobs are updated, identical unnamed jobs skipped
+    pub fn restore(&self, backup: &CronBackup, disabled: bool) -> Result<usize> {
+        let existing = self.list_jobs()?;
+        let mut count = 0;
+
+        for job in &backup.jobs {
+            if job.jobname.is_none() && existing.iter().any(|e| e.key() == job.key()) {
+                debug!(
+                    "Unnamed cron job '{}' already exists, skipping",
+                    job.command
+                );
+                continue;
+            }
+
+            self.create_job(job, disabled)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Compare jobs between a source and a target project
+pub fn diff_jobs<'a>(source: &'a [CronJob], target: &'a [CronJob]) -> CronDiff<'a> {
+    let mut diff = CronDiff::default();
+
+    for job in source {
+        match target.iter().find(|t| t.key() == job.key()) {
+            Some(t) if job.same_definition(t) => diff.unchanged += 1,
+            Some(t) => diff.changed.push((job, t)),
+            None => diff.only_source.push(job),
+        }
+    }
+
+    diff.only_target = target
+        .iter()
+        .filter(|t| !source.iter().any(|s| s.key() == t.key()))
+        .collect();
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: i64, name: Option<&str>, schedule: &str, command: &str) -> CronJob {
+        CronJob {
+            jobid: id,
+            jobname: name.map(String::from),
+            schedule: schedule.to_string(),
+            command: command.to_string(),
+            database: "postgres".to_string(),
+            username: "postgres".to_string(),
+            active: true,
+        }
+    }
+
+    #[test]
+    fn test_diff_jobs() {
+        let source = vec![
+            job(1, Some("cleanup"), "0 3 * * *", "DELETE FROM logs"),
+            job(2, Some("digest"), "0 8 * * *", "SELECT send_digest()"),
+            job(3, None, "*/5 * * * *", "SELECT refresh()"),
+            job(4, Some("report"), "0 0 * * 0", "SELECT report()"),
+        ];
+        let target = vec![
+            job(10, Some("cleanup"), "0 3 * * *", "DELETE FROM logs"),
+            job(11, Some("digest"), "0 9 * * *", "SELECT send_digest()"),
+            job(12, None, "*/5 * * * *", "SELECT refresh()"),
+            job(13, Some("legacy"), "0 1 * * *", "SELECT legacy()"),
+        ];
+
+        let diff = diff_jobs(&source, &target);
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].0.display_name(), "digest");
+        assert_eq!(diff.only_source.len(), 1);
+        assert_eq!(diff.only_source[0].display_name(), "report");
+        assert_eq!(diff.only_target.len(), 1);
+        assert_eq!(diff.only_target[0].display_name(), "legacy");
+    }
+
+    #[test]
+    fn test_cron_backup_serialization() {
+        let backup = CronBackup {
+            jobs: vec![job(7, None, "0 * * * *", "SELECT 1")],
+            exported_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+
+        let json = serde_json::to_string(&backup).unwrap();
+        assert!(!json.contains("jobname"));
+        let parsed: CronBackup = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.jobs[0].display_name(), "#7");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::db::snapshot::DbSnapshot;
 use crate::error::{Result, SupamigrateError};
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -10,10 +11,12 @@ pub struct PgDump {
     excluded_tables: Vec<String>,
     schema_only: bool,
     data_only: bool,
+    table_order: Option<Vec<String>>,
+    snapshot: Option<String>,
 }
 
 /// Query remote server for PostgreSQL major version
-fn get_server_version(db_url: &str) -> Option<u32> {
+pub fn get_server_version(db_url: &str) -> Option<u32> {
     let output = Command::new("psql")
         .arg(db_url)
         .arg("-t") // tuples only
@@ -34,6 +37,28 @@ fn get_server_version(db_url: &str) -> Option<u32> {
     Some(version_num / 10000) // 150001 -> 15
 }
 
+/// Whether a table is left out of the dump by the given exclusions: schemas support
+/// `prefix*` patterns, tables are given as `table` or `schema.table`
+pub fn is_excluded(
+    schema: &str,
+    table: &str,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) -> bool {
+    let schema_excluded = excluded_schemas
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => schema.starts_with(prefix),
+            None => pattern == schema,
+        });
+    let qualified = format!("{}.{}", schema, table);
+
+    schema_excluded
+        || excluded_tables
+            .iter()
+            .any(|e| e == table || *e == qualified)
+}
+
 /// Find pg_dump binary compatible with server version
 fn find_compatible_pg_dump(server_major: u32) -> PathBuf {
     // Check versions from exact match up to +3 (pg_dump is forward-compatible)
@@ -94,6 +119,8 @@ impl PgDump {
             excluded_tables: Vec::new(),
             schema_only: false,
             data_only: false,
+            table_order: None,
+            snapshot: None,
         }
     }
 
@@ -117,6 +144,19 @@ impl PgDump {
         self
     }
 
+    /// Dump the data of these tables one at a time, in the given order (data-only),
+    /// all read from one snapshot
+    pub fn table_order(mut self, tables: Vec<String>) -> Self {
+        self.table_order = Some(tables);
+        self
+    }
+
+    /// Read the database as of a snapshot exported by another session
+    pub fn snapshot(mut self, id: Option<String>) -> Self {
+        self.snapshot = id;
+        self
+    }
+
     /// Check if pg_dump is available
     fn check_available(&self) -> Result<()> {
         let output = Command::new(&self.binary_path).arg("--version").output();
@@ -143,10 +183,11 @@ impl PgDump {
         info!("Starting database dump...");
 
         let mut cmd = Command::new(&self.binary_path);
-        cmd.arg(&self.db_url)
-            .arg("--clean")
-            .arg("--if-exists")
-            .arg("--quote-all-identifiers");
+        cmd.arg(&self.db_url).arg("--quote-all-identifiers");
+
+        if !self.data_only {
+            cmd.arg("--clean").arg("--if-exists");
+        }
 
         // Add schema/data only flags
         if self.schema_only {
@@ -159,6 +200,10 @@ impl PgDump {
         // Exclude storage.objects data (always)
         cmd.arg("--exclude-table-data=storage.objects");
 
+        if let Some(snapshot) = &self.snapshot {
+            cmd.arg(format!("--snapshot={}", snapshot));
+        }
+
         // Exclude schemas
         if !self.excluded_schemas.is_empty() {
             let schema_pattern = self.excluded_schemas.join("|");
@@ -193,11 +238,38 @@ impl PgDump {
     pub fn dump_to_string(&self) -> Result<String> {
         self.check_available()?;
 
+        match &self.table_order {
+            Some(tables) => {
+                // Every table is read from one snapshot, so rows referenced across
+                // tables are consistent
+                let exported = match &self.snapshot {
+                    Some(_) => None,
+                    None => Some(DbSnapshot::export(&self.db_url)?),
+                };
+                let snapshot = self
+                    .snapshot
+                    .as_deref()
+                    .or_else(|| exported.as_ref().and_then(|s| s.id.as_deref()));
+
+                let mut sql = String::new();
+                for table in tables {
+                    debug!("Dumping data for {}", table);
+                    sql.push_str(&self.run_to_string(Some(table), snapshot)?);
+                }
+                Ok(sql)
+            }
+            None => self.run_to_string(None, self.snapshot.as_deref()),
+        }
+    }
+
+    fn run_to_string(&self, table: Option<&str>, snapshot: Option<&str>) -> Result<String> {
         let mut cmd = Command::new(&self.binary_path);
-        cmd.arg(&self.db_url)
-            .arg("--clean")
-            .arg("--if-exists")
-            .arg("--quote-all-identifiers");
+        cmd.arg(&self.db_url).arg("--quote-all-identifiers");
+
+        // pg_dump rejects --clean together with --data-only
+        if !self.data_only {
+            cmd.arg("--clean").arg("--if-exists");
+        }
 
         if self.schema_only {
             cmd.arg("--schema-only");
@@ -208,16 +280,24 @@ impl PgDump {
 
         cmd.arg("--exclude-table-data=storage.objects");
 
-        if !self.excluded_schemas.is_empty() {
-            let schema_pattern = self.excluded_schemas.join("|");
-            cmd.arg(format!("--exclude-schema={}", schema_pattern));
+        if let Some(snapshot) = snapshot {
+            cmd.arg(format!("--snapshot={}", snapshot));
         }
 
-        for table in &self.excluded_tables {
-            cmd.arg(format!("--exclude-table={}", table));
-        }
+        if let Some(table) = table {
+            cmd.arg(format!("--table={}", table));
+        } else {
+            if !self.excluded_schemas.is_empty() {
+                let schema_pattern = self.excluded_schemas.join("|");
+                cmd.arg(format!("--exclude-schema={}", schema_pattern));
+            }
 
-        cmd.arg("--schema=*");
+            for table in &self.excluded_tables {
+                cmd.arg(format!("--exclude-table={}", table));
+            }
+
+            cmd.arg("--schema=*");
+        }
 
         let output = cmd.output()?;
 
@@ -0,0 +1,242 @@
+use crate::db::dump::get_server_version;
+use crate::db::psql::{self, quote_ident};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// An extension installed in a database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extension {
+    pub name: String,
+    pub schema: String,
+    pub version: String,
+    /// Whether objects outside the extension (columns, defaults, indexes, triggers...)
+    /// depend on it; unknown for older backups, so assumed
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// An extension the server can install (from `pg_available_extensions`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailableExtension {
+    pub name: String,
+    pub default_version: Option<String>,
+    pub installed_version: Option<String>,
+}
+
+/// Result of comparing source extensions with what the target offers
+#[derive(Debug, Default)]
+pub struct ExtensionPlan {
+    /// Available on the target but not installed yet
+    pub to_enable: Vec<Extension>,
+    /// Already installed on the target
+    pub installed: Vec<Extension>,
+    /// Not available on the target's Postgres server
+    pub unavailable: Vec<Extension>,
+}
+
+/// Client for reading and enabling extensions via the system catalogs
+pub struct ExtensionsClient {
+    db_url: String,
+}
+
+impl ExtensionsClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// List installed extensions with their schema and version, and whether
+    /// anything outside the extension depends on one of its objects
+    pub fn list_installed(&self) -> Result<Vec<Extension>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'name', e.extname,
+                        'schema', n.nspname,
+                        'version', e.extversion,
+                        'required', EXISTS (
+                            SELECT 1
+                            FROM pg_depend member
+                            JOIN pg_depend dep
+                              ON dep.refclassid = member.classid
+                             AND dep.refobjid = member.objid
+                            WHERE member.refclassid = 'pg_extension'::regclass
+                              AND member.refobjid = e.oid
+                              AND member.deptype = 'e'
+                              AND dep.deptype IN ('n', 'a')
+                              AND dep.classid IN (
+                                  'pg_class'::regclass, 'pg_attrdef'::regclass,
+                                  'pg_constraint'::regclass, 'pg_trigger'::regclass,
+                                  'pg_proc'::regclass, 'pg_type'::regclass,
+                                  'pg_rewrite'::regclass, 'pg_policy'::regclass
+                              )
+                              AND NOT EXISTS (
+                                  SELECT 1
+                                  FROM pg_depend own
+                                  WHERE own.classid = dep.classid
+                                    AND own.objid = dep.objid
+                                    AND own.deptype = 'e'
+                              )
+                        )
+                    )
+                    ORDER BY e.extname
+                ),
+                '[]'::json
+            )::text
+            FROM pg_extension e
+            JOIN pg_namespace n ON n.oid = e.extnamespace
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// List extensions the server can install
+    pub fn list_available(&self) -> Result<Vec<AvailableExtension>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'name', name,
+                        'default_version', default_version,
+                        'installed_version', installed_version
+                    )
+                    ORDER BY name
+                ),
+                '[]'::json
+            )::text
+            FROM pg_available_extensions
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// Compare the given source extensions against this (target) database
+    pub fn plan(&self, source: &[Extension]) -> Result<ExtensionPlan> {
+        let available = self.list_available()?;
+        Ok(plan_extensions(source, &available))
+    }
+
+    /// Enable an extension in the same schema it uses on the source
+    pub fn enable(&self, extension: &Extension) -> Result<()> {
+        let schema = quote_ident(&extension.schema);
+        let sql = format!(
+            "CREATE SCHEMA IF NOT EXISTS {schema}; CREATE EXTENSION IF NOT EXISTS {} WITH SCHEMA {schema} CASCADE",
+            quote_ident(&extension.name)
+        );
+        debug!(
+            "Enabling extension {} in schema {}",
+            extension.name, extension.schema
+        );
+        psql::query(&self.db_url, &sql)?;
+        Ok(())
+    }
+
+    /// Postgres major version of the server, if it can be detected
+    pub fn server_version(&self) -> Option<u32> {
+        get_server_version(&self.db_url)
+    }
+}
+
+/// Split source extensions into those to enable, already installed and unavailable
+pub fn plan_extensions(source: &[Extension], available: &[AvailableExtension]) -> ExtensionPlan {
+    let mut plan = ExtensionPlan::default();
+
+    for extension in source {
+        match available.iter().find(|a| a.name == extension.name) {
+            Some(a) if a.installed_version.is_some() => plan.installed.push(extension.clone()),
+            Some(_) => plan.to_enable.push(extension.clone()),
+            None => plan.unavailable.push(extension.clone()),
+        }
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extension(name: &str, schema: &str) -> Extension {
+        Extension {
+            name: name.to_string(),
+            schema: schema.to_string(),
+            version: "1.0".to_string(),
+            required: false,
+        }
+    }
+
+    fn available(name: &str, installed: bool) -> AvailableExtension {
+        AvailableExtension {
+            name: name.to_string(),
+            default_version: Some("1.0".to_string()),
+            installed_version: installed.then(|| "1.0".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_plan_extensions() {
+        let source = vec![
+            extension("plpgsql", "pg_catalog"),
+            extension("vector", "extensions"),
+            extension("postgis", "extensions"),
+            extension("timescaledb", "public"),
+        ];
+        let target = vec![
+            available("plpgsql", true),
+            available("vector", false),
+            available("postgis", false),
+        ];
+
+        let plan = plan_extensions(&source, &target);
+        assert_eq!(plan.installed, vec![extension("plpgsql", "pg_catalog")]);
+        assert_eq!(
+            plan.to_enable,
+            vec![
+                extension("vector", "extensions"),
+                extension("postgis", "extensions")
+            ]
+        );
+        assert_eq!(plan.unavailable, vec![extension("timescaledb", "public")]);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_list_installed_required() {
+        let db_url = std::env::var("SUPAMIGRATE_TEST_DB_URL").unwrap();
+        psql::query(
+            &db_url,
+            "DROP TABLE IF EXISTS extension_test; \
+             DROP EXTENSION IF EXISTS citext; DROP EXTENSION IF EXISTS hstore; \
+             CREATE EXTENSION citext; CREATE EXTENSION hstore; \
+             CREATE TABLE extension_test (email citext)",
+        )
+        .unwrap();
+
+        let installed = ExtensionsClient::new(db_url.clone())
+            .list_installed()
+            .unwrap();
+        let required = |name: &str| installed.iter().find(|e| e.name == name).unwrap().required;
+        assert!(required("citext"));
+        assert!(!required("hstore"));
+
+        psql::query(
+            &db_url,
+            "DROP TABLE extension_test; DROP EXTENSION citext; DROP EXTENSION hstore",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_extension_required_by_default() {
+        let extension: Extension = serde_json::from_str(
+            r#"{"name": "vector", "schema": "extensions", "version": "0.7.0"}"#,
+        )
+        .unwrap();
+        assert!(extension.required);
+    }
+}
@@ -0,0 +1,584 @@
+use crate::db::psql::quote_ident;
+use crate::db::toc::parse_header;
+use std::collections::BTreeSet;
+
+/// Selects entries of a plain-format `pg_dump` by table and schema, for restoring
+/// part of a backup
+#[derive(Debug, Default)]
+pub struct DumpFilter {
+    tables: Vec<String>,
+    schemas: Vec<String>,
+    data_only: bool,
+    replace: bool,
+    rename: Option<String>,
+}
+
+/// SQL left after filtering, with the requested tables that are not in the dump
+#[derive(Debug)]
+pub struct FilteredDump {
+    pub sql: String,
+    pub entries: usize,
+    pub missing_tables: Vec<String>,
+}
+
+/// One `-- Name: ...` entry of a dump and the lines up to the next one
+struct Entry<'a> {
+    kind: String,
+    schema: Option<String>,
+    name: String,
+    lines: Vec<&'a str>,
+}
+
+type Table = (String, String);
+
+/// Schema (`None` for schemas and extensions) and name of an object, with
+/// table-scoped objects named `table object` as in dump headers
+type Object = (Option<String>, String);
+
+impl DumpFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only keep these tables (`table` or `schema.table`) with their defaults,
+    /// constraints, indexes, triggers, policies and owned sequences
+    pub fn tables(mut self, tables: Vec<String>) -> Self {
+        self.tables = tables;
+        self
+    }
+
+    /// Only keep the objects in these schemas
+    pub fn schemas(mut self, schemas: Vec<String>) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    /// Only keep table data and sequence values
+    pub fn data_only(mut self, value: bool) -> Self {
+        self.data_only = value;
+        self
+    }
+
+    /// Keep the `--clean` DROP statements of the selected objects, so live tables
+    /// are dropped and recreated from the backup. Without it a selective restore
+    /// never drops anything.
+    pub fn replace(mut self, value: bool) -> Self {
+        self.replace = value;
+        self
+    }
+
+    /// Load the selected tables into `name` (`table` or `schema.table`) instead,
+    /// keeping only their definition and data
+    pub fn rename_to(mut self, name: Option<String>) -> Self {
+        self.rename = name;
+        self
+    }
+
+    /// Whether the filter keeps the whole dump
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+            && self.schemas.is_empty()
+            && !self.data_only
+            && self.rename.is_none()
+    }
+
+    pub fn apply(&self, sql: &str) -> FilteredDump {
+        let (preamble, drops, entries) = split_entries(sql);
+
+        let found: BTreeSet<Table> = entries
+            .iter()
+            .filter(|e| e.kind == "TABLE" || e.kind == "TABLE DATA")
+            .filter_map(|e| Some((e.schema.clone()?, e.name.clone())))
+            .filter(|(schema, table)| self.selects_table(schema, table))
+            .collect();
+        // Sequences owned by (or the identity of) a selected table
+        let sequences: BTreeSet<Table> = entries
+            .iter()
+            .filter(|e| e.kind == "SEQUENCE" || e.kind == "SEQUENCE OWNED BY")
+            .filter(|e| found.iter().any(|table| e.references(table)))
+            .filter_map(|e| Some((e.schema.clone()?, e.name.clone())))
+            .collect();
+
+        let kept: Vec<&Entry> = entries
+            .iter()
+            .filter(|e| self.selects(e, &found, &sequences))
+            .filter(|e| !self.data_only || e.kind == "TABLE DATA" || e.kind == "SEQUENCE SET")
+            .collect();
+
+        // `--clean` drops every object up front; only undo objects that are recreated,
+        // and for a selection only when asked to replace it
+        let recreated: BTreeSet<Object> = kept.iter().filter_map(|e| e.object()).collect();
+        let drop_selected = self.rename.is_none()
+            && (self.replace || (self.tables.is_empty() && self.schemas.is_empty()));
+        let mut sql = preamble.join("\n");
+        for line in drops {
+            let keep = drop_selected
+                && dropped_object(line).is_some_and(|object| recreated.contains(&object));
+            if keep {
+                sql.push('\n');
+                sql.push_str(line);
+            }
+        }
+
+        let mut count = 0;
+        for entry in kept {
+            let lines = match &self.rename {
+                Some(rename) if entry.kind == "TABLE" || entry.kind == "TABLE DATA" => {
+                    entry.renamed(rename)
+                }
+                Some(_) => continue,
+                None => entry.lines.iter().map(|l| l.to_string()).collect(),
+            };
+            sql.push('\n');
+            sql.push_str(&lines.join("\n"));
+            count += 1;
+        }
+        sql.push('\n');
+
+        let missing_tables = self
+            .tables
+            .iter()
+            .filter(|t| {
+                !found
+                    .iter()
+                    .any(|(schema, table)| *t == table || **t == format!("{}.{}", schema, table))
+            })
+            .cloned()
+            .collect();
+
+        FilteredDump {
+            sql,
+            entries: count,
+            missing_tables,
+        }
+    }
+
+    fn selects_table(&self, schema: &str, table: &str) -> bool {
+        let qualified = format!("{}.{}", schema, table);
+        self.tables.iter().any(|t| t == table || *t == qualified)
+    }
+
+    fn selects(&self, entry: &Entry, found: &BTreeSet<Table>, sequences: &BTreeSet<Table>) -> bool {
+        if self.tables.is_empty() && self.schemas.is_empty() {
+            return true;
+        }
+
+        let in_schema = match &entry.schema {
+            Some(schema) => self.schemas.contains(schema),
+            // The schema itself, and its comment and privileges
+            None => {
+                let name = entry.name.strip_prefix("SCHEMA ").unwrap_or(&entry.name);
+                entry.kind != "EXTENSION" && self.schemas.iter().any(|s| s == name)
+            }
+        };
+        if in_schema {
+            return true;
+        }
+
+        let Some(schema) = entry.schema.clone() else {
+            return false;
+        };
+        match entry.kind.as_str() {
+            "SEQUENCE" | "SEQUENCE OWNED BY" | "SEQUENCE SET" => {
+                sequences.contains(&(schema, entry.name.clone()))
+            }
+            "INDEX" => found.iter().any(|table| entry.references(table)),
+            _ => entry
+                .table()
+                .is_some_and(|table| found.contains(&(schema, table.to_string()))),
+        }
+    }
+}
+
+impl Entry<'_> {
+    /// Object the entry creates, matching what `--clean` drops for it; `None` for
+    /// entries that only load or annotate an object
+    fn object(&self) -> Option<Object> {
+        match self.kind.as_str() {
+            "TABLE DATA" | "SEQUENCE SET" | "SEQUENCE OWNED BY" | "COMMENT" | "ACL"
+            | "DEFAULT ACL" => None,
+            _ => {
+                let name = self.name.split('(').next().unwrap_or(&self.name);
+                Some((self.schema.clone(), name.to_string()))
+            }
+        }
+    }
+
+    /// Table an entry belongs to, from its name: `orders` for `orders`,
+    /// `orders orders_pkey`, `TABLE orders` and `COLUMN orders.id`
+    fn table(&self) -> Option<&str> {
+        match self.kind.as_str() {
+            "TABLE" | "TABLE DATA" | "ROW SECURITY" => Some(&self.name),
+            "DEFAULT" | "CONSTRAINT" | "FK CONSTRAINT" | "TRIGGER" | "POLICY" | "RULE" => {
+                self.name.split(' ').next()
+            }
+            "COMMENT" | "ACL" => {
+                if let Some(table) = self.name.strip_prefix("TABLE ") {
+                    Some(table)
+                } else {
+                    self.name
+                        .strip_prefix("COLUMN ")?
+                        .rsplit_once('.')
+                        .map(|(t, _)| t)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the statements of the entry refer to `table`, as the indexes and
+    /// sequences of a table do
+    fn references(&self, (schema, table): &Table) -> bool {
+        names(schema, table).iter().any(|name| {
+            let patterns = [
+                format!("OWNED BY {}.", name),
+                format!(" ON {} ", name),
+                format!(" ON ONLY {} ", name),
+                format!("ALTER TABLE {} ", name),
+                format!("ALTER TABLE ONLY {} ", name),
+            ];
+            self.lines
+                .iter()
+                .any(|line| patterns.iter().any(|p| line.contains(p.as_str())))
+        })
+    }
+
+    /// Lines with the statements creating, altering and loading the table pointed at `rename`
+    fn renamed(&self, rename: &str) -> Vec<String> {
+        let schema = self.schema.as_deref().unwrap_or("public");
+        let (new_schema, new_table) = rename.split_once('.').unwrap_or((schema, rename));
+        let target = format!("{}.{}", quote_ident(new_schema), quote_ident(new_table));
+        let names = names(schema, &self.name);
+
+        self.lines
+            .iter()
+            .map(|line| {
+                for prefix in [
+                    "CREATE TABLE ",
+                    "ALTER TABLE ONLY ",
+                    "ALTER TABLE ",
+                    "COPY ",
+                ] {
+                    for name in &names {
+                        let head = format!("{}{} ", prefix, name);
+                        if let Some(rest) = line.strip_prefix(&head) {
+                            return format!("{}{} {}", prefix, target, rest);
+                        }
+                    }
+                }
+                line.to_string()
+            })
+            .collect()
+    }
+}
+
+/// Quoted and unquoted ways pg_dump writes a table name
+fn names(schema: &str, table: &str) -> [String; 2] {
+    [
+        format!("{}.{}", quote_ident(schema), quote_ident(table)),
+        format!("{}.{}", schema, table),
+    ]
+}
+
+/// Lines before the first entry (session settings), the `--clean` statements among
+/// them, and the entries of a dump
+fn split_entries(sql: &str) -> (Vec<&str>, Vec<&str>, Vec<Entry<'_>>) {
+    let mut preamble = Vec::new();
+    let mut drops = Vec::new();
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for line in sql.lines() {
+        if let Some((_, kind, schema, name)) = parse_header(line) {
+            // The `--` line above the header belongs to the new entry
+            let previous = match entries.last_mut() {
+                Some(entry) => &mut entry.lines,
+                None => &mut preamble,
+            };
+            if previous.last() == Some(&"--") {
+                previous.pop();
+            }
+            entries.push(Entry {
+                kind,
+                schema,
+                name,
+                lines: vec!["--", line],
+            });
+        } else if let Some(entry) = entries.last_mut() {
+            entry.lines.push(line);
+        } else if line.starts_with("DROP ")
+            || (line.starts_with("ALTER ") && line.contains(" DROP "))
+        {
+            drops.push(line);
+        } else {
+            preamble.push(line);
+        }
+    }
+
+    (preamble, drops, entries)
+}
+
+/// Object removed by one of the statements `pg_dump --clean --if-exists` writes:
+/// `DROP <kind> IF EXISTS name [ON table]`, or `ALTER TABLE IF EXISTS [ONLY] table`
+/// dropping a constraint or a column default
+fn dropped_object(line: &str) -> Option<Object> {
+    let line = line.trim_end().trim_end_matches(';');
+
+    if let Some(rest) = line.strip_prefix("ALTER TABLE IF EXISTS ") {
+        let rest = rest.strip_prefix("ONLY ").unwrap_or(rest);
+        let ((schema, table), rest) = qualified_name(rest)?;
+        let rest = rest.trim_start();
+        if let Some(constraint) = rest.strip_prefix("DROP CONSTRAINT IF EXISTS ") {
+            let (constraint, _) = identifier(constraint)?;
+            return Some((schema, format!("{} {}", table, constraint)));
+        }
+        let (column, rest) = identifier(rest.strip_prefix("ALTER COLUMN ")?)?;
+        return (rest.trim() == "DROP DEFAULT").then(|| (schema, format!("{} {}", table, column)));
+    }
+
+    let (_, rest) = line.strip_prefix("DROP ")?.split_once(" IF EXISTS ")?;
+    let ((schema, name), rest) = qualified_name(rest)?;
+    match rest.trim_start().strip_prefix("ON ") {
+        // Triggers, policies and rules are named after their table
+        Some(table) => {
+            let ((schema, table), _) = qualified_name(table)?;
+            Some((schema, format!("{} {}", table, name)))
+        }
+        None => Some((schema, name)),
+    }
+}
+
+/// `schema.name` or `name`, quoted or not, and the text after it
+fn qualified_name(s: &str) -> Option<(Object, &str)> {
+    let (first, rest) = identifier(s)?;
+    match rest.strip_prefix('.') {
+        Some(rest) => {
+            let (second, rest) = identifier(rest)?;
+            Some(((Some(first), second), rest))
+        }
+        None => Some(((None, first), rest)),
+    }
+}
+
+fn identifier(s: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        let mut name = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c != '"' {
+                name.push(c);
+            } else if quoted[i + 1..].starts_with('"') {
+                name.push('"');
+                chars.next();
+            } else {
+                return Some((name, &quoted[i + 1..]));
+            }
+        }
+        return None;
+    }
+    let end = s
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(s.len());
+    (end > 0).then(|| (s[..end].to_string(), &s[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = r#"SET statement_timeout = 0;
+
+ALTER TABLE IF EXISTS ONLY "public"."orders" DROP CONSTRAINT IF EXISTS "orders_pkey";
+DROP INDEX IF EXISTS "public"."users_id_idx";
+DROP INDEX IF EXISTS "public"."orders_total_idx";
+ALTER TABLE IF EXISTS "public"."orders" ALTER COLUMN "id" DROP DEFAULT;
+DROP TABLE IF EXISTS "public"."users";
+DROP SEQUENCE IF EXISTS "public"."orders_id_seq";
+DROP TABLE IF EXISTS "public"."orders";
+DROP TABLE IF EXISTS "archive"."old_orders";
+DROP SCHEMA IF EXISTS "archive";
+
+--
+-- Name: archive; Type: SCHEMA; Schema: -; Owner: -
+--
+
+CREATE SCHEMA archive;
+
+--
+-- Name: orders; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.orders (
+    id bigint NOT NULL,
+    total numeric
+);
+
+ALTER TABLE public.orders OWNER TO postgres;
+
+--
+-- Name: orders_id_seq; Type: SEQUENCE; Schema: public; Owner: -
+--
+
+CREATE SEQUENCE public.orders_id_seq;
+
+--
+-- Name: orders_id_seq; Type: SEQUENCE OWNED BY; Schema: public; Owner: -
+--
+
+ALTER SEQUENCE public.orders_id_seq OWNED BY public.orders.id;
+
+--
+-- Name: users; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.users (id bigint);
+
+--
+-- Name: old_orders; Type: TABLE; Schema: archive; Owner: -
+--
+
+CREATE TABLE archive.old_orders (id bigint);
+
+--
+-- Data for Name: orders; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+COPY public.orders (id, total) FROM stdin;
+1	9.99
+\.
+
+--
+-- Data for Name: users; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+COPY public.users (id) FROM stdin;
+1
+\.
+
+--
+-- Name: orders_id_seq; Type: SEQUENCE SET; Schema: public; Owner: -
+--
+
+SELECT pg_catalog.setval('public.orders_id_seq', 1, true);
+
+--
+-- Name: orders orders_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.orders
+    ADD CONSTRAINT orders_pkey PRIMARY KEY (id);
+
+--
+-- Name: orders_total_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX orders_total_idx ON public.orders USING btree (total);
+
+--
+-- Name: users_id_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX users_id_idx ON public.users USING btree (id);
+"#;
+
+    fn drops(sql: &str) -> Vec<&str> {
+        sql.lines()
+            .filter(|l| l.starts_with("DROP ") || l.contains(" DROP "))
+            .collect()
+    }
+
+    fn kinds(sql: &str) -> Vec<String> {
+        sql.lines()
+            .filter_map(parse_header)
+            .map(|(_, kind, schema, name)| {
+                format!("{} {}.{}", kind, schema.unwrap_or_default(), name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_tables() {
+        let filtered = DumpFilter::new()
+            .tables(vec!["orders".to_string(), "public.missing".to_string()])
+            .replace(true)
+            .apply(DUMP);
+        assert!(filtered.sql.starts_with("SET statement_timeout = 0;"));
+        assert_eq!(
+            kinds(&filtered.sql),
+            vec![
+                "TABLE public.orders",
+                "SEQUENCE public.orders_id_seq",
+                "SEQUENCE OWNED BY public.orders_id_seq",
+                "TABLE DATA public.orders",
+                "SEQUENCE SET public.orders_id_seq",
+                "CONSTRAINT public.orders orders_pkey",
+                "INDEX public.orders_total_idx",
+            ]
+        );
+        assert_eq!(filtered.missing_tables, vec!["public.missing"]);
+        assert_eq!(
+            drops(&filtered.sql),
+            vec![
+                r#"ALTER TABLE IF EXISTS ONLY "public"."orders" DROP CONSTRAINT IF EXISTS "orders_pkey";"#,
+                r#"DROP INDEX IF EXISTS "public"."orders_total_idx";"#,
+                r#"DROP SEQUENCE IF EXISTS "public"."orders_id_seq";"#,
+                r#"DROP TABLE IF EXISTS "public"."orders";"#,
+            ]
+        );
+
+        let data = DumpFilter::new()
+            .tables(vec!["public.orders".to_string()])
+            .data_only(true)
+            .apply(DUMP);
+        assert_eq!(
+            kinds(&data.sql),
+            vec![
+                "TABLE DATA public.orders",
+                "SEQUENCE SET public.orders_id_seq"
+            ]
+        );
+        assert!(drops(&data.sql).is_empty());
+
+        // Live tables are only dropped when asked to
+        let kept = DumpFilter::new()
+            .tables(vec!["orders".to_string()])
+            .apply(DUMP);
+        assert_eq!(kept.entries, filtered.entries);
+        assert!(drops(&kept.sql).is_empty());
+
+        let schema = DumpFilter::new()
+            .schemas(vec!["archive".to_string()])
+            .replace(true)
+            .apply(DUMP);
+        assert_eq!(
+            kinds(&schema.sql),
+            vec!["SCHEMA .archive", "TABLE archive.old_orders"]
+        );
+        assert_eq!(
+            drops(&schema.sql),
+            vec![
+                r#"DROP TABLE IF EXISTS "archive"."old_orders";"#,
+                r#"DROP SCHEMA IF EXISTS "archive";"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_rename() {
+        let filtered = DumpFilter::new()
+            .tables(vec!["orders".to_string()])
+            .rename_to(Some("orders_restored".to_string()))
+            .apply(DUMP);
+        assert_eq!(filtered.entries, 2);
+        assert!(filtered
+            .sql
+            .contains("CREATE TABLE \"public\".\"orders_restored\" (\n    id bigint NOT NULL,"));
+        assert!(filtered
+            .sql
+            .contains("ALTER TABLE \"public\".\"orders_restored\" OWNER TO postgres;"));
+        assert!(filtered
+            .sql
+            .contains("COPY \"public\".\"orders_restored\" (id, total) FROM stdin;\n1\t9.99\n\\."));
+        assert!(!filtered.sql.contains("public.orders"));
+        assert!(drops(&filtered.sql).is_empty());
+    }
+}
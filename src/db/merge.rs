@@ -0,0 +1,368 @@
+use crate::db::psql::{self, quote_ident};
+use crate::db::tables::{TableColumns, TableName, TablesClient};
+use crate::error::{Result, SupamigrateError};
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+const STAGE_TABLE: &str = "\"_supamigrate_stage\"";
+
+/// Row counts for one merged table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeStats {
+    pub source_rows: u64,
+    pub inserted: u64,
+    pub updated: u64,
+    pub deleted: u64,
+}
+
+/// Merges source rows into existing target tables by primary key
+pub struct MergeClient {
+    source_db_url: String,
+    target_db_url: String,
+    disable_triggers: bool,
+}
+
+impl fmt::Display for MergeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} source rows, {} inserted, {} updated, {} deleted",
+            self.source_rows, self.inserted, self.updated, self.deleted
+        )
+    }
+}
+
+impl MergeClient {
+    pub fn new(source_db_url: String, target_db_url: String) -> Self {
+        Self {
+            source_db_url,
+            target_db_url,
+            disable_triggers: false,
+        }
+    }
+
+    /// Run the target session with `session_replication_role = replica`
+    pub fn disable_triggers(mut self, value: bool) -> Self {
+        self.disable_triggers = value;
+        self
+    }
+
+    /// Stage the source rows of a table on the target and upsert them
+    pub fn merge_table(&self, table: &TableName) -> Result<MergeStats> {
+        let target = self.target_columns(table)?;
+
+        // Only copy columns both sides have, in target order
+        let source = TablesClient::new(self.source_db_url.clone()).columns(table)?;
+        let columns: Vec<String> = target
+            .columns
+            .into_iter()
+            .filter(|c| source.columns.contains(c))
+            .collect();
+
+        let select = format!(
+            "SELECT {} FROM {}",
+            quoted_list(&columns),
+            table.qualified_name()
+        );
+        let (before, after) = merge_script(table, &columns, &target.primary_key);
+        let output = self.run_script(&before, &select, &after)?;
+
+        parse_stats(&output).ok_or_else(|| {
+            SupamigrateError::Database(format!(
+                "Unexpected merge output for {}.{}: {}",
+                table.schema, table.table, output
+            ))
+        })
+    }
+
+    /// Delete target rows whose primary key is not present in the source. Run it
+    /// after every table is merged, referencing tables before the tables they
+    /// reference, so no row is deleted while another still points at it.
+    pub fn delete_missing(&self, table: &TableName) -> Result<u64> {
+        let target = self.target_columns(table)?;
+        let select = format!(
+            "SELECT {} FROM {}",
+            quoted_list(&target.primary_key),
+            table.qualified_name()
+        );
+        let (before, after) = delete_script(table, &target.primary_key);
+        let output = self.run_script(&before, &select, &after)?;
+
+        output.parse().map_err(|_| {
+            SupamigrateError::Database(format!(
+                "Unexpected delete output for {}.{}: {}",
+                table.schema, table.table, output
+            ))
+        })
+    }
+
+    fn target_columns(&self, table: &TableName) -> Result<TableColumns> {
+        let target = TablesClient::new(self.target_db_url.clone()).columns(table)?;
+        if target.primary_key.is_empty() {
+            return Err(SupamigrateError::Database(format!(
+                "Table {}.{} has no primary key and cannot be merged",
+                table.schema, table.table
+            )));
+        }
+        Ok(target)
+    }
+
+    /// Run `before`, the source rows of `select` as COPY data, then `after` in one
+    /// target session; rows are piped from the source without buffering the table
+    fn run_script(&self, before: &str, select: &str, after: &str) -> Result<String> {
+        let mut cmd = Command::new("psql");
+        cmd.arg(&self.target_db_url)
+            .arg("-q")
+            .arg("-t")
+            .arg("-A")
+            .arg("-v")
+            .arg("ON_ERROR_STOP=1")
+            .arg("--file")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if self.disable_triggers {
+            cmd.env("PGOPTIONS", psql::replica_pgoptions());
+        }
+
+        let mut child = cmd.spawn()?;
+        let sent = match child.stdin.take() {
+            Some(mut stdin) => (|| -> Result<()> {
+                stdin.write_all(before.as_bytes())?;
+                psql::copy_out(&self.source_db_url, select, &mut stdin)?;
+                stdin.write_all(after.as_bytes())?;
+                Ok(())
+            })(),
+            None => Ok(()),
+        };
+
+        // A target error explains a broken pipe on our side, so report it first.
+        // If the source failed, the script never reaches COMMIT and is rolled back.
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(SupamigrateError::Database(format!(
+                "Merge failed: {}",
+                stderr.trim()
+            )));
+        }
+        sent?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+fn quoted_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| quote_ident(c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// SQL sent before and after the `COPY ... FROM STDIN` data of a table
+fn merge_script(table: &TableName, columns: &[String], primary_key: &[String]) -> (String, String) {
+    let target = table.qualified_name();
+    let column_list = quoted_list(columns);
+    let key_list = quoted_list(primary_key);
+
+    let before = format!(
+        "BEGIN;\n\
+         CREATE TEMP TABLE {STAGE_TABLE} ON COMMIT DROP AS SELECT {column_list} FROM {target} WITH NO DATA;\n\
+         COPY {STAGE_TABLE} ({column_list}) FROM STDIN;\n"
+    );
+
+    let updates: Vec<&String> = columns
+        .iter()
+        .filter(|c| !primary_key.contains(c))
+        .collect();
+    let conflict_action = if updates.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        let set: Vec<String> = updates
+            .iter()
+            .map(|c| format!("{} = EXCLUDED.{}", quote_ident(c), quote_ident(c)))
+            .collect();
+        let current: Vec<String> = updates
+            .iter()
+            .map(|c| format!("t.{}", quote_ident(c)))
+            .collect();
+        let incoming: Vec<String> = updates
+            .iter()
+            .map(|c| format!("EXCLUDED.{}", quote_ident(c)))
+            .collect();
+        // Skip rows that would not change so they don't count as updated
+        format!(
+            "DO UPDATE SET {} WHERE ({}) IS DISTINCT FROM ({})",
+            set.join(", "),
+            current.join(", "),
+            incoming.join(", ")
+        )
+    };
+
+    let after = format!(
+        "\\.\n\
+         SELECT count(*) FROM {STAGE_TABLE};\n\
+         WITH upserted AS (\
+         INSERT INTO {target} AS t ({column_list}) OVERRIDING SYSTEM VALUE \
+         SELECT {column_list} FROM {STAGE_TABLE} \
+         ON CONFLICT ({key_list}) {conflict_action} \
+         RETURNING (xmax = 0) AS inserted\
+         ) SELECT count(*) FILTER (WHERE inserted) || '|' || count(*) FILTER (WHERE NOT inserted) FROM upserted;\n\
+         COMMIT;\n"
+    );
+
+    (before, after)
+}
+
+/// SQL sent before and after the source primary keys of a table, deleting the
+/// target rows that are not among them
+fn delete_script(table: &TableName, primary_key: &[String]) -> (String, String) {
+    let target = table.qualified_name();
+    let key_list = quoted_list(primary_key);
+
+    let before = format!(
+        "BEGIN;\n\
+         CREATE TEMP TABLE {STAGE_TABLE} ON COMMIT DROP AS SELECT {key_list} FROM {target} WITH NO DATA;\n\
+         COPY {STAGE_TABLE} ({key_list}) FROM STDIN;\n"
+    );
+
+    let matches: Vec<String> = primary_key
+        .iter()
+        .map(|c| format!("s.{} = t.{}", quote_ident(c), quote_ident(c)))
+        .collect();
+    let after = format!(
+        "\\.\n\
+         WITH deleted AS (\
+         DELETE FROM {target} AS t WHERE NOT EXISTS (SELECT 1 FROM {STAGE_TABLE} s WHERE {}) \
+         RETURNING 1\
+         ) SELECT count(*) FROM deleted;\n\
+         COMMIT;\n",
+        matches.join(" AND ")
+    );
+
+    (before, after)
+}
+
+/// Parse the `count` and `inserted|updated` result lines
+fn parse_stats(output: &str) -> Option<MergeStats> {
+    let mut lines = output.lines().map(str::trim).filter(|l| !l.is_empty());
+
+    let source_rows = lines.next()?.parse().ok()?;
+    let (inserted, updated) = lines.next()?.split_once('|')?;
+
+    debug!(
+        "Merge output: {} rows, {} inserted, {} updated",
+        source_rows, inserted, updated
+    );
+
+    Some(MergeStats {
+        source_rows,
+        inserted: inserted.parse().ok()?,
+        updated: updated.parse().ok()?,
+        deleted: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_script() {
+        let table = TableName {
+            schema: "public".to_string(),
+            table: "plans".to_string(),
+        };
+        let columns = vec!["id".to_string(), "name".to_string(), "price".to_string()];
+        let primary_key = vec!["id".to_string()];
+
+        let (before, after) = merge_script(&table, &columns, &primary_key);
+
+        assert!(before
+            .ends_with("COPY \"_supamigrate_stage\" (\"id\", \"name\", \"price\") FROM STDIN;\n"));
+        assert!(after.starts_with("\\.\n"));
+        assert!(after.contains(
+            "ON CONFLICT (\"id\") DO UPDATE SET \"name\" = EXCLUDED.\"name\", \"price\" = EXCLUDED.\"price\" \
+             WHERE (t.\"name\", t.\"price\") IS DISTINCT FROM (EXCLUDED.\"name\", EXCLUDED.\"price\")"
+        ));
+        assert!(after.ends_with("COMMIT;\n"));
+        assert!(!after.contains("DELETE"));
+
+        let (_, after) = merge_script(&table, &primary_key, &primary_key);
+        assert!(after.contains("ON CONFLICT (\"id\") DO NOTHING"));
+
+        let (before, after) = delete_script(&table, &primary_key);
+        assert!(before.ends_with("COPY \"_supamigrate_stage\" (\"id\") FROM STDIN;\n"));
+        assert!(after.contains("WHERE s.\"id\" = t.\"id\""));
+        assert!(after.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        assert_eq!(
+            parse_stats("120\n15|3"),
+            Some(MergeStats {
+                source_rows: 120,
+                inserted: 15,
+                updated: 3,
+                deleted: 0,
+            })
+        );
+        assert_eq!(parse_stats("ERROR"), None);
+    }
+
+    /// Needs a disposable server:
+    /// `SUPAMIGRATE_TEST_DB_URL=postgresql://postgres@localhost/postgres cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_merge_delete_missing() {
+        let db_url = std::env::var("SUPAMIGRATE_TEST_DB_URL").unwrap();
+        let (server, _) = db_url.rsplit_once('/').unwrap();
+        let setup = |name: &str, rows: &str| {
+            psql::query(&db_url, &format!("DROP DATABASE IF EXISTS {}", name)).unwrap();
+            psql::query(&db_url, &format!("CREATE DATABASE {}", name)).unwrap();
+            let url = format!("{}/{}", server, name);
+            psql::query(
+                &url,
+                &format!(
+                    "CREATE TABLE users (id int PRIMARY KEY); \
+                     CREATE TABLE orders (id int PRIMARY KEY, user_id int REFERENCES users); {}",
+                    rows
+                ),
+            )
+            .unwrap();
+            url
+        };
+        let source = setup(
+            "merge_source",
+            "INSERT INTO users VALUES (1); INSERT INTO orders VALUES (1, 1)",
+        );
+        let target = setup(
+            "merge_target",
+            "INSERT INTO users VALUES (1), (2); INSERT INTO orders VALUES (2, 2)",
+        );
+
+        let client = MergeClient::new(source, target.clone());
+        let tables = TablesClient::new(target.clone())
+            .load_order(&[], &[])
+            .unwrap();
+        for table in &tables {
+            client.merge_table(table).unwrap();
+        }
+        let deleted: Vec<u64> = tables
+            .iter()
+            .rev()
+            .map(|t| client.delete_missing(t).unwrap())
+            .collect();
+        assert_eq!(deleted, vec![1, 1]);
+        assert_eq!(
+            psql::query(&target, "SELECT string_agg(id::text, ',') FROM users").unwrap(),
+            "1"
+        );
+    }
+}
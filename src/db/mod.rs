@@ -1,9 +1,38 @@
+pub mod cron;
 mod dump;
+mod extensions;
+mod filter;
+mod merge;
+mod psql;
+pub mod publications;
+mod recovery;
+mod replication;
 mod restore;
+mod roles;
+pub mod sequences;
+mod snapshot;
+mod tables;
+mod toc;
 mod transform;
 pub mod vault;
+pub mod verify;
+mod webhooks;
 
+pub use cron::{CronBackup, CronClient};
 pub use dump::PgDump;
+pub use extensions::{Extension, ExtensionsClient};
+pub use filter::DumpFilter;
+pub use merge::MergeClient;
+pub use publications::{PublicationChange, PublicationsClient};
+pub use recovery::RecoveryClient;
+pub use replication::Replication;
 pub use restore::PgRestore;
+pub use roles::{RolesBackup, RolesClient};
+pub use sequences::{SequenceChange, SequencesClient};
+pub use snapshot::{DbSnapshot, SnapshotInfo};
+pub use tables::{TableName, TablesClient};
+pub use toc::DumpToc;
 pub use transform::SqlTransformer;
 pub use vault::{VaultBackup, VaultClient};
+pub use verify::VerifyClient;
+pub use webhooks::{ProjectRewrite, WebhooksClient};
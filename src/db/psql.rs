@@ -0,0 +1,136 @@
+use crate::error::{Result, SupamigrateError};
+use serde::Deserialize;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+/// Execute a SQL query with psql and return the tuples-only output
+pub fn query(db_url: &str, sql: &str) -> Result<String> {
+    let mut cmd = Command::new("psql");
+    cmd.arg(db_url)
+        .arg("-t") // Tuples only (no headers)
+        .arg("-A") // Unaligned output
+        .arg("-v")
+        .arg("ON_ERROR_STOP=1")
+        .arg("-c")
+        .arg(sql)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    debug!("Executing query: {}", sql);
+
+    let output = cmd.output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SupamigrateError::Database(format!(
+            "Query failed: {}",
+            stderr.trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run `COPY (...) TO STDOUT` and stream the raw text-format rows into `out`
+pub fn copy_out(db_url: &str, select: &str, out: &mut impl Write) -> Result<()> {
+    let sql = format!("COPY ({}) TO STDOUT", select);
+    let mut child = Command::new("psql")
+        .arg(db_url)
+        .arg("-v")
+        .arg("ON_ERROR_STOP=1")
+        .arg("-c")
+        .arg(&sql)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Dropping stdout after a failed write ends psql with a broken pipe
+    let copied = child
+        .stdout
+        .take()
+        .map(|mut stdout| io::copy(&mut stdout, out));
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SupamigrateError::Database(format!(
+            "Copy failed: {}",
+            stderr.trim()
+        )));
+    }
+    copied.transpose()?;
+
+    Ok(())
+}
+
+/// `PGOPTIONS` value that runs the session as a replica, so user triggers and
+/// foreign key checks do not fire
+pub fn replica_pgoptions() -> String {
+    let replica = "-c session_replication_role=replica";
+    match std::env::var("PGOPTIONS") {
+        Ok(existing) if !existing.is_empty() => format!("{} {}", existing, replica),
+        _ => replica.to_string(),
+    }
+}
+
+/// Execute a SQL query that returns a single JSON value
+pub fn query_json<T: for<'de> Deserialize<'de>>(db_url: &str, sql: &str) -> Result<T> {
+    let output = query(db_url, sql)?;
+    if output.is_empty() {
+        return Err(SupamigrateError::Database("Empty response".to_string()));
+    }
+    serde_json::from_str(&output).map_err(|e| {
+        SupamigrateError::Database(format!("Failed to parse JSON: {} - Output: {}", e, output))
+    })
+}
+
+/// Execute a SQL query that returns a single JSON value or no rows
+pub fn query_json_optional<T: for<'de> Deserialize<'de>>(
+    db_url: &str,
+    sql: &str,
+) -> Result<Option<T>> {
+    let output = query(db_url, sql)?;
+    if output.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(&output).map(Some).map_err(|e| {
+        SupamigrateError::Database(format!("Failed to parse JSON: {} - Output: {}", e, output))
+    })
+}
+
+/// Check whether an extension is installed in the database
+pub fn has_extension(db_url: &str, name: &str) -> Result<bool> {
+    let sql = format!(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = {})",
+        quote_literal(name)
+    );
+    let result = query(db_url, &sql)?;
+    Ok(result == "t" || result == "true")
+}
+
+/// Quote an identifier for use in generated SQL
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quote a string literal for use in generated SQL
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("orders"), "\"orders\"");
+        assert_eq!(quote_ident("we\"ird"), "\"we\"\"ird\"");
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+}
@@ -0,0 +1,435 @@
+use crate::db::dump::{get_server_version, is_excluded};
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::db::replication::REPLICATION_PREFIX;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A logical replication publication (e.g. `supabase_realtime`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Publication {
+    pub name: String,
+    #[serde(default)]
+    pub all_tables: bool,
+    pub insert: bool,
+    pub update: bool,
+    pub delete: bool,
+    pub truncate: bool,
+    #[serde(default)]
+    pub tables: Vec<PublicationTable>,
+}
+
+/// A table published by a publication, with optional column list and row filter (PG15+)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicationTable {
+    pub schema: String,
+    pub table: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_filter: Option<String>,
+}
+
+/// A change needed to make a target publication match the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicationChange {
+    Create(Publication),
+    /// Drop and create again; whether a publication is `FOR ALL TABLES` cannot be altered
+    Recreate(Publication),
+    SetPublish {
+        publication: String,
+        operations: String,
+    },
+    AddTable {
+        publication: String,
+        table: PublicationTable,
+    },
+    UpdateTable {
+        publication: String,
+        table: PublicationTable,
+    },
+    DropTable {
+        publication: String,
+        table: PublicationTable,
+    },
+}
+
+/// Client for reading and reconciling publications via the system catalogs
+pub struct PublicationsClient {
+    db_url: String,
+}
+
+impl Publication {
+    /// Operations in `publish = '...'` form
+    pub fn operations(&self) -> String {
+        [
+            (self.insert, "insert"),
+            (self.update, "update"),
+            (self.delete, "delete"),
+            (self.truncate, "truncate"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, op)| *op)
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl PublicationTable {
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", quote_ident(&self.schema), quote_ident(&self.table))
+    }
+
+    /// Table spec for `ADD TABLE`, including column list and row filter
+    fn to_sql(&self) -> String {
+        let mut sql = self.qualified_name();
+        if let Some(columns) = &self.columns {
+            let columns: Vec<String> = columns.iter().map(|c| quote_ident(c)).collect();
+            sql.push_str(&format!(" ({})", columns.join(", ")));
+        }
+        if let Some(filter) = &self.row_filter {
+            sql.push_str(&format!(" WHERE ({})", filter));
+        }
+        sql
+    }
+}
+
+impl PublicationChange {
+    pub fn to_sql(&self) -> String {
+        match self {
+            Self::Recreate(publication) => format!(
+                "DROP PUBLICATION IF EXISTS {}; {}",
+                quote_ident(&publication.name),
+                Self::Create(publication.clone()).to_sql()
+            ),
+            Self::Create(publication) => {
+                let target = if publication.all_tables {
+                    " FOR ALL TABLES".to_string()
+                } else if publication.tables.is_empty() {
+                    String::new()
+                } else {
+                    let tables: Vec<String> =
+                        publication.tables.iter().map(PublicationTable::to_sql).collect();
+                    format!(" FOR TABLE {}", tables.join(", "))
+                };
+                format!(
+                    "CREATE PUBLICATION {}{} WITH (publish = {});",
+                    quote_ident(&publication.name),
+                    target,
+                    quote_literal(&publication.operations())
+                )
+            }
+            Self::SetPublish {
+                publication,
+                operations,
+            } => format!(
+                "ALTER PUBLICATION {} SET (publish = {});",
+                quote_ident(publication),
+                quote_literal(operations)
+            ),
+            Self::AddTable { publication, table } => format!(
+                "ALTER PUBLICATION {} ADD TABLE {};",
+                quote_ident(publication),
+                table.to_sql()
+            ),
+            Self::UpdateTable { publication, table } => format!(
+                "ALTER PUBLICATION {pub_name} DROP TABLE {}; ALTER PUBLICATION {pub_name} ADD TABLE {};",
+                table.qualified_name(),
+                table.to_sql(),
+                pub_name = quote_ident(publication)
+            ),
+            Self::DropTable { publication, table } => format!(
+                "ALTER PUBLICATION {} DROP TABLE {};",
+                quote_ident(publication),
+                table.qualified_name()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PublicationChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Create(publication) | Self::Recreate(publication) => write!(
+                f,
+                "{} publication {} ({} tables, publish: {})",
+                if matches!(self, Self::Create(_)) {
+                    "create"
+                } else {
+                    "recreate"
+                },
+                publication.name,
+                if publication.all_tables {
+                    "all".to_string()
+                } else {
+                    publication.tables.len().to_string()
+                },
+                publication.operations()
+            ),
+            Self::SetPublish {
+                publication,
+                operations,
+            } => write!(f, "{}: set publish = '{}'", publication, operations),
+            Self::AddTable { publication, table } => {
+                write!(f, "{}: add {}.{}", publication, table.schema, table.table)
+            }
+            Self::UpdateTable { publication, table } => write!(
+                f,
+                "{}: update {}.{} (columns/row filter)",
+                publication, table.schema, table.table
+            ),
+            Self::DropTable { publication, table } => {
+                write!(f, "{}: drop {}.{}", publication, table.schema, table.table)
+            }
+        }
+    }
+}
+
+impl PublicationsClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// List publications and their tables
+    pub fn list_publications(&self) -> Result<Vec<Publication>> {
+        // Column lists and row filters only exist on PG15+
+        let (columns, row_filter) = if get_server_version(&self.db_url).unwrap_or(0) >= 15 {
+            (
+                "CASE WHEN pr.prattrs IS NULL THEN NULL ELSE (
+                    SELECT json_agg(a.attname ORDER BY a.attnum)
+                    FROM pg_attribute a
+                    WHERE a.attrelid = pr.prrelid AND a.attnum = ANY(pr.prattrs)
+                ) END",
+                "pg_get_expr(pr.prqual, pr.prrelid)",
+            )
+        } else {
+            ("NULL", "NULL")
+        };
+
+        let sql = format!(
+            r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'name', p.pubname,
+                        'all_tables', p.puballtables,
+                        'insert', p.pubinsert,
+                        'update', p.pubupdate,
+                        'delete', p.pubdelete,
+                        'truncate', p.pubtruncate,
+                        'tables', COALESCE((
+                            SELECT json_agg(
+                                json_build_object(
+                                    'schema', n.nspname,
+                                    'table', c.relname,
+                                    'columns', {columns},
+                                    'row_filter', {row_filter}
+                                )
+                                ORDER BY n.nspname, c.relname
+                            )
+                            FROM pg_publication_rel pr
+                            JOIN pg_class c ON c.oid = pr.prrelid
+                            JOIN pg_namespace n ON n.oid = c.relnamespace
+                            WHERE pr.prpubid = p.oid
+                        ), '[]'::json)
+                    )
+                    ORDER BY p.pubname
+                ),
+                '[]'::json
+            )::text
+            FROM pg_publication p
+        "
+        );
+
+        psql::query_json(&self.db_url, &sql)
+    }
+
+    /// Apply changes to this (target) database
+    pub fn apply(&self, changes: &[PublicationChange]) -> Result<()> {
+        for change in changes {
+            psql::query(&self.db_url, &change.to_sql())?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the changes that make `target` publications match `source`
+pub fn diff_publications(source: &[Publication], target: &[Publication]) -> Vec<PublicationChange> {
+    let mut changes = Vec::new();
+
+    for publication in source {
+        let Some(existing) = target.iter().find(|t| t.name == publication.name) else {
+            changes.push(PublicationChange::Create(publication.clone()));
+            continue;
+        };
+
+        if existing.all_tables != publication.all_tables {
+            changes.push(PublicationChange::Recreate(publication.clone()));
+            continue;
+        }
+
+        if existing.operations() != publication.operations() {
+            changes.push(PublicationChange::SetPublish {
+                publication: publication.name.clone(),
+                operations: publication.operations(),
+            });
+        }
+
+        if publication.all_tables {
+            continue;
+        }
+
+        for table in &publication.tables {
+            let current = existing
+                .tables
+                .iter()
+                .find(|t| t.schema == table.schema && t.table == table.table);
+            match current {
+                None => changes.push(PublicationChange::AddTable {
+                    publication: publication.name.clone(),
+                    table: table.clone(),
+                }),
+                Some(current) if current != table => {
+                    changes.push(PublicationChange::UpdateTable {
+                        publication: publication.name.clone(),
+                        table: table.clone(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        for table in &existing.tables {
+            if !publication
+                .tables
+                .iter()
+                .any(|t| t.schema == table.schema && t.table == table.table)
+            {
+                changes.push(PublicationChange::DropTable {
+                    publication: publication.name.clone(),
+                    table: table.clone(),
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+/// Remove tables that are not migrated because of schema or table exclusions, and the
+/// publications `replicate` creates, which only exist for a running replication
+pub fn filter_excluded(
+    publications: &mut Vec<Publication>,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) {
+    publications.retain(|p| !p.name.starts_with(REPLICATION_PREFIX));
+    for publication in publications {
+        publication
+            .tables
+            .retain(|t| !is_excluded(&t.schema, &t.table, excluded_schemas, excluded_tables));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> PublicationTable {
+        PublicationTable {
+            schema: "public".to_string(),
+            table: name.to_string(),
+            columns: None,
+            row_filter: None,
+        }
+    }
+
+    fn publication(name: &str, tables: Vec<PublicationTable>) -> Publication {
+        Publication {
+            name: name.to_string(),
+            all_tables: false,
+            insert: true,
+            update: true,
+            delete: true,
+            truncate: true,
+            tables,
+        }
+    }
+
+    #[test]
+    fn test_diff_publications() {
+        let mut filtered = table("orders");
+        filtered.row_filter = Some("(status = 'open'::text)".to_string());
+
+        let source = vec![
+            publication("supabase_realtime", vec![table("messages"), filtered]),
+            publication("analytics", vec![table("events")]),
+        ];
+        let mut target_realtime =
+            publication("supabase_realtime", vec![table("orders"), table("legacy")]);
+        target_realtime.truncate = false;
+        let target = vec![target_realtime];
+
+        let changes = diff_publications(&source, &target);
+        let sql: Vec<String> = changes.iter().map(PublicationChange::to_sql).collect();
+
+        assert_eq!(
+            sql,
+            vec![
+                "ALTER PUBLICATION \"supabase_realtime\" SET (publish = 'insert, update, delete, truncate');",
+                "ALTER PUBLICATION \"supabase_realtime\" ADD TABLE \"public\".\"messages\";",
+                "ALTER PUBLICATION \"supabase_realtime\" DROP TABLE \"public\".\"orders\"; ALTER PUBLICATION \"supabase_realtime\" ADD TABLE \"public\".\"orders\" WHERE ((status = 'open'::text));",
+                "ALTER PUBLICATION \"supabase_realtime\" DROP TABLE \"public\".\"legacy\";",
+                "CREATE PUBLICATION \"analytics\" FOR TABLE \"public\".\"events\" WITH (publish = 'insert, update, delete, truncate');",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_publications_all_tables() {
+        let mut all = publication("supabase_realtime", vec![]);
+        all.all_tables = true;
+        let all = vec![all];
+        let listed = vec![publication("supabase_realtime", vec![table("messages")])];
+
+        let changes = diff_publications(&all, &listed);
+        assert_eq!(changes, vec![PublicationChange::Recreate(all[0].clone())]);
+        assert_eq!(
+            changes[0].to_sql(),
+            "DROP PUBLICATION IF EXISTS \"supabase_realtime\"; CREATE PUBLICATION \"supabase_realtime\" FOR ALL TABLES WITH (publish = 'insert, update, delete, truncate');"
+        );
+
+        // ALTER PUBLICATION ... DROP TABLE is rejected for FOR ALL TABLES publications
+        let changes = diff_publications(&listed, &all);
+        assert_eq!(
+            changes,
+            vec![PublicationChange::Recreate(listed[0].clone())]
+        );
+        assert_eq!(
+            changes[0].to_sql(),
+            "DROP PUBLICATION IF EXISTS \"supabase_realtime\"; CREATE PUBLICATION \"supabase_realtime\" FOR TABLE \"public\".\"messages\" WITH (publish = 'insert, update, delete, truncate');"
+        );
+    }
+
+    #[test]
+    fn test_filter_excluded() {
+        let mut internal = table("messages");
+        internal.schema = "realtime".to_string();
+        let mut publications = vec![
+            publication(
+                "supabase_realtime",
+                vec![table("orders"), table("audit_log"), internal],
+            ),
+            publication("supamigrate_abcd1234_staging_ref", vec![table("orders")]),
+        ];
+
+        filter_excluded(
+            &mut publications,
+            &["realtime".to_string(), "pg_*".to_string()],
+            &["public.audit_log".to_string()],
+        );
+        assert_eq!(publications.len(), 1);
+        assert_eq!(publications[0].tables, vec![table("orders")]);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::error::{Result, SupamigrateError};
+use serde::Deserialize;
+use tracing::debug;
+
+/// Start of the comment on schemas created by `restore --into-schema`; only schemas
+/// carrying it are dropped by `restore cleanup`
+const RECOVERY_COMMENT: &str = "supamigrate recovery";
+
+/// A schema a backup was restored into
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecoverySchema {
+    pub name: String,
+    /// `supamigrate recovery of <backup>`
+    pub comment: String,
+    pub tables: usize,
+}
+
+/// Client for creating and dropping the schemas backups are restored into
+pub struct RecoveryClient {
+    db_url: String,
+}
+
+impl RecoveryClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// Create an empty schema marked as the recovery of `backup`; an existing schema
+    /// is never reused
+    pub fn create_schema(&self, schema: &str, backup: &str) -> Result<()> {
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = {})",
+            quote_literal(schema)
+        );
+        let exists = psql::query(&self.db_url, &sql)?;
+        if exists == "t" || exists == "true" {
+            return Err(SupamigrateError::Database(format!(
+                "Schema {} already exists in the target",
+                schema
+            )));
+        }
+
+        let sql = format!(
+            "CREATE SCHEMA {schema}; COMMENT ON SCHEMA {schema} IS {comment}",
+            schema = quote_ident(schema),
+            comment = quote_literal(&format!("{} of {}", RECOVERY_COMMENT, backup)),
+        );
+        debug!("Creating recovery schema {}", schema);
+        psql::query(&self.db_url, &sql)?;
+        Ok(())
+    }
+
+    /// Schemas created by `restore --into-schema`, with their table counts
+    pub fn list(&self) -> Result<Vec<RecoverySchema>> {
+        let sql = format!(
+            r"
+            SELECT COALESCE(json_agg(s ORDER BY s.name), '[]')
+            FROM (
+                SELECT
+                    n.nspname AS name,
+                    obj_description(n.oid, 'pg_namespace') AS comment,
+                    (SELECT count(*) FROM pg_class c
+                     WHERE c.relnamespace = n.oid AND c.relkind IN ('r', 'p')) AS tables
+                FROM pg_namespace n
+                WHERE obj_description(n.oid, 'pg_namespace') LIKE {}
+            ) s
+            ",
+            quote_literal(&format!("{}%", RECOVERY_COMMENT))
+        );
+        psql::query_json(&self.db_url, &sql)
+    }
+
+    /// Drop a recovery schema and everything in it; other schemas are refused
+    pub fn drop_schema(&self, schema: &str) -> Result<()> {
+        if !self.list()?.iter().any(|s| s.name == schema) {
+            return Err(SupamigrateError::Database(format!(
+                "Schema {} was not created by `restore --into-schema`, refusing to drop it",
+                schema
+            )));
+        }
+
+        psql::query(
+            &self.db_url,
+            &format!("DROP SCHEMA {} CASCADE", quote_ident(schema)),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::db::publications::{Publication, PublicationChange, PublicationTable};
+use crate::db::tables::TableName;
+use crate::error::{Result, SupamigrateError};
+use serde::Deserialize;
+use std::fmt;
+use tracing::debug;
+
+/// Prefix of the publications, subscriptions and slots `replicate` creates
+pub const REPLICATION_PREFIX: &str = "supamigrate_";
+
+/// Replication slot on the source, as seen through `pg_replication_slots`
+#[derive(Debug, Clone, Deserialize)]
+struct SlotStatus {
+    active: bool,
+    lag_bytes: Option<i64>,
+    confirmed_flush_lsn: Option<String>,
+}
+
+/// Subscription on the target, as seen through `pg_subscription`
+#[derive(Debug, Clone, Deserialize)]
+struct SubscriptionStatus {
+    enabled: bool,
+    tables: u64,
+    ready: u64,
+}
+
+/// Combined replication state of a source/target pair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationStatus {
+    pub subscription_exists: bool,
+    pub enabled: bool,
+    pub slot_active: bool,
+    /// WAL bytes not yet confirmed by the subscriber
+    pub lag_bytes: Option<i64>,
+    /// Last source WAL position the subscriber confirmed
+    pub confirmed_lsn: Option<u64>,
+    pub tables: u64,
+    pub tables_ready: u64,
+}
+
+/// Logical replication from a source project to a target project.
+/// The publication, subscription and replication slot share one name.
+pub struct Replication {
+    source_db_url: String,
+    target_db_url: String,
+    name: String,
+}
+
+impl ReplicationStatus {
+    /// All tables finished their initial copy
+    pub fn is_synchronized(&self) -> bool {
+        self.subscription_exists && self.tables_ready == self.tables
+    }
+
+    /// All tables finished their initial copy and the subscriber confirmed the
+    /// source WAL up to `lsn`. The source keeps writing WAL of its own even when
+    /// idle, so the lag against its current position may never reach zero.
+    pub fn is_caught_up_to(&self, lsn: u64) -> bool {
+        self.is_synchronized() && self.confirmed_lsn.is_some_and(|confirmed| confirmed >= lsn)
+    }
+}
+
+impl fmt::Display for ReplicationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.subscription_exists {
+            return write!(f, "no subscription");
+        }
+        let lag = self
+            .lag_bytes
+            .map_or_else(|| "unknown".to_string(), |b| format!("{} bytes", b));
+        write!(
+            f,
+            "{}, slot {}, {}/{} tables synchronized, lag {}",
+            if self.enabled { "enabled" } else { "disabled" },
+            if self.slot_active {
+                "active"
+            } else {
+                "inactive"
+            },
+            self.tables_ready,
+            self.tables,
+            lag
+        )
+    }
+}
+
+impl Replication {
+    pub fn new(
+        source_db_url: String,
+        target_db_url: String,
+        source_ref: &str,
+        target_ref: &str,
+    ) -> Self {
+        Self {
+            source_db_url,
+            target_db_url,
+            name: replication_name(source_ref, target_ref),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Tables that cannot publish updates and deletes (no primary key or replica identity)
+    pub fn tables_without_identity(&self, tables: &[TableName]) -> Result<Vec<TableName>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(json_build_object('schema', n.nspname, 'table', c.relname)),
+                '[]'::json
+            )::text
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE c.relkind = 'r'
+              AND (
+                  c.relreplident = 'n'
+                  OR (c.relreplident = 'd' AND NOT EXISTS (
+                      SELECT 1 FROM pg_index i WHERE i.indrelid = c.oid AND i.indisprimary
+                  ))
+              )
+        ";
+
+        let missing: Vec<TableName> = psql::query_json(&self.source_db_url, sql)?;
+        Ok(tables
+            .iter()
+            .filter(|t| missing.contains(t))
+            .cloned()
+            .collect())
+    }
+
+    /// Create the publication on the source for the given tables
+    pub fn create_publication(&self, tables: &[TableName]) -> Result<()> {
+        let publication = Publication {
+            name: self.name.clone(),
+            all_tables: false,
+            insert: true,
+            update: true,
+            delete: true,
+            truncate: true,
+            tables: tables
+                .iter()
+                .map(|t| PublicationTable {
+                    schema: t.schema.clone(),
+                    table: t.table.clone(),
+                    columns: None,
+                    row_filter: None,
+                })
+                .collect(),
+        };
+
+        debug!("Creating publication {} on source", self.name);
+        psql::query(
+            &self.source_db_url,
+            &PublicationChange::Create(publication).to_sql(),
+        )?;
+        Ok(())
+    }
+
+    /// Create the subscription on the target; this also creates the slot on the source
+    /// and starts the initial copy of every published table
+    pub fn create_subscription(&self) -> Result<()> {
+        let sql = format!(
+            "CREATE SUBSCRIPTION {name} CONNECTION {} PUBLICATION {name} WITH (copy_data = true, slot_name = {})",
+            quote_literal(&self.source_db_url),
+            quote_literal(&self.name),
+            name = quote_ident(&self.name)
+        );
+
+        debug!("Creating subscription {} on target", self.name);
+        // Cannot run inside a transaction block, so use a bare psql -c
+        psql::query(&self.target_db_url, &sql)?;
+        Ok(())
+    }
+
+    /// Current WAL position of the source
+    pub fn source_lsn(&self) -> Result<u64> {
+        let lsn = psql::query(&self.source_db_url, "SELECT pg_current_wal_lsn()::text")?;
+        parse_lsn(&lsn)
+            .ok_or_else(|| SupamigrateError::Database(format!("Unexpected WAL position: {}", lsn)))
+    }
+
+    pub fn status(&self) -> Result<ReplicationStatus> {
+        let subscription_sql = format!(
+            r"
+            SELECT json_build_object(
+                'enabled', s.subenabled,
+                'tables', count(r.srrelid),
+                'ready', count(r.srrelid) FILTER (WHERE r.srsubstate = 'r')
+            )::text
+            FROM pg_subscription s
+            LEFT JOIN pg_subscription_rel r ON r.srsubid = s.oid
+            WHERE s.subname = {}
+            GROUP BY s.subenabled
+        ",
+            quote_literal(&self.name)
+        );
+        let slot_sql = format!(
+            r"
+            SELECT json_build_object(
+                'active', active,
+                'lag_bytes', pg_wal_lsn_diff(pg_current_wal_lsn(), confirmed_flush_lsn)::bigint,
+                'confirmed_flush_lsn', confirmed_flush_lsn::text
+            )::text
+            FROM pg_replication_slots
+            WHERE slot_name = {}
+        ",
+            quote_literal(&self.name)
+        );
+
+        let subscription = psql::query_json_optional::<SubscriptionStatus>(
+            &self.target_db_url,
+            &subscription_sql,
+        )?;
+        let slot = psql::query_json_optional::<SlotStatus>(&self.source_db_url, &slot_sql)?;
+
+        Ok(ReplicationStatus {
+            subscription_exists: subscription.is_some(),
+            enabled: subscription.as_ref().is_some_and(|s| s.enabled),
+            slot_active: slot.as_ref().is_some_and(|s| s.active),
+            lag_bytes: slot.as_ref().and_then(|s| s.lag_bytes),
+            confirmed_lsn: slot
+                .as_ref()
+                .and_then(|s| s.confirmed_flush_lsn.as_deref())
+                .and_then(parse_lsn),
+            tables: subscription.as_ref().map_or(0, |s| s.tables),
+            tables_ready: subscription.as_ref().map_or(0, |s| s.ready),
+        })
+    }
+
+    /// Drop the subscription (and with it the source slot), then the publication
+    pub fn drop(&self) -> Result<()> {
+        psql::query(
+            &self.target_db_url,
+            &format!("DROP SUBSCRIPTION IF EXISTS {}", quote_ident(&self.name)),
+        )?;
+        psql::query(
+            &self.source_db_url,
+            &format!("DROP PUBLICATION IF EXISTS {}", quote_ident(&self.name)),
+        )?;
+        Ok(())
+    }
+}
+
+/// Parse a `pg_lsn` in its `XXXXXXXX/XXXXXXXX` text form
+fn parse_lsn(lsn: &str) -> Option<u64> {
+    let (high, low) = lsn.trim().split_once('/')?;
+    let high = u64::from_str_radix(high, 16).ok()?;
+    let low = u64::from_str_radix(low, 16).ok()?;
+    Some((high << 32) | low)
+}
+
+/// Publication, subscription and slot name (lowercase letters, digits and underscores)
+fn replication_name(source_ref: &str, target_ref: &str) -> String {
+    format!("{}{}_{}", REPLICATION_PREFIX, source_ref, target_ref)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_name() {
+        assert_eq!(
+            replication_name("abcd1234", "Staging-Ref"),
+            "supamigrate_abcd1234_staging_ref"
+        );
+    }
+
+    #[test]
+    fn test_parse_lsn() {
+        assert_eq!(parse_lsn("0/16B3748"), Some(0x16B_3748));
+        assert_eq!(parse_lsn("1/0\n"), Some(1 << 32));
+        assert_eq!(parse_lsn("16B3748"), None);
+    }
+
+    #[test]
+    fn test_is_caught_up_to() {
+        let mut status = ReplicationStatus {
+            subscription_exists: true,
+            enabled: true,
+            slot_active: true,
+            lag_bytes: Some(96),
+            confirmed_lsn: Some(0x1500),
+            tables: 4,
+            tables_ready: 3,
+        };
+        assert!(!status.is_caught_up_to(0x1500));
+
+        // WAL written after the cutover position does not hold it back
+        status.tables_ready = 4;
+        assert!(status.is_caught_up_to(0x1500));
+        assert!(status.is_caught_up_to(0x1400));
+        assert!(!status.is_caught_up_to(0x1600));
+
+        status.confirmed_lsn = None;
+        assert!(!status.is_caught_up_to(0x1400));
+    }
+}
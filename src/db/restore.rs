@@ -1,3 +1,4 @@
+use crate::db::psql;
 use crate::error::{Result, SupamigrateError};
 use std::path::Path;
 use std::process::{Command, Stdio};
@@ -5,11 +6,63 @@ use tracing::{debug, info};
 
 pub struct PgRestore {
     db_url: String,
+    disable_triggers: bool,
+    defer_constraints: bool,
+    atomic: bool,
 }
 
 impl PgRestore {
     pub fn new(db_url: String) -> Self {
-        Self { db_url }
+        Self {
+            db_url,
+            disable_triggers: false,
+            defer_constraints: false,
+            atomic: false,
+        }
+    }
+
+    /// Run with `session_replication_role = replica`, so user triggers and
+    /// foreign key checks do not fire while loading data
+    pub fn disable_triggers(mut self, value: bool) -> Self {
+        self.disable_triggers = value;
+        self
+    }
+
+    /// Run in a single transaction with deferrable constraints checked at commit.
+    /// Only constraints declared `DEFERRABLE` are deferred; foreign keys are
+    /// `NOT DEFERRABLE` unless created otherwise and are still checked per row, so
+    /// loading them needs referenced tables first or disabled triggers.
+    pub fn defer_constraints(mut self, value: bool) -> Self {
+        self.defer_constraints = value;
+        self
+    }
+
+    /// Stop at the first error and run in a single transaction, so a failed
+    /// restore leaves the target unchanged
+    pub fn atomic(mut self, value: bool) -> Self {
+        self.atomic = value;
+        self
+    }
+
+    /// psql command with the session options for this restore
+    fn psql_command(&self) -> Command {
+        let mut cmd = Command::new("psql");
+        cmd.arg(&self.db_url);
+
+        if self.disable_triggers {
+            cmd.env("PGOPTIONS", psql::replica_pgoptions());
+        }
+
+        if self.atomic || self.defer_constraints {
+            cmd.arg("--single-transaction")
+                .arg("-v")
+                .arg("ON_ERROR_STOP=1");
+        }
+        if self.defer_constraints {
+            cmd.arg("-c").arg("SET CONSTRAINTS ALL DEFERRED");
+        }
+
+        cmd
     }
 
     /// Check if psql is available
@@ -32,9 +85,8 @@ impl PgRestore {
 
         info!("Starting database restore from {}...", input_path.display());
 
-        let mut cmd = Command::new("psql");
-        cmd.arg(&self.db_url)
-            .arg("--file")
+        let mut cmd = self.psql_command();
+        cmd.arg("--file")
             .arg(input_path)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
@@ -65,8 +117,9 @@ impl PgRestore {
 
         info!("Starting database restore...");
 
-        let mut cmd = Command::new("psql");
-        cmd.arg(&self.db_url)
+        let mut cmd = self.psql_command();
+        cmd.arg("--file")
+            .arg("-")
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
@@ -0,0 +1,393 @@
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Roles created and managed by Supabase itself (never migrated)
+const MANAGED_ROLES: &[&str] = &[
+    "anon",
+    "authenticated",
+    "authenticator",
+    "dashboard_user",
+    "pgbouncer",
+    "postgres",
+    "service_role",
+    "pgsodium_keyholder",
+    "pgsodium_keyiduser",
+    "pgsodium_keymaker",
+];
+
+/// Role name prefixes reserved by Postgres and Supabase
+const MANAGED_ROLE_PREFIXES: &[&str] = &["pg_", "supabase_"];
+
+/// A custom database role with its attributes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbRole {
+    pub name: String,
+    #[serde(default)]
+    pub login: bool,
+    #[serde(default = "default_inherit")]
+    pub inherit: bool,
+    #[serde(default)]
+    pub create_role: bool,
+    #[serde(default)]
+    pub create_db: bool,
+    #[serde(default)]
+    pub bypass_rls: bool,
+    #[serde(default = "default_connection_limit")]
+    pub connection_limit: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<String>,
+    /// Per-role settings in `name=value` form (e.g. `search_path=app, public`)
+    #[serde(default)]
+    pub settings: Vec<String>,
+}
+
+fn default_inherit() -> bool {
+    true
+}
+
+fn default_connection_limit() -> i32 {
+    -1
+}
+
+/// Membership of `member` in `role` (`GRANT role TO member`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleMembership {
+    pub role: String,
+    pub member: String,
+    #[serde(default)]
+    pub admin_option: bool,
+}
+
+/// Backup structure for custom roles and their memberships
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolesBackup {
+    pub roles: Vec<DbRole>,
+    pub memberships: Vec<RoleMembership>,
+    /// Custom superuser roles, which cannot be created on Supabase
+    #[serde(default)]
+    pub skipped_superusers: Vec<String>,
+    pub exported_at: String,
+}
+
+/// Client for reading and recreating custom roles via the system catalogs
+pub struct RolesClient {
+    db_url: String,
+}
+
+/// Check if a role is created and managed by Supabase or Postgres
+pub fn is_managed_role(name: &str) -> bool {
+    MANAGED_ROLES.contains(&name)
+        || MANAGED_ROLE_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// Whether a membership involves a custom role and can be granted on the target.
+/// `postgres` holds the API roles WITH ADMIN OPTION and creates the custom roles, but
+/// cannot grant to or from superusers (filtered in SQL) or `supabase_*` roles.
+fn is_portable_membership(membership: &RoleMembership) -> bool {
+    let reserved = |name: &str| name.starts_with("supabase_");
+    (!is_managed_role(&membership.role) || !is_managed_role(&membership.member))
+        && !reserved(&membership.role)
+        && !reserved(&membership.member)
+}
+
+impl RolesClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// List custom (non-managed) roles with attributes and per-role settings
+    pub fn list_roles(&self) -> Result<Vec<DbRole>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'name', r.rolname,
+                        'login', r.rolcanlogin,
+                        'inherit', r.rolinherit,
+                        'create_role', r.rolcreaterole,
+                        'create_db', r.rolcreatedb,
+                        'bypass_rls', r.rolbypassrls,
+                        'connection_limit', r.rolconnlimit,
+                        'valid_until', r.rolvaliduntil::text,
+                        'settings', COALESCE((
+                            SELECT json_agg(cfg)
+                            FROM pg_db_role_setting s, unnest(s.setconfig) cfg
+                            WHERE s.setrole = r.oid
+                              AND s.setdatabase IN (
+                                  0,
+                                  (SELECT oid FROM pg_database WHERE datname = current_database())
+                              )
+                        ), '[]'::json)
+                    )
+                    ORDER BY r.rolname
+                ),
+                '[]'::json
+            )::text
+            FROM pg_roles r
+            WHERE NOT r.rolsuper
+        ";
+
+        let roles: Vec<DbRole> = psql::query_json(&self.db_url, sql)?;
+        Ok(roles
+            .into_iter()
+            .filter(|r| !is_managed_role(&r.name))
+            .collect())
+    }
+
+    /// List memberships involving at least one custom role, such as
+    /// `GRANT app_readonly TO authenticator`; grants the target rejects are left out
+    pub fn list_memberships(&self) -> Result<Vec<RoleMembership>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'role', r.rolname,
+                        'member', m.rolname,
+                        'admin_option', am.admin_option
+                    )
+                    ORDER BY r.rolname, m.rolname
+                ),
+                '[]'::json
+            )::text
+            FROM pg_auth_members am
+            JOIN pg_roles r ON r.oid = am.roleid
+            JOIN pg_roles m ON m.oid = am.member
+            WHERE NOT r.rolsuper AND NOT m.rolsuper
+        ";
+
+        let memberships: Vec<RoleMembership> = psql::query_json(&self.db_url, sql)?;
+        Ok(memberships
+            .into_iter()
+            .filter(is_portable_membership)
+            .collect())
+    }
+
+    /// Custom roles that are superusers; they are left out of role backups
+    pub fn list_superusers(&self) -> Result<Vec<String>> {
+        let sql = r"
+            SELECT COALESCE(json_agg(rolname ORDER BY rolname), '[]'::json)::text
+            FROM pg_roles
+            WHERE rolsuper
+        ";
+
+        let names: Vec<String> = psql::query_json(&self.db_url, sql)?;
+        Ok(names
+            .into_iter()
+            .filter(|name| !is_managed_role(name))
+            .collect())
+    }
+
+    /// Backup all custom roles and memberships
+    pub fn backup(&self) -> Result<RolesBackup> {
+        Ok(RolesBackup {
+            roles: self.list_roles()?,
+            memberships: self.list_memberships()?,
+            skipped_superusers: self.list_superusers()?,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+impl RolesBackup {
+    pub fn is_empty(&self) -> bool {
+        self.roles.is_empty() && self.memberships.is_empty()
+    }
+
+    /// Roles that can log in (their passwords cannot be read and are not migrated)
+    pub fn login_roles(&self) -> Vec<&str> {
+        self.roles
+            .iter()
+            .filter(|r| r.login)
+            .map(|r| r.name.as_str())
+            .collect()
+    }
+
+    /// Generate idempotent SQL that recreates the roles and memberships
+    pub fn to_sql(&self) -> String {
+        let mut sql = String::from("-- Custom roles generated by supamigrate\n");
+
+        for role in &self.roles {
+            let name = quote_ident(&role.name);
+            let _ = write!(
+                sql,
+                "DO $$\nBEGIN\n  IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = {}) THEN\n    CREATE ROLE {};\n  END IF;\nEND\n$$;\n",
+                quote_literal(&role.name),
+                name
+            );
+
+            let _ = writeln!(
+                sql,
+                "ALTER ROLE {} WITH {};",
+                name,
+                role_options(role).join(" ")
+            );
+
+            for setting in &role.settings {
+                if let Some((key, value)) = setting.split_once('=') {
+                    let _ = writeln!(
+                        sql,
+                        "ALTER ROLE {} SET {} TO {};",
+                        name,
+                        quote_ident(key),
+                        setting_value(key, value)
+                    );
+                }
+            }
+        }
+
+        for membership in &self.memberships {
+            let _ = writeln!(
+                sql,
+                "GRANT {} TO {}{};",
+                quote_ident(&membership.role),
+                quote_ident(&membership.member),
+                if membership.admin_option {
+                    " WITH ADMIN OPTION"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        sql
+    }
+}
+
+/// Every role attribute in explicit form, so a role that already exists on the
+/// target also loses attributes the source role does not have
+fn role_options(role: &DbRole) -> Vec<String> {
+    let flag = |set: bool, name: &str| {
+        if set {
+            name.to_string()
+        } else {
+            format!("NO{}", name)
+        }
+    };
+    vec![
+        flag(role.login, "LOGIN"),
+        flag(role.inherit, "INHERIT"),
+        flag(role.create_role, "CREATEROLE"),
+        flag(role.create_db, "CREATEDB"),
+        flag(role.bypass_rls, "BYPASSRLS"),
+        format!("CONNECTION LIMIT {}", role.connection_limit),
+        format!(
+            "VALID UNTIL {}",
+            quote_literal(role.valid_until.as_deref().unwrap_or("infinity"))
+        ),
+    ]
+}
+
+/// Quote a role setting value; list settings like search_path keep their elements
+fn setting_value(key: &str, value: &str) -> String {
+    if key == "search_path" {
+        value
+            .split(',')
+            .map(|part| quote_literal(part.trim().trim_matches('"')))
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        quote_literal(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str) -> DbRole {
+        DbRole {
+            name: name.to_string(),
+            login: false,
+            inherit: true,
+            create_role: false,
+            create_db: false,
+            bypass_rls: false,
+            connection_limit: -1,
+            valid_until: None,
+            settings: vec![],
+        }
+    }
+
+    #[test]
+    fn test_is_managed_role() {
+        assert!(is_managed_role("anon"));
+        assert!(is_managed_role("service_role"));
+        assert!(is_managed_role("supabase_admin"));
+        assert!(is_managed_role("pg_read_all_data"));
+        assert!(!is_managed_role("app_readonly"));
+        assert!(!is_managed_role("reporting"));
+    }
+
+    #[test]
+    fn test_is_portable_membership() {
+        let membership = |role: &str, member: &str| RoleMembership {
+            role: role.to_string(),
+            member: member.to_string(),
+            admin_option: false,
+        };
+        // PostgREST switches into custom roles through authenticator
+        assert!(is_portable_membership(&membership(
+            "app_readonly",
+            "authenticator"
+        )));
+        assert!(is_portable_membership(&membership(
+            "authenticated",
+            "reporting"
+        )));
+        assert!(is_portable_membership(&membership(
+            "app_readonly",
+            "reporting"
+        )));
+        assert!(!is_portable_membership(&membership(
+            "anon",
+            "authenticator"
+        )));
+        assert!(!is_portable_membership(&membership(
+            "app_readonly",
+            "supabase_admin"
+        )));
+        assert!(!is_portable_membership(&membership(
+            "supabase_admin",
+            "reporting"
+        )));
+    }
+
+    #[test]
+    fn test_roles_to_sql() {
+        let mut reporting = role("reporting");
+        reporting.login = true;
+        reporting.connection_limit = 5;
+        reporting.settings = vec!["search_path=reporting, public".to_string()];
+
+        let backup = RolesBackup {
+            roles: vec![role("app_readonly"), reporting],
+            memberships: vec![RoleMembership {
+                role: "app_readonly".to_string(),
+                member: "reporting".to_string(),
+                admin_option: false,
+            }],
+            skipped_superusers: vec![],
+            exported_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+
+        let sql = backup.to_sql();
+        assert!(sql.contains("WHERE rolname = 'app_readonly'"));
+        assert!(sql.contains("CREATE ROLE \"app_readonly\";"));
+        // Attributes are explicit so an existing role loses ones the source lacks
+        assert!(sql.contains(
+            "ALTER ROLE \"app_readonly\" WITH NOLOGIN INHERIT NOCREATEROLE NOCREATEDB NOBYPASSRLS CONNECTION LIMIT -1 VALID UNTIL 'infinity';"
+        ));
+        assert!(sql.contains(
+            "ALTER ROLE \"reporting\" WITH LOGIN INHERIT NOCREATEROLE NOCREATEDB NOBYPASSRLS CONNECTION LIMIT 5 VALID UNTIL 'infinity';"
+        ));
+        assert!(
+            sql.contains("ALTER ROLE \"reporting\" SET \"search_path\" TO 'reporting', 'public';")
+        );
+        assert!(sql.contains("GRANT \"app_readonly\" TO \"reporting\";"));
+        assert_eq!(backup.login_roles(), vec!["reporting"]);
+    }
+}
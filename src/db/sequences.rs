@@ -0,0 +1,320 @@
+use crate::db::dump::is_excluded;
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// A sequence and, for serial/identity columns, the column that owns it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub schema: String,
+    pub name: String,
+    /// `None` if the sequence has never been used
+    pub last_value: Option<i64>,
+    /// Negative for sequences that count down
+    #[serde(default = "default_increment")]
+    pub increment_by: i64,
+    pub owner: Option<SequenceOwner>,
+}
+
+fn default_increment() -> i64 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SequenceOwner {
+    pub schema: String,
+    pub table: String,
+    pub column: String,
+}
+
+/// A sequence whose value will be moved on the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceChange {
+    pub schema: String,
+    pub name: String,
+    pub from: Option<i64>,
+    pub to: i64,
+}
+
+/// Client for reading and setting sequence values via SQL
+pub struct SequencesClient {
+    db_url: String,
+}
+
+impl Sequence {
+    fn descending(&self) -> bool {
+        self.increment_by < 0
+    }
+
+    /// The value of `a` and `b` the sequence reaches last
+    fn furthest(&self, a: i64, b: i64) -> i64 {
+        if self.descending() {
+            a.min(b)
+        } else {
+            a.max(b)
+        }
+    }
+
+    /// Change that advances the sequence to `value` in its counting direction; never
+    /// moves it backwards, which would make the next inserts collide with existing rows
+    fn change_to(&self, value: i64) -> Option<SequenceChange> {
+        self.last_value
+            .map_or(true, |last| {
+                last != value && self.furthest(last, value) == value
+            })
+            .then(|| SequenceChange {
+                schema: self.schema.clone(),
+                name: self.name.clone(),
+                from: self.last_value,
+                to: value,
+            })
+    }
+}
+
+impl SequencesClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// List all sequences with their current values and owning columns
+    pub fn list_sequences(&self) -> Result<Vec<Sequence>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'schema', s.schemaname,
+                        'name', s.sequencename,
+                        'last_value', s.last_value,
+                        'increment_by', s.increment_by,
+                        'owner', (
+                            SELECT json_build_object(
+                                'schema', tn.nspname,
+                                'table', t.relname,
+                                'column', a.attname
+                            )
+                            FROM pg_class sc
+                            JOIN pg_namespace sn ON sn.oid = sc.relnamespace
+                            JOIN pg_depend d ON d.objid = sc.oid
+                                AND d.classid = 'pg_class'::regclass
+                                AND d.refclassid = 'pg_class'::regclass
+                                AND d.deptype IN ('a', 'i')
+                            JOIN pg_class t ON t.oid = d.refobjid
+                            JOIN pg_namespace tn ON tn.oid = t.relnamespace
+                            JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = d.refobjsubid
+                            WHERE sn.nspname = s.schemaname AND sc.relname = s.sequencename
+                            LIMIT 1
+                        )
+                    )
+                    ORDER BY s.schemaname, s.sequencename
+                ),
+                '[]'::json
+            )::text
+            FROM pg_sequences s
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// Plan changes that set each sequence to the furthest value of its owning column
+    pub fn plan_from_max(&self, sequences: &[Sequence]) -> Result<Vec<SequenceChange>> {
+        let mut changes = Vec::new();
+        for sequence in sequences {
+            if let Some(bound) = self.column_bound(sequence)? {
+                changes.extend(sequence.change_to(bound));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Furthest value of the column owning `sequence` in its counting direction
+    /// (`max`, or `min` for descending sequences); `None` if it has no owner or
+    /// the table has no rows
+    pub fn column_bound(&self, sequence: &Sequence) -> Result<Option<i64>> {
+        let Some(owner) = &sequence.owner else {
+            return Ok(None);
+        };
+        let sql = format!(
+            "SELECT {}({})::bigint FROM {}.{}",
+            if sequence.descending() { "min" } else { "max" },
+            quote_ident(&owner.column),
+            quote_ident(&owner.schema),
+            quote_ident(&owner.table)
+        );
+        // Empty output means the table has no rows
+        let output = psql::query(&self.db_url, &sql)?;
+        Ok(output.parse::<i64>().ok())
+    }
+
+    /// Set sequence values so the next `nextval` returns the value after `to`
+    pub fn apply(&self, changes: &[SequenceChange]) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let sql: Vec<String> = changes
+            .iter()
+            .map(|change| {
+                let name = format!(
+                    "{}.{}",
+                    quote_ident(&change.schema),
+                    quote_ident(&change.name)
+                );
+                format!(
+                    "SELECT setval({}::regclass, {}, true);",
+                    quote_literal(&name),
+                    change.to
+                )
+            })
+            .collect();
+
+        psql::query(&self.db_url, &sql.join(" "))?;
+        Ok(())
+    }
+}
+
+/// Plan changes that copy `last_value` from matching source sequences, or
+/// the target's `column_bound` when its rows go further
+pub fn plan_from_source(
+    source: &[Sequence],
+    target: &[Sequence],
+    mut column_bound: impl FnMut(&Sequence) -> Result<Option<i64>>,
+) -> Result<Vec<SequenceChange>> {
+    let mut changes = Vec::new();
+    for sequence in target {
+        let source_value = source
+            .iter()
+            .find(|s| s.schema == sequence.schema && s.name == sequence.name)
+            .and_then(|s| s.last_value);
+        let Some(source_value) = source_value else {
+            continue;
+        };
+        let value = match column_bound(sequence)? {
+            Some(bound) => sequence.furthest(source_value, bound),
+            None => source_value,
+        };
+        changes.extend(sequence.change_to(value));
+    }
+    Ok(changes)
+}
+
+/// Remove sequences in excluded schemas or owned by excluded tables
+pub fn filter_excluded(
+    sequences: &mut Vec<Sequence>,
+    excluded_schemas: &[String],
+    excluded_tables: &[String],
+) {
+    sequences.retain(|s| {
+        !is_excluded(&s.schema, &s.name, excluded_schemas, &[])
+            && !s.owner.as_ref().is_some_and(|o| {
+                is_excluded(&o.schema, &o.table, excluded_schemas, excluded_tables)
+            })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(name: &str, last_value: Option<i64>) -> Sequence {
+        Sequence {
+            schema: "public".to_string(),
+            name: name.to_string(),
+            last_value,
+            increment_by: 1,
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_from_source() {
+        let source = vec![
+            sequence("orders_id_seq", Some(480)),
+            sequence("users_id_seq", Some(12)),
+            sequence("unused_seq", None),
+        ];
+        let target = vec![
+            sequence("orders_id_seq", Some(1)),
+            sequence("users_id_seq", Some(12)),
+            sequence("unused_seq", Some(3)),
+            sequence("target_only_seq", Some(5)),
+        ];
+
+        let changes = plan_from_source(&source, &target, |_| Ok(None)).unwrap();
+        assert_eq!(
+            changes,
+            vec![SequenceChange {
+                schema: "public".to_string(),
+                name: "orders_id_seq".to_string(),
+                from: Some(1),
+                to: 480,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_from_source_never_lowers() {
+        let source = vec![
+            sequence("orders_id_seq", Some(480)),
+            sequence("users_id_seq", Some(12)),
+        ];
+        let target = vec![
+            sequence("orders_id_seq", Some(900)),
+            sequence("users_id_seq", Some(3)),
+        ];
+
+        // The target already holds more orders, and users up to id 40
+        let changes = plan_from_source(&source, &target, |s| {
+            Ok((s.name == "users_id_seq").then_some(40))
+        })
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![SequenceChange {
+                schema: "public".to_string(),
+                name: "users_id_seq".to_string(),
+                from: Some(3),
+                to: 40,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_from_source_descending() {
+        let descending = |name: &str, last_value| Sequence {
+            increment_by: -1,
+            ..sequence(name, Some(last_value))
+        };
+        let source = vec![
+            descending("ledger_seq", -480),
+            descending("refunds_seq", -12),
+        ];
+        let target = vec![descending("ledger_seq", -1), descending("refunds_seq", -3)];
+
+        // The target already holds refunds down to -40
+        let changes = plan_from_source(&source, &target, |s| {
+            Ok((s.name == "refunds_seq").then_some(-40))
+        })
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                SequenceChange {
+                    schema: "public".to_string(),
+                    name: "ledger_seq".to_string(),
+                    from: Some(-1),
+                    to: -480,
+                },
+                SequenceChange {
+                    schema: "public".to_string(),
+                    name: "refunds_seq".to_string(),
+                    from: Some(-3),
+                    to: -40,
+                },
+            ]
+        );
+
+        // Never moved back up
+        let changes = plan_from_source(&target, &source, |_| Ok(None)).unwrap();
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::db::psql;
+use crate::error::{Result, SupamigrateError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use tracing::debug;
+
+/// WAL position and time, as `lsn|timestamp`, on primaries and read replicas
+const POSITION: &str = "CASE WHEN pg_is_in_recovery() \
+     THEN pg_last_wal_replay_lsn() ELSE pg_current_wal_lsn() END \
+     || '|' || to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS.US\"Z\"')";
+
+/// Point in time a backup's database dump was read at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub lsn: String,
+    pub timestamp: DateTime<Utc>,
+    /// Whether the dump was read from this exact snapshot, rather than starting
+    /// right after it
+    pub exported: bool,
+}
+
+/// A database snapshot kept alive by a psql session in a repeatable read
+/// transaction, so pg_dump can read the database exactly as of `info`
+pub struct DbSnapshot {
+    session: Option<Child>,
+    /// Snapshot id for `pg_dump --snapshot`
+    pub id: Option<String>,
+    pub info: SnapshotInfo,
+}
+
+impl DbSnapshot {
+    /// Export a snapshot of the database; it stays valid until this value is dropped
+    pub fn export(db_url: &str) -> Result<Self> {
+        let mut session = Command::new("psql")
+            .arg(db_url)
+            .arg("-q")
+            .arg("-t")
+            .arg("-A")
+            .arg("-v")
+            .arg("ON_ERROR_STOP=1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let line = (|| -> Result<String> {
+            let stdin = session.stdin.as_mut().ok_or_else(|| {
+                SupamigrateError::Database("psql session has no stdin".to_string())
+            })?;
+            writeln!(
+                stdin,
+                "BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY;\n\
+                 SELECT pg_export_snapshot() || '|' || {};",
+                POSITION
+            )?;
+            stdin.flush()?;
+
+            let stdout = session.stdout.as_mut().ok_or_else(|| {
+                SupamigrateError::Database("psql session has no stdout".to_string())
+            })?;
+            let mut line = String::new();
+            BufReader::new(stdout).read_line(&mut line)?;
+            Ok(line)
+        })();
+
+        let snapshot = line.and_then(|line| {
+            let (id, position) = line.trim().split_once('|').ok_or_else(|| {
+                SupamigrateError::Database("Failed to export database snapshot".to_string())
+            })?;
+            Ok((id.to_string(), parse_position(position, true)?))
+        });
+
+        match snapshot {
+            Ok((id, info)) => {
+                debug!("Exported snapshot {} at {}", id, info.lsn);
+                Ok(Self {
+                    session: Some(session),
+                    id: Some(id),
+                    info,
+                })
+            }
+            Err(e) => {
+                let _ = session.kill();
+                let output = session.wait_with_output()?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(SupamigrateError::Database(format!(
+                    "{} {}",
+                    e,
+                    stderr.trim()
+                )))
+            }
+        }
+    }
+
+    /// Current WAL position and time, without holding a snapshot
+    pub fn current(db_url: &str) -> Result<Self> {
+        let position = psql::query(db_url, &format!("SELECT {}", POSITION))?;
+        Ok(Self {
+            session: None,
+            id: None,
+            info: parse_position(&position, false)?,
+        })
+    }
+}
+
+impl Drop for DbSnapshot {
+    fn drop(&mut self) {
+        // Closing stdin ends the session and with it the transaction
+        if let Some(mut session) = self.session.take() {
+            drop(session.stdin.take());
+            let _ = session.wait();
+        }
+    }
+}
+
+fn parse_position(position: &str, exported: bool) -> Result<SnapshotInfo> {
+    let (lsn, timestamp) = position.trim().split_once('|').ok_or_else(|| {
+        SupamigrateError::Database(format!("Unexpected WAL position: {}", position))
+    })?;
+    let timestamp = DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|e| SupamigrateError::Database(format!("Invalid snapshot time: {}", e)))?;
+
+    Ok(SnapshotInfo {
+        lsn: lsn.to_string(),
+        timestamp: timestamp.with_timezone(&Utc),
+        exported,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_position() {
+        let info = parse_position("0/1500780|2026-10-16T02:00:00.123456Z\n", true).unwrap();
+        assert_eq!(info.lsn, "0/1500780");
+        assert_eq!(
+            info.timestamp.to_rfc3339(),
+            "2026-10-16T02:00:00.123456+00:00"
+        );
+        assert!(info.exported);
+        assert!(parse_position("0/1500780", false).is_err());
+    }
+
+    /// Needs a disposable database:
+    /// `SUPAMIGRATE_TEST_DB_URL=postgresql://postgres@localhost/postgres cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_dump_from_snapshot() {
+        let db_url = std::env::var("SUPAMIGRATE_TEST_DB_URL").unwrap();
+        psql::query(
+            &db_url,
+            "DROP TABLE IF EXISTS snapshot_test; CREATE TABLE snapshot_test (id int); \
+             INSERT INTO snapshot_test VALUES (1)",
+        )
+        .unwrap();
+
+        let snapshot = DbSnapshot::export(&db_url).unwrap();
+        assert!(snapshot.info.exported);
+        psql::query(&db_url, "INSERT INTO snapshot_test VALUES (2)").unwrap();
+
+        let dump = crate::db::PgDump::new(db_url.clone())
+            .snapshot(snapshot.id.clone())
+            .dump_to_string()
+            .unwrap();
+        drop(snapshot);
+
+        // Dropping the snapshot ends its transaction
+        let open = psql::query(
+            &db_url,
+            "SELECT count(*) FROM pg_stat_activity \
+             WHERE state = 'idle in transaction' AND query LIKE '%pg_export_snapshot()%'",
+        )
+        .unwrap();
+        assert_eq!(open.trim(), "0");
+
+        // Per-table dumps export a snapshot of their own
+        let ordered = crate::db::PgDump::new(db_url.clone())
+            .data_only(true)
+            .table_order(vec!["public.snapshot_test".to_string()])
+            .dump_to_string()
+            .unwrap();
+        psql::query(&db_url, "DROP TABLE snapshot_test").unwrap();
+
+        let toc = crate::db::DumpToc::parse(&dump);
+        assert_eq!(toc.rows.get("public.snapshot_test"), Some(&1));
+        let toc = crate::db::DumpToc::parse(&ordered);
+        assert_eq!(toc.rows.get("public.snapshot_test"), Some(&2));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::db::dump::is_excluded;
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::error::Result;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A schema-qualified table name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct TableName {
+    pub schema: String,
+    pub table: String,
+}
+
+/// A foreign key edge: `table` references `references`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForeignKey {
+    pub table: TableName,
+    pub references: TableName,
+    /// Declared `DEFERRABLE`; only these are affected by `SET CONSTRAINTS ALL DEFERRED`
+    pub deferrable: bool,
+}
+
+/// Insertable (non-generated) columns and primary key of a table
+#[derive(Debug, Clone, Deserialize)]
+pub struct TableColumns {
+    pub columns: Vec<String>,
+    pub primary_key: Vec<String>,
+}
+
+/// Client for reading tables and their foreign key dependencies
+pub struct TablesClient {
+    db_url: String,
+}
+
+impl TableName {
+    /// Quoted `"schema"."table"` form, usable in SQL and pg_dump patterns
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", quote_ident(&self.schema), quote_ident(&self.table))
+    }
+}
+
+impl TablesClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// List ordinary tables (including partitions)
+    pub fn list_tables(&self) -> Result<Vec<TableName>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object('schema', n.nspname, 'table', c.relname)
+                    ORDER BY n.nspname, c.relname
+                ),
+                '[]'::json
+            )::text
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE c.relkind = 'r'
+              AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// List foreign key dependencies between tables
+    pub fn list_foreign_keys(&self) -> Result<Vec<ForeignKey>> {
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'table', json_build_object('schema', n.nspname, 'table', c.relname),
+                        'references', json_build_object('schema', rn.nspname, 'table', rc.relname),
+                        'deferrable', con.condeferrable
+                    )
+                ),
+                '[]'::json
+            )::text
+            FROM pg_constraint con
+            JOIN pg_class c ON c.oid = con.conrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_class rc ON rc.oid = con.confrelid
+            JOIN pg_namespace rn ON rn.oid = rc.relnamespace
+            WHERE con.contype = 'f'
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// Foreign keys of migrated tables that are not `DEFERRABLE`, so deferring
+    /// constraints does not cover them
+    pub fn immediate_foreign_keys(
+        &self,
+        excluded_schemas: &[String],
+        excluded_tables: &[String],
+    ) -> Result<Vec<ForeignKey>> {
+        let mut foreign_keys = self.list_foreign_keys()?;
+        foreign_keys.retain(|fk| {
+            !fk.deferrable
+                && !is_excluded(
+                    &fk.table.schema,
+                    &fk.table.table,
+                    excluded_schemas,
+                    excluded_tables,
+                )
+        });
+        Ok(foreign_keys)
+    }
+
+    /// Columns and primary key of a table
+    pub fn columns(&self, table: &TableName) -> Result<TableColumns> {
+        let relation = quote_literal(&table.qualified_name());
+        let sql = format!(
+            r"
+            SELECT json_build_object(
+                'columns', COALESCE((
+                    SELECT json_agg(a.attname ORDER BY a.attnum)
+                    FROM pg_attribute a
+                    WHERE a.attrelid = {relation}::regclass
+                      AND a.attnum > 0
+                      AND NOT a.attisdropped
+                      AND a.attgenerated = ''
+                ), '[]'::json),
+                'primary_key', COALESCE((
+                    SELECT json_agg(a.attname ORDER BY array_position(i.indkey::int2[], a.attnum))
+                    FROM pg_index i
+                    JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                    WHERE i.indrelid = {relation}::regclass AND i.indisprimary
+                ), '[]'::json)
+            )::text
+        "
+        );
+
+        psql::query_json(&self.db_url, &sql)
+    }
+
+    /// Migrated tables in an order where referenced tables are loaded first
+    pub fn load_order(
+        &self,
+        excluded_schemas: &[String],
+        excluded_tables: &[String],
+    ) -> Result<Vec<TableName>> {
+        let mut tables = self.list_tables()?;
+        tables.retain(|t| !is_excluded(&t.schema, &t.table, excluded_schemas, excluded_tables));
+        let foreign_keys = self.list_foreign_keys()?;
+
+        Ok(topological_order(&tables, &foreign_keys))
+    }
+}
+
+/// Sort tables so that every table comes after the tables it references.
+/// Self-references are ignored; tables in reference cycles are appended at the end.
+pub fn topological_order(tables: &[TableName], foreign_keys: &[ForeignKey]) -> Vec<TableName> {
+    let known: BTreeSet<&TableName> = tables.iter().collect();
+    let mut dependencies: BTreeMap<&TableName, BTreeSet<&TableName>> =
+        tables.iter().map(|t| (t, BTreeSet::new())).collect();
+
+    for fk in foreign_keys {
+        if fk.table != fk.references && known.contains(&fk.references) {
+            if let Some(deps) = dependencies.get_mut(&fk.table) {
+                deps.insert(&fk.references);
+            }
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(tables.len());
+    loop {
+        let ready: Vec<&TableName> = dependencies
+            .iter()
+            .filter(|(_, deps)| deps.is_empty())
+            .map(|(table, _)| *table)
+            .collect();
+        if ready.is_empty() {
+            break;
+        }
+
+        for table in ready {
+            dependencies.remove(table);
+            for deps in dependencies.values_mut() {
+                deps.remove(table);
+            }
+            ordered.push(table.clone());
+        }
+    }
+
+    ordered.extend(dependencies.into_keys().cloned());
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> TableName {
+        TableName {
+            schema: "public".to_string(),
+            table: name.to_string(),
+        }
+    }
+
+    fn fk(from: &str, to: &str) -> ForeignKey {
+        ForeignKey {
+            table: table(from),
+            references: table(to),
+            deferrable: false,
+        }
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let tables = vec![
+            table("a_order_items"),
+            table("b_orders"),
+            table("c_users"),
+            table("d_parent"),
+            table("e_child"),
+        ];
+        let foreign_keys = vec![
+            fk("a_order_items", "b_orders"),
+            fk("b_orders", "c_users"),
+            fk("c_users", "c_users"),
+            fk("b_orders", "auth_users"),
+            fk("d_parent", "e_child"),
+            fk("e_child", "d_parent"),
+        ];
+
+        let names: Vec<String> = topological_order(&tables, &foreign_keys)
+            .into_iter()
+            .map(|t| t.table)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "c_users",
+                "b_orders",
+                "a_order_items",
+                "d_parent",
+                "e_child"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        assert_eq!(table("Orders").qualified_name(), "\"public\".\"Orders\"");
+    }
+}
@@ -0,0 +1,162 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Objects and table row counts of a plain-format `pg_dump`, read from the
+/// `-- Name: ...; Type: ...; Schema: ...` comment that precedes every entry
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DumpToc {
+    /// `"TABLE public.users"` → SHA-256 of its definition
+    pub objects: BTreeMap<String, String>,
+    /// `"public.users"` → rows in its `COPY` block
+    pub rows: BTreeMap<String, u64>,
+}
+
+/// Entry header of a dump: whether it is table data, type, schema and name
+pub(super) fn parse_header(line: &str) -> Option<(bool, String, Option<String>, String)> {
+    let (is_data, rest) = if let Some(rest) = line.strip_prefix("-- Data for Name: ") {
+        (true, rest)
+    } else {
+        (false, line.strip_prefix("-- Name: ")?)
+    };
+
+    let mut parts = rest.split("; ");
+    let name = parts.next()?.to_string();
+    let mut kind = None;
+    let mut schema = None;
+    for part in parts {
+        if let Some(value) = part.strip_prefix("Type: ") {
+            kind = Some(value.to_string());
+        } else if let Some(value) = part.strip_prefix("Schema: ") {
+            schema = Some(value.to_string()).filter(|s| s != "-");
+        }
+    }
+    Some((is_data, kind?, schema, name))
+}
+
+impl DumpToc {
+    pub fn parse(sql: &str) -> Self {
+        let mut toc = Self::default();
+        // Key of the entry being read and its definition so far
+        let mut current: Option<(String, String)> = None;
+        // Table of the last data header, and of the `COPY` block being read
+        let mut data_table: Option<String> = None;
+        let mut copy_table: Option<String> = None;
+
+        for line in sql.lines() {
+            if let Some(table) = &copy_table {
+                if line == "\\." {
+                    copy_table = None;
+                } else {
+                    *toc.rows.entry(table.clone()).or_default() += 1;
+                }
+                continue;
+            }
+
+            if let Some((is_data, kind, schema, name)) = parse_header(line) {
+                toc.finish(current.take());
+                let qualified = match schema {
+                    Some(schema) => format!("{}.{}", schema, name),
+                    None => name,
+                };
+                if is_data {
+                    toc.rows.entry(qualified.clone()).or_default();
+                    data_table = Some(qualified);
+                } else {
+                    current = Some((format!("{} {}", kind, qualified), String::new()));
+                }
+                continue;
+            }
+
+            if line.starts_with("COPY ") && line.ends_with("FROM stdin;") {
+                copy_table = data_table.take();
+                continue;
+            }
+
+            if let Some((_, definition)) = current.as_mut() {
+                if !line.starts_with("--") {
+                    definition.push_str(line);
+                    definition.push('\n');
+                }
+            }
+        }
+        toc.finish(current);
+
+        toc
+    }
+
+    fn finish(&mut self, entry: Option<(String, String)>) {
+        if let Some((key, definition)) = entry {
+            let hash = hex::encode(Sha256::digest(definition.trim().as_bytes()));
+            self.objects.insert(key, hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = r#"--
+-- PostgreSQL database dump
+--
+
+SET statement_timeout = 0;
+
+--
+-- Name: users; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE "public"."users" (
+    "id" bigint NOT NULL,
+    "name" "text"
+);
+
+--
+-- Name: hello(); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION "public"."hello"() RETURNS "text"
+    LANGUAGE "sql"
+    AS $$ SELECT 'hi'; $$;
+
+--
+-- Data for Name: users; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+COPY "public"."users" ("id", "name") FROM stdin;
+1	alice
+2	bob
+\.
+
+--
+-- Name: users users_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY "public"."users"
+    ADD CONSTRAINT "users_pkey" PRIMARY KEY ("id");
+"#;
+
+    #[test]
+    fn test_parse_toc() {
+        let toc = DumpToc::parse(DUMP);
+        assert_eq!(
+            toc.objects.keys().collect::<Vec<_>>(),
+            vec![
+                "CONSTRAINT public.users users_pkey",
+                "FUNCTION public.hello()",
+                "TABLE public.users",
+            ]
+        );
+        assert_eq!(toc.rows.get("public.users"), Some(&2));
+
+        let changed = DumpToc::parse(&DUMP.replace("\"name\" \"text\"", "\"name\" \"varchar\""));
+        assert_ne!(
+            changed.objects["TABLE public.users"],
+            toc.objects["TABLE public.users"]
+        );
+        assert_eq!(
+            changed.objects["FUNCTION public.hello()"],
+            toc.objects["FUNCTION public.hello()"]
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use crate::db::psql::quote_ident;
+use crate::db::toc::parse_header;
+use crate::db::webhooks::ProjectRewrite;
+use std::collections::BTreeSet;
 use tracing::debug;
 
 /// Transforms SQL dump to be compatible with Supabase target project
@@ -26,6 +30,150 @@ impl SqlTransformer {
         result
     }
 
+    /// Rewrite project URLs and keys in trigger definitions (database webhooks)
+    pub fn rewrite_triggers(sql: &str, rewrite: &ProjectRewrite) -> String {
+        if rewrite.is_empty() {
+            return sql.to_string();
+        }
+
+        sql.lines()
+            .map(|line| {
+                if Self::is_trigger_line(line) {
+                    rewrite.apply(line)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Count database webhook triggers in a dump
+    pub fn count_webhook_triggers(sql: &str) -> usize {
+        sql.lines()
+            .filter(|line| Self::is_webhook_line(line))
+            .count()
+    }
+
+    /// Count database webhook triggers still holding an API key of the source project
+    pub fn count_source_keys(sql: &str, rewrite: &ProjectRewrite) -> usize {
+        sql.lines()
+            .filter(|line| Self::is_webhook_line(line) && rewrite.has_source_key(line))
+            .count()
+    }
+
+    /// Comment out database webhook triggers
+    pub fn strip_webhook_triggers(sql: &str) -> String {
+        sql.lines()
+            .map(|line| {
+                if Self::is_webhook_line(line) {
+                    format!("-- {}", line)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Schemas the entries of a dump belong to
+    pub fn dump_schemas(sql: &str) -> BTreeSet<String> {
+        sql.lines()
+            .filter_map(parse_header)
+            .filter_map(|(_, _, schema, _)| schema)
+            .collect()
+    }
+
+    /// Move every object of schema `from` into schema `to`, which must already exist:
+    /// qualified names and `SCHEMA` clauses are rewritten and the statements creating,
+    /// dropping or commenting on the schema itself are commented out. Table data is
+    /// never rewritten. The `--clean` statements before the first entry are all
+    /// commented out: `to` is always new, and they would otherwise reach live objects.
+    pub fn move_schema(sql: &str, from: &str, to: &str) -> String {
+        let target = quote_ident(to);
+        let mut in_copy = false;
+        let mut preamble = true;
+
+        sql.lines()
+            .map(|line| {
+                if preamble && parse_header(line).is_some() {
+                    preamble = false;
+                }
+                if preamble && (line.starts_with("DROP ") || line.starts_with("ALTER ")) {
+                    return format!("-- {}", line);
+                }
+                if in_copy {
+                    in_copy = line != "\\.";
+                    return line.to_string();
+                }
+                if line.starts_with("COPY ") && line.ends_with("FROM stdin;") {
+                    in_copy = true;
+                }
+
+                let line = Self::replace_schema(line, from, &target);
+                let own_statement = ["CREATE SCHEMA ", "DROP SCHEMA ", "COMMENT ON SCHEMA "]
+                    .iter()
+                    .any(|p| line.trim_start().starts_with(p));
+                let names_target = line.contains(&format!("SCHEMA {}", target))
+                    || line.contains(&format!("EXISTS {}", target));
+                if own_statement && names_target {
+                    format!("-- {}", line)
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Replace `"from".`, `from.` and `SCHEMA from` with the quoted `target` where
+    /// `from` is a whole identifier
+    fn replace_schema(line: &str, from: &str, target: &str) -> String {
+        let quoted = quote_ident(from);
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$' || c == '"';
+
+        let mut result = String::with_capacity(line.len());
+        let mut rest = line;
+        while !rest.is_empty() {
+            let before = result.chars().last();
+            let boundary = !before.is_some_and(is_ident);
+            let schema_clause = result.ends_with("SCHEMA ") || result.ends_with("EXISTS ");
+
+            let matched = [quoted.as_str(), from].into_iter().find(|name| {
+                let Some(after) = rest.strip_prefix(name) else {
+                    return false;
+                };
+                let qualifies = after.starts_with('.');
+                let ends = after.chars().next().map_or(true, |c| !is_ident(c));
+                boundary && (qualifies || (schema_clause && ends))
+            });
+
+            match matched {
+                Some(name) => {
+                    result.push_str(target);
+                    rest = &rest[name.len()..];
+                }
+                None => {
+                    let c = rest.chars().next().unwrap_or_default();
+                    result.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        result
+    }
+
+    fn is_trigger_line(line: &str) -> bool {
+        let line = line.trim_start();
+        line.starts_with("CREATE TRIGGER") || line.starts_with("CREATE OR REPLACE TRIGGER")
+    }
+
+    fn is_webhook_line(line: &str) -> bool {
+        Self::is_trigger_line(line)
+            && (line.contains("\"supabase_functions\".\"http_request\"")
+                || line.contains("supabase_functions.http_request"))
+    }
+
     /// Comment out a specific line
     fn comment_line(sql: &str, target: &str) -> String {
         sql.lines()
@@ -92,4 +240,87 @@ ALTER DEFAULT PRIVILEGES FOR ROLE "supabase_admin" IN SCHEMA "public" GRANT ALL
         let result = SqlTransformer::transform(input);
         assert!(result.contains("-- ALTER DEFAULT PRIVILEGES FOR ROLE \"supabase_admin\""));
     }
+
+    #[test]
+    fn test_move_schema() {
+        let input = r#"SET statement_timeout = 0;
+ALTER TABLE IF EXISTS ONLY "public"."orders" DROP CONSTRAINT IF EXISTS "orders_pkey";
+DROP TABLE IF EXISTS "public"."orders";
+DROP TABLE IF EXISTS "archive"."old_orders";
+DROP SCHEMA IF EXISTS "archive";
+--
+-- Name: public; Type: SCHEMA; Schema: -; Owner: -
+--
+CREATE SCHEMA "public";
+COMMENT ON SCHEMA "public" IS 'standard public schema';
+CREATE TABLE public.orders (id bigint DEFAULT nextval('public.orders_id_seq'::regclass));
+CREATE TABLE "public"."public_notes" (note text);
+ALTER SEQUENCE public.orders_id_seq OWNED BY public.orders.id;
+GRANT USAGE ON SCHEMA public TO anon;
+CREATE POLICY "own" ON "public"."orders" USING (my_public.check());
+COPY "public"."public_notes" (note) FROM stdin;
+see public.orders
+\.
+SELECT 1;"#;
+        let result = SqlTransformer::move_schema(input, "public", "recovered");
+        // A side-by-side recovery never drops anything
+        assert!(result
+            .lines()
+            .filter(|l| !l.starts_with("--"))
+            .all(|l| !l.contains("DROP ")));
+
+        let lines: Vec<&str> = result.lines().skip(8).collect();
+        assert_eq!(
+            lines[..7],
+            [
+                r#"-- CREATE SCHEMA "recovered";"#,
+                r#"-- COMMENT ON SCHEMA "recovered" IS 'standard public schema';"#,
+                r#"CREATE TABLE "recovered".orders (id bigint DEFAULT nextval('"recovered".orders_id_seq'::regclass));"#,
+                r#"CREATE TABLE "recovered"."public_notes" (note text);"#,
+                r#"ALTER SEQUENCE "recovered".orders_id_seq OWNED BY "recovered".orders.id;"#,
+                r#"GRANT USAGE ON SCHEMA "recovered" TO anon;"#,
+                r#"CREATE POLICY "own" ON "recovered"."orders" USING (my_public.check());"#,
+            ]
+        );
+        // Table data is never rewritten
+        assert_eq!(lines[8], "see public.orders");
+        assert_eq!(lines[10], "SELECT 1;");
+
+        assert_eq!(
+            SqlTransformer::dump_schemas("-- Name: orders; Type: TABLE; Schema: public; Owner: -"),
+            BTreeSet::from(["public".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_webhook_triggers() {
+        let input = r#"
+CREATE TRIGGER "notify" AFTER INSERT ON "public"."orders" FOR EACH ROW EXECUTE FUNCTION "supabase_functions"."http_request"('https://oldref.supabase.co/functions/v1/hook', 'POST', '{}', '{}', '1000');
+CREATE TRIGGER "touch" BEFORE UPDATE ON "public"."orders" FOR EACH ROW EXECUTE FUNCTION "public"."touch"();
+INSERT INTO "public"."links" VALUES ('https://oldref.supabase.co/x');
+"#;
+        assert_eq!(SqlTransformer::count_webhook_triggers(input), 1);
+
+        let stripped = SqlTransformer::strip_webhook_triggers(input);
+        assert!(stripped.contains("-- CREATE TRIGGER \"notify\""));
+        assert!(stripped.contains("\nCREATE TRIGGER \"touch\""));
+
+        let target = crate::config::ProjectConfig {
+            project_ref: "newref".to_string(),
+            db_password: "password".to_string(),
+            service_key: None,
+            anon_key: None,
+            db_host: None,
+            db_port: None,
+            api_url: None,
+            access_token: None,
+            encryption: None,
+            retention: None,
+        };
+        let rewrite = ProjectRewrite::from_ref("oldref", None, &target);
+        let rewritten = SqlTransformer::rewrite_triggers(input, &rewrite);
+        assert!(rewritten.contains("'https://newref.supabase.co/functions/v1/hook'"));
+        // Table data is never rewritten
+        assert!(rewritten.contains("VALUES ('https://oldref.supabase.co/x')"));
+    }
 }
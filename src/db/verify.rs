@@ -0,0 +1,242 @@
+use crate::db::psql::{self, quote_ident, quote_literal};
+use crate::db::tables::TableName;
+use crate::error::Result;
+use serde::Deserialize;
+
+/// Number of rows in a table
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TableCount {
+    pub schema: String,
+    pub table: String,
+    pub rows: i64,
+}
+
+/// Number and total size of the objects in a storage bucket
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BucketUsage {
+    pub bucket: String,
+    pub objects: i64,
+    pub bytes: i64,
+}
+
+/// A value that differs between source and target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub object: String,
+    pub source: String,
+    pub target: String,
+}
+
+/// Client for computing row counts, content hashes and storage usage via SQL
+pub struct VerifyClient {
+    db_url: String,
+}
+
+impl VerifyClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// Count the rows of each table
+    pub fn row_counts(&self, tables: &[TableName]) -> Result<Vec<TableCount>> {
+        if tables.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let counts: Vec<String> = tables
+            .iter()
+            .map(|t| {
+                format!(
+                    "SELECT {} AS schema, {} AS \"table\", count(*) AS rows FROM {}",
+                    quote_literal(&t.schema),
+                    quote_literal(&t.table),
+                    t.qualified_name()
+                )
+            })
+            .collect();
+        let sql = format!(
+            "SELECT COALESCE(json_agg(c), '[]'::json)::text FROM ({}) c",
+            counts.join(" UNION ALL ")
+        );
+
+        psql::query_json(&self.db_url, &sql)
+    }
+
+    /// md5 of each chunk of `chunk_size` rows, ordered by primary key
+    pub fn checksums(
+        &self,
+        table: &TableName,
+        columns: &[String],
+        primary_key: &[String],
+        chunk_size: u64,
+    ) -> Result<Vec<String>> {
+        let quote_all = |names: &[String]| names.iter().map(|c| quote_ident(c)).collect::<Vec<_>>();
+        let sql = format!(
+            r"
+            SELECT COALESCE(json_agg(hash ORDER BY chunk), '[]'::json)::text
+            FROM (
+                SELECT (rn - 1) / {chunk_size} AS chunk,
+                       md5(string_agg(row_hash, '' ORDER BY rn)) AS hash
+                FROM (
+                    SELECT row_number() OVER (ORDER BY {}) AS rn,
+                           md5(ROW({})::text) AS row_hash
+                    FROM {}
+                ) r
+                GROUP BY 1
+            ) c
+        ",
+            quote_all(primary_key).join(", "),
+            quote_all(columns).join(", "),
+            table.qualified_name()
+        );
+
+        psql::query_json(&self.db_url, &sql)
+    }
+
+    /// Object counts and sizes per bucket from `storage.objects`
+    pub fn storage_usage(&self) -> Result<Vec<BucketUsage>> {
+        let sql = r"
+            SELECT COALESCE(json_agg(u ORDER BY u.bucket), '[]'::json)::text
+            FROM (
+                SELECT bucket_id AS bucket,
+                       count(*) AS objects,
+                       COALESCE(sum((metadata->>'size')::bigint), 0) AS bytes
+                FROM storage.objects
+                GROUP BY bucket_id
+            ) u
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+}
+
+/// Tables whose row counts differ or that are missing on the target; with
+/// `allow_extra_rows` only tables with fewer target rows count
+pub fn compare_row_counts(
+    source: &[TableCount],
+    target: &[TableCount],
+    allow_extra_rows: bool,
+) -> Vec<Mismatch> {
+    source
+        .iter()
+        .filter_map(|s| {
+            let t = target
+                .iter()
+                .find(|t| t.schema == s.schema && t.table == s.table);
+            let target_rows = t.map_or_else(|| "missing".to_string(), |t| t.rows.to_string());
+            let matches =
+                t.is_some_and(|t| t.rows == s.rows || (allow_extra_rows && t.rows > s.rows));
+            (!matches).then(|| Mismatch {
+                object: format!("{}.{}", s.schema, s.table),
+                source: s.rows.to_string(),
+                target: target_rows,
+            })
+        })
+        .collect()
+}
+
+/// Buckets whose object counts or total sizes differ
+pub fn compare_storage(source: &[BucketUsage], target: &[BucketUsage]) -> Vec<Mismatch> {
+    let describe = |u: &BucketUsage| format!("{} objects, {} bytes", u.objects, u.bytes);
+
+    let mut mismatches: Vec<Mismatch> = source
+        .iter()
+        .filter_map(|s| {
+            let t = target.iter().find(|t| t.bucket == s.bucket);
+            (t != Some(s)).then(|| Mismatch {
+                object: s.bucket.clone(),
+                source: describe(s),
+                target: t.map_or_else(|| "missing".to_string(), describe),
+            })
+        })
+        .collect();
+
+    mismatches.extend(
+        target
+            .iter()
+            .filter(|t| !source.iter().any(|s| s.bucket == t.bucket))
+            .map(|t| Mismatch {
+                object: t.bucket.clone(),
+                source: "missing".to_string(),
+                target: describe(t),
+            }),
+    );
+
+    mismatches
+}
+
+/// Index of the first chunk whose hash differs (including a differing chunk count)
+pub fn first_differing_chunk(source: &[String], target: &[String]) -> Option<usize> {
+    source
+        .iter()
+        .zip(target)
+        .position(|(s, t)| s != t)
+        .or_else(|| (source.len() != target.len()).then(|| source.len().min(target.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(table: &str, rows: i64) -> TableCount {
+        TableCount {
+            schema: "public".to_string(),
+            table: table.to_string(),
+            rows,
+        }
+    }
+
+    fn usage(bucket: &str, objects: i64, bytes: i64) -> BucketUsage {
+        BucketUsage {
+            bucket: bucket.to_string(),
+            objects,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn test_compare_row_counts() {
+        let source = vec![count("users", 10), count("orders", 25), count("logs", 3)];
+        let target = vec![count("users", 10), count("orders", 24)];
+
+        let mismatches = compare_row_counts(&source, &target, false);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].object, "public.orders");
+        assert_eq!(mismatches[0].target, "24");
+        assert_eq!(mismatches[1].object, "public.logs");
+        assert_eq!(mismatches[1].target, "missing");
+
+        // A merge keeps target rows that are not in the source
+        let target = vec![count("users", 12), count("orders", 24), count("logs", 3)];
+        let mismatches = compare_row_counts(&source, &target, true);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].object, "public.orders");
+    }
+
+    #[test]
+    fn test_compare_storage() {
+        let source = vec![usage("avatars", 5, 1000), usage("docs", 2, 50)];
+        let target = vec![
+            usage("avatars", 5, 1000),
+            usage("docs", 2, 40),
+            usage("tmp", 1, 1),
+        ];
+
+        let objects: Vec<String> = compare_storage(&source, &target)
+            .into_iter()
+            .map(|m| m.object)
+            .collect();
+        assert_eq!(objects, vec!["docs", "tmp"]);
+    }
+
+    #[test]
+    fn test_first_differing_chunk() {
+        let a = vec!["x".to_string(), "y".to_string()];
+        let b = vec!["x".to_string(), "z".to_string()];
+        let c = vec!["x".to_string()];
+
+        assert_eq!(first_differing_chunk(&a, &a), None);
+        assert_eq!(first_differing_chunk(&a, &b), Some(1));
+        assert_eq!(first_differing_chunk(&a, &c), Some(1));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::config::ProjectConfig;
+use crate::db::psql;
+use crate::error::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// A database webhook: a trigger calling `supabase_functions.http_request`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub schema: String,
+    pub table: String,
+    pub name: String,
+    /// Full `CREATE TRIGGER` statement from `pg_get_triggerdef`
+    pub definition: String,
+}
+
+/// Rewrites project URLs and keys from a source project to a target project
+#[derive(Debug, Clone, Default)]
+pub struct ProjectRewrite {
+    replacements: Vec<(String, String)>,
+    source_ref: String,
+    /// Configured API keys of the source project
+    source_keys: Vec<String>,
+}
+
+/// Client for detecting and recreating database webhooks via SQL
+pub struct WebhooksClient {
+    db_url: String,
+}
+
+impl Webhook {
+    /// The URL the webhook calls (first argument of `http_request`)
+    pub fn url(&self) -> Option<&str> {
+        let start = self.definition.find("http_request(")? + "http_request(".len();
+        let rest = self.definition[start..].trim_start().strip_prefix('\'')?;
+        rest.split('\'').next()
+    }
+}
+
+impl ProjectRewrite {
+    /// Rewrite from a configured source project to a configured target project
+    pub fn new(source: &ProjectConfig, target: &ProjectConfig) -> Self {
+        Self::from_ref(&source.project_ref, Some(source), target)
+    }
+
+    /// Rewrite from a source project known by reference (e.g. from backup metadata),
+    /// with its configuration if it is still configured
+    pub fn from_ref(
+        source_ref: &str,
+        source: Option<&ProjectConfig>,
+        target: &ProjectConfig,
+    ) -> Self {
+        let mut rewrite = Self {
+            source_ref: source_ref.to_string(),
+            ..Self::default()
+        };
+
+        rewrite.add(
+            format!("https://{}.functions.supabase.co", source_ref),
+            format!("https://{}.functions.supabase.co", target.project_ref),
+        );
+        rewrite.add(
+            format!("https://{}.supabase.co", source_ref),
+            target.api_url(),
+        );
+        let Some(source) = source else {
+            return rewrite;
+        };
+        if let Some(api_url) = &source.api_url {
+            rewrite.add(api_url.clone(), target.api_url());
+        }
+        for (from, to) in [
+            (&source.service_key, &target.service_key),
+            (&source.anon_key, &target.anon_key),
+        ] {
+            let Some(from) = from else { continue };
+            rewrite.source_keys.push(from.clone());
+            if let Some(to) = to {
+                rewrite.add(from.clone(), to.clone());
+            }
+        }
+
+        rewrite
+    }
+
+    fn add(&mut self, from: String, to: String) {
+        if !from.is_empty() && from != to {
+            self.replacements.push((from, to));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replacements.is_empty()
+    }
+
+    /// Apply all replacements to a piece of SQL
+    pub fn apply(&self, text: &str) -> String {
+        self.replacements
+            .iter()
+            .fold(text.to_string(), |acc, (from, to)| acc.replace(from, to))
+    }
+
+    /// Whether `text` still contains an API key of the source project: a configured
+    /// key without a target counterpart, or any JWT issued for the source project
+    pub fn has_source_key(&self, text: &str) -> bool {
+        self.source_keys
+            .iter()
+            .any(|key| text.contains(key.as_str()))
+            || text
+                .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .any(|token| jwt_project_ref(token).is_some_and(|r| r == self.source_ref))
+    }
+}
+
+/// `ref` claim of a Supabase API key (a JWT), without verifying its signature
+fn jwt_project_ref(token: &str) -> Option<String> {
+    let mut parts = token.split('.');
+    let (_, payload, _) = (parts.next()?, parts.next()?, parts.next()?);
+    if !token.starts_with("eyJ") || parts.next().is_some() {
+        return None;
+    }
+    let claims: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    claims["ref"].as_str().map(String::from)
+}
+
+impl WebhooksClient {
+    pub fn new(db_url: String) -> Self {
+        Self { db_url }
+    }
+
+    /// Check if database webhooks are enabled (the `supabase_functions.http_request` function exists)
+    pub fn is_enabled(&self) -> Result<bool> {
+        let sql = r"
+            SELECT EXISTS (
+                SELECT 1 FROM pg_proc p
+                JOIN pg_namespace n ON n.oid = p.pronamespace
+                WHERE n.nspname = 'supabase_functions' AND p.proname = 'http_request'
+            )
+        ";
+        let result = psql::query(&self.db_url, sql)?;
+        Ok(result == "t" || result == "true")
+    }
+
+    /// List all webhook triggers
+    pub fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        if !self.is_enabled()? {
+            return Ok(vec![]);
+        }
+
+        let sql = r"
+            SELECT COALESCE(
+                json_agg(
+                    json_build_object(
+                        'schema', n.nspname,
+                        'table', c.relname,
+                        'name', t.tgname,
+                        'definition', pg_get_triggerdef(t.oid)
+                    )
+                    ORDER BY n.nspname, c.relname, t.tgname
+                ),
+                '[]'::json
+            )::text
+            FROM pg_trigger t
+            JOIN pg_class c ON c.oid = t.tgrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_proc p ON p.oid = t.tgfoid
+            JOIN pg_namespace pn ON pn.oid = p.pronamespace
+            WHERE NOT t.tgisinternal
+              AND pn.nspname = 'supabase_functions'
+              AND p.proname = 'http_request'
+        ";
+
+        psql::query_json(&self.db_url, sql)
+    }
+
+    /// Recreate webhooks with rewritten URLs and keys (replacing existing triggers)
+    pub fn apply(&self, webhooks: &[Webhook], rewrite: &ProjectRewrite) -> Result<usize> {
+        for webhook in webhooks {
+            let sql = format!(
+                "BEGIN; DROP TRIGGER IF EXISTS {} ON {}.{}; {}; COMMIT;",
+                psql::quote_ident(&webhook.name),
+                psql::quote_ident(&webhook.schema),
+                psql::quote_ident(&webhook.table),
+                rewrite.apply(&webhook.definition)
+            );
+            debug!(
+                "Recreating webhook {} on {}.{}",
+                webhook.name, webhook.schema, webhook.table
+            );
+            psql::query(&self.db_url, &sql)?;
+        }
+
+        Ok(webhooks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(project_ref: &str, service_key: &str) -> ProjectConfig {
+        ProjectConfig {
+            project_ref: project_ref.to_string(),
+            db_password: "password".to_string(),
+            service_key: Some(service_key.to_string()),
+            anon_key: None,
+            db_host: None,
+            db_port: None,
+            api_url: None,
+            access_token: None,
+            encryption: None,
+            retention: None,
+        }
+    }
+
+    #[test]
+    fn test_project_rewrite() {
+        let rewrite =
+            ProjectRewrite::new(&project("oldref", "old-key"), &project("newref", "new-key"));
+
+        let sql = r#"CREATE TRIGGER "notify" AFTER INSERT ON "public"."orders" FOR EACH ROW EXECUTE FUNCTION "supabase_functions"."http_request"('https://oldref.supabase.co/functions/v1/send-email', 'POST', '{"Authorization":"Bearer old-key"}', '{}', '5000');"#;
+        let rewritten = rewrite.apply(sql);
+
+        assert!(rewritten.contains("'https://newref.supabase.co/functions/v1/send-email'"));
+        assert!(rewritten.contains("Bearer new-key"));
+        assert!(!rewritten.contains("oldref"));
+        assert!(!rewritten.contains("old-key"));
+        assert!(!rewrite.has_source_key(&rewritten));
+    }
+
+    #[test]
+    fn test_project_rewrite_anon_key() {
+        let mut source = project("oldref", "old-key");
+        source.anon_key = Some("old-anon".to_string());
+        let mut target = project("newref", "new-key");
+        let sql = r#"EXECUTE FUNCTION "supabase_functions"."http_request"('https://oldref.supabase.co/rest/v1/log', 'POST', '{"apikey":"old-anon"}', '{}', '5000');"#;
+
+        let rewrite = ProjectRewrite::new(&source, &target);
+        assert!(rewrite.has_source_key(&rewrite.apply(sql)));
+
+        target.anon_key = Some("new-anon".to_string());
+        let rewrite = ProjectRewrite::new(&source, &target);
+        let rewritten = rewrite.apply(sql);
+        assert!(rewritten.contains(r#"{"apikey":"new-anon"}"#));
+        assert!(!rewrite.has_source_key(&rewritten));
+
+        // Unconfigured keys are recognized by the project ref in their claims
+        let jwt = "eyJhbGciOiJIUzI1NiJ9.eyJpc3MiOiJzdXBhYmFzZSIsInJlZiI6Im9sZHJlZiIsInJvbGUiOiJhbm9uIn0.c2ln";
+        assert!(rewrite.has_source_key(&format!(r#"'{{"Authorization":"Bearer {}"}}'"#, jwt)));
+        assert!(!ProjectRewrite::new(&target, &source).has_source_key(jwt));
+    }
+
+    #[test]
+    fn test_webhook_url() {
+        let webhook = Webhook {
+            schema: "public".to_string(),
+            table: "orders".to_string(),
+            name: "notify".to_string(),
+            definition: "CREATE TRIGGER notify AFTER INSERT ON public.orders FOR EACH ROW EXECUTE FUNCTION supabase_functions.http_request('https://abc.supabase.co/functions/v1/hook', 'POST', '{}', '{}', '1000')".to_string(),
+        };
+        assert_eq!(
+            webhook.url(),
+            Some("https://abc.supabase.co/functions/v1/hook")
+        );
+    }
+}
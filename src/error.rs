@@ -65,6 +65,12 @@ pub enum SupamigrateError {
 
     #[error("Invalid backup format: {0}")]
     InvalidBackup(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("S3 error: {0}")]
+    S3(String),
 }
 
 pub type Result<T> = std::result::Result<T, SupamigrateError>;
@@ -1,5 +1,5 @@
 use crate::error::{Result, SupamigrateError};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
@@ -9,7 +9,7 @@ const SUPABASE_API_URL: &str = "https://api.supabase.com";
 pub struct FunctionsClient {
     client: Client,
     project_ref: String,
-    service_key: String,
+    access_token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,20 +57,55 @@ pub struct FunctionBackup {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionFile {
     pub name: String,
-    pub content: String,
+    /// Raw file bytes; functions may bundle WASM modules, fonts or images
+    pub content: Vec<u8>,
+}
+
+/// Differences between the functions of two projects
+#[derive(Debug, Default)]
+pub struct FunctionsDiff<'a> {
+    pub only_source: Vec<&'a FunctionBackup>,
+    pub only_target: Vec<&'a FunctionBackup>,
+    pub changed: Vec<FunctionChanges<'a>>,
+    pub unchanged: usize,
+}
+
+/// How one function differs between two projects
+#[derive(Debug, Default)]
+pub struct FunctionChanges<'a> {
+    pub slug: &'a str,
+    /// `(setting, source value, target value)`
+    pub settings: Vec<(&'static str, String, String)>,
+    pub only_source: Vec<&'a str>,
+    pub only_target: Vec<&'a str>,
+    /// Files present in both projects with different contents
+    pub changed: Vec<&'a str>,
 }
 
 impl FunctionsClient {
-    pub fn new(project_ref: String, service_key: String) -> Self {
+    /// Functions are managed through the Management API, which takes a personal
+    /// access token rather than a project API key
+    pub fn new(project_ref: String, access_token: String) -> Self {
         Self {
             client: Client::new(),
             project_ref,
-            service_key,
+            access_token,
         }
     }
 
     fn auth_header(&self) -> String {
-        format!("Bearer {}", self.service_key)
+        format!("Bearer {}", self.access_token)
+    }
+
+    fn error(&self, action: String, status: StatusCode, body: String) -> SupamigrateError {
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return SupamigrateError::Functions(format!(
+                "Failed to {}: the Management API rejected the access token ({}). \
+                 access_token must be a personal access token with access to project {}",
+                action, status, self.project_ref
+            ));
+        }
+        SupamigrateError::Functions(format!("Failed to {}: {} - {}", action, status, body))
     }
 
     /// List all edge functions
@@ -91,10 +126,7 @@ impl FunctionsClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(SupamigrateError::Functions(format!(
-                "Failed to list functions: {} - {}",
-                status, body
-            )));
+            return Err(self.error("list functions".to_string(), status, body));
         }
 
         let functions: Vec<EdgeFunction> = response.json().await?;
@@ -120,10 +152,7 @@ impl FunctionsClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(SupamigrateError::Functions(format!(
-                "Failed to get function '{}': {} - {}",
-                slug, status, body
-            )));
+            return Err(self.error(format!("get function '{}'", slug), status, body));
         }
 
         let function: EdgeFunctionBody = response.json().await?;
@@ -149,10 +178,7 @@ impl FunctionsClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(SupamigrateError::Functions(format!(
-                "Failed to download function '{}': {} - {}",
-                slug, status, body
-            )));
+            return Err(self.error(format!("download function '{}'", slug), status, body));
         }
 
         // Check content type - might be JSON or tarball
@@ -170,7 +196,7 @@ impl FunctionsClient {
                     name: body
                         .entrypoint_path
                         .unwrap_or_else(|| "index.ts".to_string()),
-                    content: source,
+                    content: source.into_bytes(),
                 }]);
             }
             return Ok(vec![]);
@@ -220,7 +246,10 @@ impl FunctionsClient {
 
         // Add files
         for file in &backup.files {
-            form = form.text(file.name.clone(), file.content.clone());
+            let part = reqwest::multipart::Part::bytes(file.content.clone())
+                .file_name(file.name.clone())
+                .mime_str(&mime_type(&file.name))?;
+            form = form.part(file.name.clone(), part);
         }
 
         let request = if exists {
@@ -238,10 +267,31 @@ impl FunctionsClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(SupamigrateError::Functions(format!(
-                "Failed to deploy function '{}': {} - {}",
-                backup.slug, status, body
-            )));
+            return Err(self.error(format!("deploy function '{}'", backup.slug), status, body));
+        }
+
+        Ok(())
+    }
+
+    /// Delete an edge function
+    pub async fn delete_function(&self, slug: &str) -> Result<()> {
+        let url = format!(
+            "{}/v1/projects/{}/functions/{}",
+            SUPABASE_API_URL, self.project_ref, slug
+        );
+        debug!("Deleting function: {}", url);
+
+        let response = self
+            .client
+            .delete(&url)
+            .header("Authorization", self.auth_header())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(self.error(format!("delete function '{}'", slug), status, body));
         }
 
         Ok(())
@@ -270,46 +320,230 @@ impl FunctionsClient {
     }
 }
 
-/// Extract files from a gzipped tarball
+impl<'a> FunctionChanges<'a> {
+    fn new(source: &'a FunctionBackup, target: &'a FunctionBackup) -> Self {
+        let mut changes = Self {
+            slug: &source.slug,
+            ..Default::default()
+        };
+
+        let optional = |v: &Option<String>| v.clone().unwrap_or_else(|| "none".to_string());
+        if source.verify_jwt != target.verify_jwt {
+            changes.settings.push((
+                "verify_jwt",
+                source.verify_jwt.to_string(),
+                target.verify_jwt.to_string(),
+            ));
+        }
+        if source.entrypoint_path != target.entrypoint_path {
+            changes.settings.push((
+                "entrypoint",
+                optional(&source.entrypoint_path),
+                optional(&target.entrypoint_path),
+            ));
+        }
+        if source.import_map_path != target.import_map_path {
+            changes.settings.push((
+                "import_map",
+                optional(&source.import_map_path),
+                optional(&target.import_map_path),
+            ));
+        }
+
+        for file in &source.files {
+            match target.files.iter().find(|f| f.name == file.name) {
+                Some(f) if f.content == file.content => {}
+                Some(_) => changes.changed.push(&file.name),
+                None => changes.only_source.push(&file.name),
+            }
+        }
+        changes.only_target = target
+            .files
+            .iter()
+            .filter(|f| !source.files.iter().any(|s| s.name == f.name))
+            .map(|f| f.name.as_str())
+            .collect();
+
+        changes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+            && self.only_source.is_empty()
+            && self.only_target.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// Compare functions, with their files and settings, between a source and a target project
+pub fn diff_functions<'a>(
+    source: &'a [FunctionBackup],
+    target: &'a [FunctionBackup],
+) -> FunctionsDiff<'a> {
+    let mut diff = FunctionsDiff::default();
+
+    for function in source {
+        match target.iter().find(|t| t.slug == function.slug) {
+            Some(t) => {
+                let changes = FunctionChanges::new(function, t);
+                if changes.is_empty() {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed.push(changes);
+                }
+            }
+            None => diff.only_source.push(function),
+        }
+    }
+
+    diff.only_target = target
+        .iter()
+        .filter(|t| !source.iter().any(|s| s.slug == t.slug))
+        .collect();
+
+    diff
+}
+
+/// First bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Extract files from a gzipped tarball; anything else is a single source file
 fn extract_tarball(data: &[u8]) -> Result<Vec<FunctionFile>> {
     use flate2::read::GzDecoder;
     use std::io::Read;
 
+    // Reading entries from a non-gzip body only fails later, so decide up front
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(vec![FunctionFile {
+            name: "index.ts".to_string(),
+            content: data.to_vec(),
+        }]);
+    }
+
     let mut files = Vec::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(data));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+
+        // Skip directories
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
 
-    // Try to decompress as gzip first
-    let decoder = GzDecoder::new(data);
-    let mut archive = tar::Archive::new(decoder);
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
 
-    match archive.entries() {
-        Ok(entries) => {
-            for entry in entries {
-                let mut entry = entry?;
-                let path = entry.path()?.to_string_lossy().to_string();
+        files.push(FunctionFile {
+            name: path,
+            content,
+        });
+    }
 
-                // Skip directories
-                if entry.header().entry_type().is_dir() {
-                    continue;
-                }
+    Ok(files)
+}
 
-                let mut content = String::new();
-                entry.read_to_string(&mut content)?;
+/// MIME type of a function file for the deploy form. Source files get their script
+/// types, since `.ts` would otherwise be guessed as an MPEG transport stream.
+fn mime_type(name: &str) -> String {
+    let extension = name.rsplit_once('.').map_or("", |(_, e)| e);
+    match extension.to_ascii_lowercase().as_str() {
+        "ts" | "mts" | "tsx" => "application/typescript".to_string(),
+        "js" | "mjs" | "jsx" => "application/javascript".to_string(),
+        "wasm" => "application/wasm".to_string(),
+        _ => mime_guess::from_path(name)
+            .first_or_octet_stream()
+            .to_string(),
+    }
+}
 
-                files.push(FunctionFile {
-                    name: path,
-                    content,
-                });
-            }
-        }
-        Err(_) => {
-            // Not a tarball, try as plain text
-            let content = String::from_utf8_lossy(data).to_string();
-            files.push(FunctionFile {
-                name: "index.ts".to_string(),
-                content,
-            });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type("index.ts"), "application/typescript");
+        assert_eq!(mime_type("lib/util.mjs"), "application/javascript");
+        assert_eq!(mime_type("pkg/module.wasm"), "application/wasm");
+        assert_eq!(mime_type("import_map.json"), "application/json");
+        assert_eq!(mime_type("fonts/Inter.woff2"), "font/woff2");
+        assert_eq!(mime_type("logo.png"), "image/png");
+        assert_eq!(mime_type("LICENSE"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_diff_functions() {
+        let function = |slug: &str, verify_jwt: bool, files: &[(&str, &str)]| FunctionBackup {
+            slug: slug.to_string(),
+            name: slug.to_string(),
+            verify_jwt,
+            entrypoint_path: Some("index.ts".to_string()),
+            import_map_path: None,
+            files: files
+                .iter()
+                .map(|(name, content)| FunctionFile {
+                    name: name.to_string(),
+                    content: content.as_bytes().to_vec(),
+                })
+                .collect(),
+        };
+        let source = vec![
+            function("hello", true, &[("index.ts", "a"), ("util.ts", "b")]),
+            function("checkout", false, &[("index.ts", "new"), ("cors.ts", "c")]),
+            function("new", true, &[("index.ts", "")]),
+        ];
+        let target = vec![
+            function("hello", true, &[("index.ts", "a"), ("util.ts", "b")]),
+            function("checkout", true, &[("index.ts", "old"), ("old.ts", "")]),
+            function("legacy", true, &[("index.ts", "")]),
+        ];
+
+        let diff = diff_functions(&source, &target);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.only_source[0].slug, "new");
+        assert_eq!(diff.only_target[0].slug, "legacy");
+
+        let checkout = &diff.changed[0];
+        assert_eq!(checkout.slug, "checkout");
+        assert_eq!(
+            checkout.settings,
+            vec![("verify_jwt", "false".to_string(), "true".to_string())]
+        );
+        assert_eq!(checkout.changed, vec!["index.ts"]);
+        assert_eq!(checkout.only_source, vec!["cors.ts"]);
+        assert_eq!(checkout.only_target, vec!["old.ts"]);
+    }
+
+    #[test]
+    fn test_extract_tarball_binary() {
+        let wasm = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0xff, 0xfe];
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (name, content) in [("index.ts", &b"export {}"[..]), ("module.wasm", &wasm[..])] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
         }
+        let data = builder.into_inner().unwrap().finish().unwrap();
+
+        let files = extract_tarball(&data).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].content, b"export {}");
+        assert_eq!(files[1].name, "module.wasm");
+        assert_eq!(files[1].content, wasm);
     }
 
-    Ok(files)
+    #[test]
+    fn test_extract_tarball_plain_source() {
+        let source = b"Deno.serve(() => new Response(\"ok\"));";
+        let files = extract_tarball(source).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "index.ts");
+        assert_eq!(files[0].content, source);
+    }
 }
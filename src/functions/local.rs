@@ -0,0 +1,331 @@
+use crate::error::{Result, SupamigrateError};
+use crate::functions::{FunctionBackup, FunctionFile};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+/// Files whose relative imports are followed into shared folders
+const SCRIPT_EXTENSIONS: [&str; 6] = ["ts", "tsx", "mts", "js", "jsx", "mjs"];
+
+/// `supabase/config.toml`, of which only the per-function settings are read
+#[derive(Debug, Default, Deserialize)]
+struct SupabaseConfig {
+    #[serde(default)]
+    functions: BTreeMap<String, FunctionSettings>,
+}
+
+/// `[functions.<slug>]` settings; paths are relative to the `supabase` directory
+#[derive(Debug, Default, Clone, Deserialize)]
+struct FunctionSettings {
+    enabled: Option<bool>,
+    verify_jwt: Option<bool>,
+    import_map: Option<String>,
+    entrypoint: Option<String>,
+}
+
+/// Load the functions of a local `supabase/functions` directory for deployment,
+/// optionally only those in `only`. Folders starting with `_` or `.` hold shared
+/// code and are deployed only as imports of a function. File names are relative
+/// to `dir`, so shared imports keep their relative paths.
+pub fn load_local_functions(dir: &Path, only: &[String]) -> Result<Vec<FunctionBackup>> {
+    let dir = fs::canonicalize(dir).map_err(|e| {
+        SupamigrateError::Functions(format!("Cannot read {}: {}", dir.display(), e))
+    })?;
+    let root = dir.parent().unwrap_or(&dir).to_path_buf();
+    let config_path = root.join("config.toml");
+    let config: SupabaseConfig = if config_path.is_file() {
+        toml::from_str(&fs::read_to_string(&config_path)?)?
+    } else {
+        SupabaseConfig::default()
+    };
+
+    let mut slugs = BTreeSet::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && !name.starts_with('_') && !name.starts_with('.') {
+            slugs.insert(name);
+        }
+    }
+    if let Some(missing) = only.iter().find(|s| !slugs.contains(*s)) {
+        return Err(SupamigrateError::Functions(format!(
+            "Function {} not found in {}",
+            missing,
+            dir.display()
+        )));
+    }
+
+    let mut functions = Vec::new();
+    for slug in slugs {
+        let settings = config.functions.get(&slug).cloned().unwrap_or_default();
+        // Disabled functions are still deployed when asked for by name
+        let selected = if only.is_empty() {
+            settings.enabled != Some(false)
+        } else {
+            only.contains(&slug)
+        };
+        if selected {
+            functions.push(load_function(&dir, &root, &slug, &settings)?);
+        }
+    }
+    Ok(functions)
+}
+
+fn load_function(
+    dir: &Path,
+    root: &Path,
+    slug: &str,
+    settings: &FunctionSettings,
+) -> Result<FunctionBackup> {
+    let outside = |path: &str| {
+        SupamigrateError::Functions(format!(
+            "{} of function {} is outside {}",
+            path,
+            slug,
+            dir.display()
+        ))
+    };
+
+    let entrypoint = match &settings.entrypoint {
+        Some(path) => relative_to(dir, &root.join(path)).ok_or_else(|| outside(path))?,
+        None => format!("{}/index.ts", slug),
+    };
+    if !dir.join(&entrypoint).is_file() {
+        return Err(SupamigrateError::Functions(format!(
+            "Entrypoint {} of function {} not found",
+            entrypoint, slug
+        )));
+    }
+    let import_map = match &settings.import_map {
+        Some(path) => Some(relative_to(dir, &root.join(path)).ok_or_else(|| outside(path))?),
+        None => [
+            format!("{}/deno.json", slug),
+            format!("{}/deno.jsonc", slug),
+            "import_map.json".to_string(),
+        ]
+        .into_iter()
+        .find(|path| dir.join(path).is_file()),
+    };
+
+    let mut pending = Vec::new();
+    collect_files(dir, &dir.join(slug), &mut pending)?;
+    pending.push(entrypoint.clone());
+    pending.extend(import_map.clone());
+
+    let mut files = BTreeMap::new();
+    while let Some(name) = pending.pop() {
+        if files.contains_key(&name) {
+            continue;
+        }
+        let path = dir.join(&name);
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Skipping {} of function {}: {}", name, slug, e);
+                continue;
+            }
+        };
+
+        if is_script(&name) {
+            if let Ok(source) = std::str::from_utf8(&content) {
+                let base = path.parent().unwrap_or(dir);
+                for import in relative_imports(source) {
+                    let imported = relative_to(dir, &base.join(&import)).ok_or_else(|| {
+                        SupamigrateError::Functions(format!(
+                            "{} imports {}, which is outside {}",
+                            name,
+                            import,
+                            dir.display()
+                        ))
+                    })?;
+                    pending.push(imported);
+                }
+            }
+        }
+        files.insert(name, content);
+    }
+
+    Ok(FunctionBackup {
+        slug: slug.to_string(),
+        name: slug.to_string(),
+        verify_jwt: settings.verify_jwt.unwrap_or(true),
+        entrypoint_path: Some(entrypoint),
+        import_map_path: import_map,
+        files: files
+            .into_iter()
+            .map(|(name, content)| FunctionFile { name, content })
+            .collect(),
+    })
+}
+
+/// Names of the files under `path`, relative to `dir`, skipping hidden files and
+/// `node_modules`
+fn collect_files(dir: &Path, path: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == "node_modules" {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            collect_files(dir, &entry.path(), files)?;
+        } else if let Some(name) = relative_to(dir, &entry.path()) {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// `/`-separated path of `path` below `dir` after resolving `.` and `..`;
+/// `None` if it points outside `dir`
+fn relative_to(dir: &Path, path: &Path) -> Option<String> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    let relative = normalized.strip_prefix(dir).ok()?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn is_script(name: &str) -> bool {
+    name.rsplit_once('.')
+        .is_some_and(|(_, extension)| SCRIPT_EXTENSIONS.contains(&extension))
+}
+
+/// Relative specifiers of `import`/`export ... from` statements and dynamic imports
+fn relative_imports(source: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find(['"', '\'']) {
+        let quote = &rest[start..=start];
+        let before = rest[..start].trim_end();
+        let after = &rest[start + 1..];
+        let Some(end) = after.find(quote) else {
+            break;
+        };
+        let literal = &after[..end];
+        // A stray quote, e.g. in a comment, must not swallow the lines after it
+        if literal.contains('\n') {
+            rest = after;
+            continue;
+        }
+        if (literal.starts_with("./") || literal.starts_with("../"))
+            && ["from", "import", "import("]
+                .iter()
+                .any(|keyword| before.ends_with(keyword))
+        {
+            imports.push(literal.to_string());
+        }
+        rest = &after[end + 1..];
+    }
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_imports() {
+        let source = r#"
+// Don't deploy on Fridays
+import { corsHeaders } from "../_shared/cors.ts";
+import "./polyfill.ts";
+export * from './types.ts';
+import { serve } from "https://deno.land/std/http/server.ts";
+const wasm = await import("./pkg/module.js");
+const path = "./not-an-import.ts";
+"#;
+        assert_eq!(
+            relative_imports(source),
+            vec![
+                "../_shared/cors.ts",
+                "./polyfill.ts",
+                "./types.ts",
+                "./pkg/module.js"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_local_functions() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("functions");
+        let write = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            "hello/index.ts",
+            r#"import { cors } from "../_shared/cors.ts";"#,
+        );
+        write("hello/logo.png", "\u{89}PNG");
+        write("_shared/cors.ts", r#"export * from "./headers.ts";"#);
+        write("_shared/headers.ts", "export const cors = {};");
+        write("_shared/unused.ts", "");
+        write("webhook/main.ts", "");
+        write("webhook/deno.json", "{}");
+        write("old/index.ts", "");
+        fs::write(
+            root.path().join("config.toml"),
+            r#"
+[api]
+port = 54321
+
+[functions.webhook]
+verify_jwt = false
+entrypoint = "./functions/webhook/main.ts"
+
+[functions.old]
+enabled = false
+"#,
+        )
+        .unwrap();
+
+        let functions = load_local_functions(&dir, &[]).unwrap();
+        let slugs: Vec<_> = functions.iter().map(|f| f.slug.as_str()).collect();
+        assert_eq!(slugs, vec!["hello", "webhook"]);
+
+        let hello = &functions[0];
+        let names: Vec<_> = hello.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "_shared/cors.ts",
+                "_shared/headers.ts",
+                "hello/index.ts",
+                "hello/logo.png"
+            ]
+        );
+        assert!(hello.verify_jwt);
+        assert_eq!(hello.entrypoint_path.as_deref(), Some("hello/index.ts"));
+
+        let webhook = &functions[1];
+        assert!(!webhook.verify_jwt);
+        assert_eq!(webhook.entrypoint_path.as_deref(), Some("webhook/main.ts"));
+        assert_eq!(
+            webhook.import_map_path.as_deref(),
+            Some("webhook/deno.json")
+        );
+
+        let old = load_local_functions(&dir, &["old".to_string()]).unwrap();
+        assert_eq!(old.len(), 1);
+        assert!(load_local_functions(&dir, &["missing".to_string()]).is_err());
+
+        write("hello/index.ts", r#"import "../../outside.ts";"#);
+        assert!(load_local_functions(&dir, &["hello".to_string()]).is_err());
+    }
+}
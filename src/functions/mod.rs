@@ -1,4 +1,6 @@
 mod client;
+mod local;
 pub mod secrets;
 
 pub use client::*;
+pub use local::load_local_functions;
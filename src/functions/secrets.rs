@@ -1,6 +1,8 @@
 use crate::error::{Result, SupamigrateError};
+use crate::functions::FunctionBackup;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tracing::debug;
 
 const SUPABASE_API_URL: &str = "https://api.supabase.com";
@@ -189,6 +191,41 @@ pub fn generate_env_template(secrets: &[SecretMetadata]) -> String {
     output
 }
 
+/// Secrets read with `Deno.env.get("NAME")` by each function, mapped to the slugs
+/// using them. `SUPABASE_*` variables are provided by the platform and left out.
+pub fn referenced_secrets(functions: &[FunctionBackup]) -> BTreeMap<String, Vec<String>> {
+    let mut referenced: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for function in functions {
+        for file in &function.files {
+            // Bundled binaries cannot read secrets
+            let Ok(content) = std::str::from_utf8(&file.content) else {
+                continue;
+            };
+            for call in content.split("Deno.env.get(").skip(1) {
+                let call = call.trim_start();
+                let Some(quote) = call
+                    .chars()
+                    .next()
+                    .filter(|c| matches!(c, '"' | '\'' | '`'))
+                else {
+                    continue;
+                };
+                let Some((name, _)) = call[1..].split_once(quote) else {
+                    continue;
+                };
+                if name.is_empty() || name.starts_with("SUPABASE_") || name.contains("${") {
+                    continue;
+                }
+                let slugs = referenced.entry(name.to_string()).or_default();
+                if !slugs.contains(&function.slug) {
+                    slugs.push(function.slug.clone());
+                }
+            }
+        }
+    }
+    referenced
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,4 +266,39 @@ ANOTHER_KEY=value with spaces
         assert!(template.contains("API_KEY="));
         assert!(template.contains("DATABASE_URL="));
     }
+
+    #[test]
+    fn test_referenced_secrets() {
+        let function = |slug: &str, content: &str| FunctionBackup {
+            slug: slug.to_string(),
+            name: slug.to_string(),
+            verify_jwt: true,
+            entrypoint_path: None,
+            import_map_path: None,
+            files: vec![crate::functions::FunctionFile {
+                name: "index.ts".to_string(),
+                content: content.as_bytes().to_vec(),
+            }],
+        };
+        let functions = vec![
+            function(
+                "checkout",
+                r#"const key = Deno.env.get("STRIPE_KEY")!;
+const url = Deno.env.get('SUPABASE_URL');
+const hook = Deno.env.get( `STRIPE_WEBHOOK_SECRET` );
+const dynamic = Deno.env.get(name);"#,
+            ),
+            function(
+                "refund",
+                r#"Deno.env.get("STRIPE_KEY"); Deno.env.get("STRIPE_KEY")"#,
+            ),
+        ];
+
+        let referenced = referenced_secrets(&functions);
+        assert_eq!(
+            referenced.keys().collect::<Vec<_>>(),
+            vec!["STRIPE_KEY", "STRIPE_WEBHOOK_SECRET"]
+        );
+        assert_eq!(referenced["STRIPE_KEY"], vec!["checkout", "refund"]);
+    }
 }
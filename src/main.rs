@@ -10,6 +10,7 @@ use anyhow::Result;
 use clap::Parser;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+mod backup;
 mod cli;
 mod commands;
 mod config;
@@ -32,11 +33,15 @@ async fn main() -> Result<()> {
 
     match cli.command {
         Commands::Migrate(args) => commands::migrate::run(args).await,
+        Commands::Replicate(args) => commands::replicate::run(args).await,
+        Commands::Verify(args) => commands::verify::run(args),
         Commands::Backup(args) => commands::backup::run(args).await,
         Commands::Restore(args) => commands::restore::run(args).await,
         Commands::Storage(args) => commands::storage::run(args).await,
+        Commands::Functions(args) => commands::functions::run(args).await,
         Commands::Secrets(args) => commands::secrets::run(args).await,
         Commands::Vault(args) => commands::vault::run(args),
+        Commands::Cron(args) => commands::cron::run(args),
         Commands::Config(args) => commands::config::run(args),
         Commands::Doctor(args) => {
             commands::doctor::run(commands::doctor::DoctorArgs { fix: args.fix })
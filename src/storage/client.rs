@@ -1,5 +1,6 @@
 use crate::error::{Result, SupamigrateError};
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
@@ -30,12 +31,40 @@ pub struct StorageObject {
     pub updated_at: Option<String>,
 }
 
+/// An object left out of a backup because it changed after the database snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LateObject {
+    pub bucket: String,
+    pub path: String,
+    pub updated_at: String,
+}
+
 #[derive(Debug, Serialize)]
 struct CreateBucketRequest {
     name: String,
     public: bool,
 }
 
+impl StorageObject {
+    /// Whether the object was created or changed after `cutoff`; objects without a
+    /// readable update time are assumed unchanged
+    pub fn changed_after(&self, cutoff: DateTime<Utc>) -> bool {
+        self.updated_at
+            .as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| t > cutoff)
+    }
+
+    /// Record of the object for a backup's list of objects changed after its snapshot
+    pub fn late(&self, bucket: &str) -> LateObject {
+        LateObject {
+            bucket: bucket.to_string(),
+            path: self.name.clone(),
+            updated_at: self.updated_at.clone().unwrap_or_default(),
+        }
+    }
+}
+
 impl StorageClient {
     pub fn new(api_url: String, service_key: String) -> Self {
         Self {
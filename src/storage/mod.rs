@@ -1,5 +1,5 @@
 mod client;
 mod transfer;
 
-pub use client::StorageClient;
-pub use transfer::StorageTransfer;
+pub use client::{LateObject, StorageClient};
+pub use transfer::{human_bytes, StorageTransfer};
@@ -1,24 +1,32 @@
+use crate::backup::{encrypted_path, Encryption};
 use crate::error::Result;
-use crate::storage::client::{Bucket, StorageClient, StorageObject};
+use crate::storage::client::{Bucket, LateObject, StorageClient, StorageObject};
+use chrono::{DateTime, Utc};
 use futures::stream::{self, StreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::Arc;
 use tokio::fs;
 use tracing::info;
 
-pub struct StorageTransfer {
+pub struct StorageTransfer<'a> {
     pub source: StorageClient,
     target: Option<StorageClient>,
     parallel: usize,
+    snapshot: Option<DateTime<Utc>>,
+    encryption: Option<&'a Encryption>,
 }
 
-impl StorageTransfer {
+impl<'a> StorageTransfer<'a> {
     pub fn new(source: StorageClient) -> Self {
         Self {
             source,
             target: None,
             parallel: 4,
+            snapshot: None,
+            encryption: None,
         }
     }
 
@@ -32,6 +40,20 @@ impl StorageTransfer {
         self
     }
 
+    /// Only download objects last changed at or before the database snapshot;
+    /// later ones are listed in the stats instead
+    pub fn snapshot(mut self, taken_at: Option<DateTime<Utc>>) -> Self {
+        self.snapshot = taken_at;
+        self
+    }
+
+    /// Write downloaded objects encrypted to `<name>.age`, so no plaintext copy
+    /// ever reaches the disk
+    pub fn encryption(mut self, encryption: Option<&'a Encryption>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
     /// Sync all buckets from source to target
     pub async fn sync_all(&self) -> Result<SyncStats> {
         let target = self
@@ -151,6 +173,8 @@ impl StorageTransfer {
             stats.buckets += 1;
             stats.objects += bucket_stats.objects;
             stats.bytes += bucket_stats.bytes;
+            stats.late.extend(bucket_stats.late);
+            stats.hashes.extend(bucket_stats.hashes);
         }
 
         Ok(stats)
@@ -161,7 +185,17 @@ impl StorageTransfer {
         let bucket_dir = output_dir.join(&bucket.name);
         fs::create_dir_all(&bucket_dir).await?;
 
-        let objects = self.source.list_objects(&bucket.name, None).await?;
+        let mut stats = SyncStats::default();
+        let mut objects = self.source.list_objects(&bucket.name, None).await?;
+        if let Some(taken_at) = self.snapshot {
+            objects.retain(|o| {
+                let late = o.changed_after(taken_at);
+                if late {
+                    stats.late.push(o.late(&bucket.name));
+                }
+                !late
+            });
+        }
         info!("Downloading {} objects from {}", objects.len(), bucket.name);
 
         let multi = MultiProgress::new();
@@ -176,10 +210,9 @@ impl StorageTransfer {
 
         let source = Arc::new(self.source.clone());
         let bucket_name = bucket.name.clone();
+        let encryption = self.encryption;
 
-        let mut stats = SyncStats::default();
-
-        let results: Vec<Result<usize>> = stream::iter(objects.iter())
+        let results: Vec<Result<(String, usize, String)>> = stream::iter(objects.iter())
             .map(|obj| {
                 let source = Arc::clone(&source);
                 let bucket_name = bucket_name.clone();
@@ -190,15 +223,22 @@ impl StorageTransfer {
                 async move {
                     let data = source.download(&bucket_name, &name).await?;
                     let size = data.len();
+                    let sha256 = hex::encode(Sha256::digest(&data));
 
                     let file_path = bucket_dir.join(&name);
                     if let Some(parent) = file_path.parent() {
                         fs::create_dir_all(parent).await?;
                     }
-                    fs::write(&file_path, &data).await?;
+                    match encryption {
+                        Some(encryption) => {
+                            fs::write(encrypted_path(&file_path), encryption.encrypt(&data)?)
+                                .await?
+                        }
+                        None => fs::write(&file_path, &data).await?,
+                    }
 
                     pb.inc(1);
-                    Ok(size)
+                    Ok((format!("{}/{}", bucket_name, name), size, sha256))
                 }
             })
             .buffer_unordered(self.parallel)
@@ -209,9 +249,10 @@ impl StorageTransfer {
 
         for result in results {
             match result {
-                Ok(size) => {
+                Ok((object, size, sha256)) => {
                     stats.objects += 1;
                     stats.bytes += size;
+                    stats.hashes.insert(object, sha256);
                 }
                 Err(e) => {
                     stats.errors += 1;
@@ -230,6 +271,10 @@ pub struct SyncStats {
     pub objects: usize,
     pub bytes: usize,
     pub errors: usize,
+    /// Objects skipped because they changed after the database snapshot
+    pub late: Vec<LateObject>,
+    /// SHA-256 of each downloaded object's contents, as `bucket/path` → hash
+    pub hashes: BTreeMap<String, String>,
 }
 
 impl std::fmt::Display for SyncStats {
@@ -244,11 +289,14 @@ impl std::fmt::Display for SyncStats {
         if self.errors > 0 {
             write!(f, " ({} errors)", self.errors)?;
         }
+        if !self.late.is_empty() {
+            write!(f, " ({} changed after snapshot)", self.late.len())?;
+        }
         Ok(())
     }
 }
 
-fn human_bytes(bytes: usize) -> String {
+pub fn human_bytes(bytes: usize) -> String {
     const KB: usize = 1024;
     const MB: usize = KB * 1024;
     const GB: usize = MB * 1024;